@@ -0,0 +1,17 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use lightws::frame::decode_all;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(frames) = decode_all(data) {
+        let mut end = 0;
+        for (head, range) in frames {
+            // payloads are in order and within the buffer
+            assert!(range.start >= end + 2);
+            assert!(range.end <= data.len());
+            assert_eq!((range.end - range.start) as u64, head.length.to_num());
+            end = range.end;
+        }
+    }
+});
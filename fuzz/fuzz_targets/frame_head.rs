@@ -0,0 +1,15 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use lightws::frame::FrameHead;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok((head, n)) = FrameHead::decode(data) {
+        assert!(n <= data.len());
+
+        // a parsed head could always be encoded again
+        let mut buf = [0_u8; 14];
+        assert_eq!(head.encode(&mut buf), Ok(n));
+        assert_eq!(FrameHead::decode(&buf[..n]), Ok((head, n)));
+    }
+});
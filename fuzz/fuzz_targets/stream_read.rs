@@ -0,0 +1,47 @@
+#![no_main]
+
+use std::io::{Read, Result};
+
+use libfuzzer_sys::fuzz_target;
+use lightws::role::Server;
+use lightws::stream::Stream;
+
+/// Return at most `limit` bytes per read.
+struct LimitReader<'a> {
+    buf: &'a [u8],
+    limit: usize,
+}
+
+impl Read for LimitReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let n = buf.len().min(self.limit).min(self.buf.len());
+        buf[..n].copy_from_slice(&self.buf[..n]);
+        self.buf = &self.buf[n..];
+        Ok(n)
+    }
+}
+
+fuzz_target!(|data: &[u8]| {
+    // the first two bytes choose the read limit and buffer size
+    let [limit, size, data @ ..] = data else {
+        return;
+    };
+
+    let io = LimitReader {
+        buf: data,
+        limit: *limit as usize + 1,
+    };
+    let mut stream = Stream::builder(io, Server).max_message_size(0x10000).build();
+    let mut buf = vec![0_u8; *size as usize + 1];
+
+    // each read either makes progress or ends
+    let mut total = 0;
+    for _ in 0..=data.len() * 2 + 2 {
+        match stream.read(&mut buf) {
+            Ok(0) if stream.is_read_end() => break,
+            Ok(n) => total += n,
+            Err(_) => break,
+        }
+    }
+    assert!(total <= data.len());
+});
@@ -9,13 +9,13 @@ pub(crate) use writer::Writer;
 #[inline]
 pub(crate) const unsafe fn slice<T>(slice: &[T], beg: usize, end: usize) -> &[T] {
     let ptr = slice.as_ptr().add(beg);
-    &*std::ptr::slice_from_raw_parts(ptr, end - beg)
+    &*core::ptr::slice_from_raw_parts(ptr, end - beg)
 }
 
 #[inline]
 pub(crate) const unsafe fn slice_mut<T>(slice: &mut [T], beg: usize, end: usize) -> &mut [T] {
     let ptr = slice.as_mut_ptr().add(beg);
-    &mut *std::ptr::slice_from_raw_parts_mut(ptr, end - beg)
+    &mut *core::ptr::slice_from_raw_parts_mut(ptr, end - beg)
 }
 
 #[inline]
@@ -39,7 +39,7 @@ mod test {
 
     #[test]
     fn unsafe_slice() {
-        let buf: Vec<u8> = std::iter::repeat(rand::random::<u8>()).take(1024).collect();
+        let buf: Vec<u8> = std::iter::repeat_n(rand::random::<u8>(), 1024).collect();
         let mut buf2 = buf.clone();
 
         macro_rules! s {
@@ -60,7 +60,7 @@ mod test {
 
     #[test]
     fn unsafe_slice_to_array() {
-        let buf: Vec<u8> = std::iter::repeat(rand::random::<u8>()).take(4096).collect();
+        let buf: Vec<u8> = std::iter::repeat_n(rand::random::<u8>(), 4096).collect();
 
         macro_rules! s {
             ($beg: expr, $len: expr) => {
@@ -1,6 +1,6 @@
 use super::{slice, slice_mut};
 
-/// Buffer on stack.
+/// Buffer on stack, `N <= 255`.
 #[derive(Debug, Clone, Copy)]
 pub struct Store<const N: usize> {
     rd: u8,
@@ -10,8 +10,13 @@ pub struct Store<const N: usize> {
 
 #[allow(unused)]
 impl<const N: usize> Store<N> {
+    /// Positions must be able to address the whole buffer.
+    const CAPACITY_CHECK: () = assert!(N <= u8::MAX as usize);
+
     #[inline]
     pub const fn new() -> Self {
+        #[allow(clippy::let_unit_value)]
+        let _ = Self::CAPACITY_CHECK;
         Self {
             rd: 0,
             wr: 0,
@@ -19,11 +24,20 @@ impl<const N: usize> Store<N> {
         }
     }
 
-    #[inline]
-    pub fn new_with_data(data: &[u8]) -> Self {
+    /// Create a store with data, see [`try_new_with_data`](Self::try_new_with_data)
+    /// for a checked version.
+    ///
+    /// # Safety
+    ///
+    /// Caller must ensure `data.len() <= N`.
+    #[inline]
+    pub unsafe fn new_with_data(data: &[u8]) -> Self {
+        #[allow(clippy::let_unit_value)]
+        let _ = Self::CAPACITY_CHECK;
+        debug_assert!(data.len() <= N);
         let mut buf = [0_u8; N];
         unsafe {
-            std::ptr::copy_nonoverlapping(data.as_ptr(), buf.as_mut_ptr(), data.len());
+            core::ptr::copy_nonoverlapping(data.as_ptr(), buf.as_mut_ptr(), data.len());
         }
         Self {
             rd: 0,
@@ -32,10 +46,24 @@ impl<const N: usize> Store<N> {
         }
     }
 
+    /// Checked version of [`new_with_data`](Self::new_with_data),
+    /// return `None` if `data.len() > N`.
+    #[inline]
+    pub fn try_new_with_data(data: &[u8]) -> Option<Self> {
+        // length is checked
+        (data.len() <= N).then(|| unsafe { Self::new_with_data(data) })
+    }
+
+    /// Replace all data in the store.
+    ///
+    /// # Safety
+    ///
+    /// Caller must ensure `data.len() <= N`.
     #[inline]
-    pub fn replace_with_data(&mut self, data: &[u8]) {
+    pub unsafe fn replace_with_data(&mut self, data: &[u8]) {
+        debug_assert!(data.len() <= N);
         unsafe {
-            std::ptr::copy_nonoverlapping(data.as_ptr(), self.buf.as_mut_ptr(), data.len());
+            core::ptr::copy_nonoverlapping(data.as_ptr(), self.buf.as_mut_ptr(), data.len());
         }
         self.rd = 0;
         self.wr = data.len() as u8;
@@ -103,7 +131,7 @@ mod test {
 
     #[test]
     fn unsafe_store() {
-        let mut store = Store::<14>::new_with_data(b"Hello, ");
+        let mut store = unsafe { Store::<14>::new_with_data(b"Hello, ") };
         assert_eq!(store.read(), b"Hello, ");
         store.write().copy_from_slice(b"World!!");
         store.advance_wr_pos(7);
@@ -114,7 +142,7 @@ mod test {
         store.reset();
         assert_eq!(store.read(), []);
 
-        store.replace_with_data(b"hello, world!!");
+        unsafe { store.replace_with_data(b"hello, world!!") };
         assert_eq!(store.read(), b"hello, world!!");
         store.advance_rd_pos(7);
         assert_eq!(store.read(), b"world!!");
@@ -122,4 +150,15 @@ mod test {
         store.reset();
         assert_eq!(store.read(), []);
     }
+
+    #[test]
+    #[should_panic]
+    #[cfg(debug_assertions)]
+    fn unsafe_store_overflow() { let _ = unsafe { Store::<4>::new_with_data(b"12345") }; }
+
+    #[test]
+    fn checked_store() {
+        assert!(Store::<4>::try_new_with_data(b"1234").is_some());
+        assert!(Store::<4>::try_new_with_data(b"12345").is_none());
+    }
 }
@@ -1,5 +1,5 @@
-use std::marker::PhantomData;
-use std::ptr::copy_nonoverlapping;
+use core::marker::PhantomData;
+use core::ptr::copy_nonoverlapping;
 
 pub struct Writer<'a, T> {
     ptr: *mut T,
@@ -59,7 +59,7 @@ impl<'a, u8> Writer<'a, u8> {
     pub fn write_or_err<F, E>(&mut self, src: &[u8], f: F) -> Result<usize, E>
     where
         F: Fn() -> E,
-        E: std::error::Error,
+        E: core::error::Error,
     {
         if self.remaining() < src.len() {
             Err(f())
@@ -81,7 +81,7 @@ mod test {
 
         for i in (1..=1024).filter(|x| 4096 % x == 0) {
             let n = 4096 / i;
-            let data: Vec<u8> = std::iter::repeat(rand::random::<u8>()).take(i).collect();
+            let data: Vec<u8> = std::iter::repeat_n(rand::random::<u8>(), i).collect();
 
             let mut writer = Writer::new(&mut buf);
             let mut write_n = 0;
@@ -8,8 +8,9 @@ use super::detail;
 use super::Endpoint;
 
 use crate::role::ClientRole;
-use crate::handshake::{HttpHeader, Request, Response};
+use crate::handshake::{HttpHeader, Request, Response, OwnedResponse};
 use crate::handshake::{new_sec_key, derive_accept_key};
+use crate::handshake::Negotiated;
 use crate::error::HandshakeError;
 use crate::stream::Stream;
 
@@ -37,6 +38,29 @@ impl<IO: AsyncRead + AsyncWrite + Unpin, Role: ClientRole> Endpoint<IO, Role> {
         buf: &mut [u8],
         response: &mut Response<'h, 'b, N>,
     ) -> Result<usize> {
+        Self::recv_response_parsed_async(io, buf, response)
+            .await
+            .map(|(_, n)| n)
+    }
+
+    /// Async version of [`recv_response_safe`](Self::recv_response_safe).
+    pub async fn recv_response_safe_async(
+        io: &mut IO,
+        buf: &mut [u8],
+    ) -> Result<(OwnedResponse, usize)> {
+        let mut other_headers = HttpHeader::new_storage();
+        let mut response = Response::new_storage(&mut other_headers);
+        // this is safe since response is dropped before buf is used again.
+        let n = unsafe { Self::recv_response_async(io, buf, &mut response) }.await?;
+        Ok((OwnedResponse::from(&response), n))
+    }
+
+    /// Async version of [`recv_response_parsed`](Self::recv_response_parsed).
+    async unsafe fn recv_response_parsed_async<'h, 'b: 'h, const N: usize>(
+        io: &mut IO,
+        buf: &mut [u8],
+        response: &mut Response<'h, 'b, N>,
+    ) -> Result<(usize, usize)> {
         poll_fn(|cx| {
             detail::recv_response(io, buf, response, |io, buf| {
                 let mut buf = ReadBuf::new(buf);
@@ -50,29 +74,133 @@ impl<IO: AsyncRead + AsyncWrite + Unpin, Role: ClientRole> Endpoint<IO, Role> {
 
     /// Async version of [`connect`](Self::connect).
     pub async fn connect_async(
-        mut io: IO,
+        io: IO,
         buf: &mut [u8],
         host: &str,
         path: &str,
     ) -> Result<Stream<IO, Role>> {
+        Self::connect_with_async(io, buf, host, path, |_| {}).await
+    }
+
+    /// Async version of [`connect_with`](Self::connect_with).
+    pub async fn connect_with_async<F>(
+        io: IO,
+        buf: &mut [u8],
+        host: &str,
+        path: &str,
+        on_headers: F,
+    ) -> Result<Stream<IO, Role>>
+    where
+        F: FnOnce(&[HttpHeader]),
+    {
+        let mut other_headers = HttpHeader::new_storage();
+        Self::handshake_async(io, buf, host, path, &[], &mut other_headers, on_headers).await
+    }
+
+    /// Async version of [`connect_with_storage`](Self::connect_with_storage).
+    pub async fn connect_with_storage_async<'b, const N: usize>(
+        io: IO,
+        buf: &'b mut [u8],
+        host: &str,
+        path: &str,
+        other_headers: &mut [HttpHeader<'b>; N],
+    ) -> Result<Stream<IO, Role>> {
+        Self::handshake_async(io, buf, host, path, &[], other_headers, |_| {}).await
+    }
+
+    /// Async version of [`connect_with_requirements`](Self::connect_with_requirements).
+    pub async fn connect_with_requirements_async(
+        io: IO,
+        buf: &mut [u8],
+        host: &str,
+        path: &str,
+        protocols: &[&str],
+        extensions: &[&str],
+    ) -> Result<Stream<IO, Role>> {
+        let (protocol, extension) = (protocols.join(", "), extensions.join(", "));
+        let offer = detail::offer_headers(&protocol, &extension);
+
+        let mut other_headers = HttpHeader::new_storage();
+        let stream =
+            Self::handshake_async(io, buf, host, path, &offer, &mut other_headers, |_| {}).await?;
+
+        detail::check_requirements(stream.negotiated(), protocols, extensions)?;
+        Ok(stream)
+    }
+
+    async fn handshake_async<'b, const N: usize, F>(
+        mut io: IO,
+        buf: &'b mut [u8],
+        host: &str,
+        path: &str,
+        offer: &[HttpHeader<'_>],
+        other_headers: &mut [HttpHeader<'b>; N],
+        on_headers: F,
+    ) -> Result<Stream<IO, Role>>
+    where
+        F: FnOnce(&[HttpHeader]),
+    {
         let sec_key = new_sec_key();
         let sec_accept = derive_accept_key(&sec_key);
 
         // send
-        let request = Request::new(path.as_bytes(), host.as_bytes(), &sec_key);
+        let mut offer = offer.to_vec();
+        let request =
+            Request::new_with_headers(path.as_bytes(), host.as_bytes(), &sec_key, &mut offer);
         let _ = Self::send_request_async(&mut io, buf, &request).await?;
 
         // recv
-        let mut other_headers = HttpHeader::new_storage();
-        let mut response = Response::new_storage(&mut other_headers);
+        let mut response = Response::<N>::new_custom_storage(other_headers);
         // this is safe since we do not modify response.
-        let _ = unsafe { Self::recv_response_async(&mut io, buf, &mut response) }.await?;
+        let (parsed_n, read_n) =
+            unsafe { Self::recv_response_parsed_async(&mut io, buf, &mut response) }.await?;
 
         // check
         if response.sec_accept != sec_accept {
             return Err(HandshakeError::SecWebSocketAccept.into());
         }
 
-        Ok(Stream::new(io, Role::new()))
+        on_headers(response.other_headers);
+
+        let negotiated = Negotiated::from_headers(response.other_headers);
+
+        let mut stream =
+            Stream::new_with_leftover(io, Role::new(), Box::from(&buf[parsed_n..read_n]));
+        stream.set_negotiated(negotiated);
+        Ok(stream)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::role::{Client, Server};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn async_client_connect() {
+        let (local, remote) = tokio::io::duplex(0x100);
+
+        let server = tokio::spawn(async move {
+            let mut buf = vec![0u8; 1024];
+            let stream = Endpoint::<_, Server>::accept_async(remote, &mut buf, "example.com", "/")
+                .await
+                .unwrap();
+            let mut stream = stream.guard();
+            stream.write_all(b"hello").await.unwrap();
+        });
+
+        let mut buf = vec![0u8; 1024];
+        let stream = Endpoint::<_, Client>::connect_async(local, &mut buf, "example.com", "/")
+            .await
+            .unwrap();
+        assert_eq!(stream.negotiated().protocol(), None);
+
+        let mut stream = stream.guard();
+        let mut data = [0u8; 5];
+        stream.read_exact(&mut data).await.unwrap();
+        assert_eq!(&data, b"hello");
+
+        server.await.unwrap();
     }
 }
@@ -8,8 +8,9 @@ use super::detail;
 use super::Endpoint;
 
 use crate::role::ServerRole;
-use crate::handshake::{HttpHeader, Request, Response};
+use crate::handshake::{HttpHeader, Request, Response, Negotiated, OwnedRequest};
 use crate::handshake::derive_accept_key;
+use crate::handshake::{HTTP_FORBIDDEN, HTTP_UPGRADE_REQUIRED};
 use crate::error::HandshakeError;
 use crate::stream::Stream;
 
@@ -20,14 +21,46 @@ impl<IO: AsyncRead + AsyncWrite + Unpin, Role: ServerRole> Endpoint<IO, Role> {
         buf: &mut [u8],
         response: &Response<'_, '_, N>,
     ) -> Result<usize> {
+        Self::send_response_parts_async(io, buf, response.sec_accept, response.other_headers).await
+    }
+
+    /// Async version of [`send_response_parts`](Self::send_response_parts).
+    async fn send_response_parts_async(
+        io: &mut IO,
+        buf: &mut [u8],
+        sec_accept: &[u8],
+        other_headers: &[HttpHeader<'_>],
+    ) -> Result<usize> {
+        poll_fn(|cx| {
+            detail::send_response(io, buf, sec_accept, other_headers, |io, buf| {
+                Pin::new(io).poll_write(cx, buf)
+            })
+        })
+        .await
+    }
+
+    /// Async version of [`send_upgrade_required`](Self::send_upgrade_required).
+    pub async fn send_upgrade_required_async(io: &mut IO) -> Result<usize> {
         poll_fn(|cx| {
-            detail::send_response(io, buf, response, |io, buf| {
+            detail::send_reject(io, HTTP_UPGRADE_REQUIRED, |io, buf| {
                 Pin::new(io).poll_write(cx, buf)
             })
         })
         .await
     }
 
+    /// Async version of [`send_reject`](Self::send_reject).
+    pub async fn send_reject_async(
+        io: &mut IO,
+        buf: &mut [u8],
+        status: &[u8],
+        body: &[u8],
+    ) -> Result<usize> {
+        let n = Response::encode_reject(buf, status, body)?;
+        let buf = &buf[..n];
+        poll_fn(|cx| detail::send_reject(io, buf, |io, buf| Pin::new(io).poll_write(cx, buf))).await
+    }
+
     /// Async version of [`recv_request`](Self::recv_request).
     ///
     /// # Safety
@@ -39,6 +72,29 @@ impl<IO: AsyncRead + AsyncWrite + Unpin, Role: ServerRole> Endpoint<IO, Role> {
         buf: &mut [u8],
         request: &mut Request<'h, 'b, N>,
     ) -> Result<usize> {
+        Self::recv_request_parsed_async(io, buf, request)
+            .await
+            .map(|(_, n)| n)
+    }
+
+    /// Async version of [`recv_request_safe`](Self::recv_request_safe).
+    pub async fn recv_request_safe_async(
+        io: &mut IO,
+        buf: &mut [u8],
+    ) -> Result<(OwnedRequest, usize)> {
+        let mut other_headers = HttpHeader::new_storage();
+        let mut request = Request::new_storage(&mut other_headers);
+        // this is safe since request is dropped before buf is used again.
+        let n = unsafe { Self::recv_request_async(io, buf, &mut request) }.await?;
+        Ok((OwnedRequest::from(&request), n))
+    }
+
+    /// Async version of [`recv_request_parsed`](Self::recv_request_parsed).
+    async unsafe fn recv_request_parsed_async<'h, 'b: 'h, const N: usize>(
+        io: &mut IO,
+        buf: &mut [u8],
+        request: &mut Request<'h, 'b, N>,
+    ) -> Result<(usize, usize)> {
         poll_fn(|cx| {
             detail::recv_request(io, buf, request, |io, buf| {
                 let mut buf = ReadBuf::new(buf);
@@ -52,31 +108,148 @@ impl<IO: AsyncRead + AsyncWrite + Unpin, Role: ServerRole> Endpoint<IO, Role> {
 
     /// Async version of [`accept`](Self::accept).
     pub async fn accept_async(
-        mut io: IO,
+        io: IO,
         buf: &mut [u8],
         host: &str,
         path: &str,
     ) -> Result<Stream<IO, Role>> {
-        // recv
+        Self::accept_with_async(io, buf, |request| {
+            detail::check_request(request, host, path)
+        })
+        .await
+    }
+
+    /// Async version of [`accept_with_origin`](Self::accept_with_origin).
+    pub async fn accept_with_origin_async(
+        io: IO,
+        buf: &mut [u8],
+        host: &str,
+        path: &str,
+        allowed_origins: &[&str],
+    ) -> Result<Stream<IO, Role>> {
         let mut other_headers = HttpHeader::new_storage();
-        let mut request = Request::new_storage(&mut other_headers);
+        let validate = |request: &Request, _: &mut [HttpHeader]| {
+            detail::check_request(request, host, path)?;
+            detail::check_origin(request, allowed_origins).map(|_| 0)
+        };
+        Self::accept_checked_async(io, buf, &mut other_headers, &mut [], validate, true).await
+    }
+
+    /// Async version of [`accept_with`](Self::accept_with).
+    pub async fn accept_with_async<F>(
+        io: IO,
+        buf: &mut [u8],
+        validate: F,
+    ) -> Result<Stream<IO, Role>>
+    where
+        F: FnOnce(&Request) -> std::result::Result<(), HandshakeError>,
+    {
+        let mut other_headers = HttpHeader::new_storage();
+        Self::accept_with_storage_async(io, buf, &mut other_headers, &mut [], |request, _| {
+            validate(request).map(|_| 0)
+        })
+        .await
+    }
+
+    /// Async version of [`accept_with_storage`](Self::accept_with_storage).
+    pub async fn accept_with_storage_async<'b, 's, const N: usize, F>(
+        io: IO,
+        buf: &'b mut [u8],
+        other_headers: &mut [HttpHeader<'b>; N],
+        response_headers: &mut [HttpHeader<'s>],
+        negotiate: F,
+    ) -> Result<Stream<IO, Role>>
+    where
+        F: FnOnce(
+            &Request<'_, '_, N>,
+            &mut [HttpHeader<'s>],
+        ) -> std::result::Result<usize, HandshakeError>,
+    {
+        Self::accept_checked_async(io, buf, other_headers, response_headers, negotiate, false).await
+    }
+
+    /// Async version of [`accept_checked`](Self::accept_checked).
+    async fn accept_checked_async<'b, 's, const N: usize, F>(
+        mut io: IO,
+        buf: &'b mut [u8],
+        other_headers: &mut [HttpHeader<'b>; N],
+        response_headers: &mut [HttpHeader<'s>],
+        negotiate: F,
+        reject_origin: bool,
+    ) -> Result<Stream<IO, Role>>
+    where
+        F: FnOnce(
+            &Request<'_, '_, N>,
+            &mut [HttpHeader<'s>],
+        ) -> std::result::Result<usize, HandshakeError>,
+    {
+        // recv
+        let mut request = Request::<N>::new_custom_storage(other_headers);
         // this is safe since we do not modify request.
-        let _ = unsafe { Self::recv_request_async(&mut io, buf, &mut request) }.await?;
+        let (parsed_n, read_n) =
+            match unsafe { Self::recv_request_parsed_async(&mut io, buf, &mut request) }.await {
+                Ok(x) => x,
+                Err(e) => {
+                    // tell the client which version is supported
+                    if detail::is_unsupported_version(&e) {
+                        let _ = Self::send_upgrade_required_async(&mut io).await;
+                    }
+                    return Err(e);
+                }
+            };
 
-        // check
-        if request.host != host.as_bytes() {
-            return Err(HandshakeError::Manual("host mismatch").into());
-        }
+        // check, reject a disallowed origin
+        let selected_n = match negotiate(&request, response_headers) {
+            Ok(n) => n,
+            Err(e) => {
+                if reject_origin && e == HandshakeError::Origin {
+                    let _ = poll_fn(|cx| {
+                        detail::send_reject(&mut io, HTTP_FORBIDDEN, |io, buf| {
+                            Pin::new(io).poll_write(cx, buf)
+                        })
+                    })
+                    .await;
+                }
+                return Err(e.into());
+            }
+        };
+        let selected = &response_headers[..selected_n];
 
-        if request.path != path.as_bytes() {
-            return Err(HandshakeError::Manual("path mismatch").into());
-        }
+        // data read past the request, buffer is reused below
+        let leftover = Box::from(&buf[parsed_n..read_n]);
 
         // send
         let sec_accept = derive_accept_key(request.sec_key);
-        let response = Response::new(&sec_accept);
-        let _ = Self::send_response_async(&mut io, buf, &response).await?;
+        let _ = Self::send_response_parts_async(&mut io, buf, &sec_accept, selected).await?;
+
+        let mut stream = Stream::new_with_leftover(io, Role::new(), leftover);
+        stream.set_negotiated(Negotiated::from_headers(selected));
+        Ok(stream)
+    }
+
+    /// Async version of [`accept_prepared`](Self::accept_prepared).
+    pub async fn accept_prepared_async<const N: usize>(
+        io: IO,
+        request: &Request<'_, '_, N>,
+        leftover: &[u8],
+        buf: &mut [u8],
+    ) -> Result<Stream<IO, Role>> {
+        Self::accept_prepared_with_headers_async(io, request, leftover, &[], buf).await
+    }
+
+    /// Async version of [`accept_prepared_with_headers`](Self::accept_prepared_with_headers).
+    pub async fn accept_prepared_with_headers_async<const N: usize>(
+        mut io: IO,
+        request: &Request<'_, '_, N>,
+        leftover: &[u8],
+        other_headers: &[HttpHeader<'_>],
+        buf: &mut [u8],
+    ) -> Result<Stream<IO, Role>> {
+        let sec_accept = derive_accept_key(request.sec_key);
+        let _ = Self::send_response_parts_async(&mut io, buf, &sec_accept, other_headers).await?;
 
-        Ok(Stream::new(io, Role::new()))
+        let mut stream = Stream::new_with_leftover(io, Role::new(), Box::from(leftover));
+        stream.set_negotiated(Negotiated::from_headers(other_headers));
+        Ok(stream)
     }
 }
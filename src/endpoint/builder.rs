@@ -0,0 +1,268 @@
+use std::io::{Read, Write, Result};
+
+use super::detail;
+use super::Endpoint;
+
+use crate::role::{ClientRole, ServerRole};
+use crate::handshake::{HttpHeader, MAX_ALLOW_HEADERS};
+use crate::stream::{Stream, StreamBuilder};
+
+/// Default size of the handshake buffer, 1024.
+pub const DEFAULT_BUFFER_SIZE: usize = 1024;
+
+/// Endpoint with a reusable handshake buffer and stream settings.
+///
+/// The const generic paramater represents the max decode header size.
+///
+/// Example:
+///
+/// ```no_run
+/// use std::net::TcpListener;
+/// use lightws::role::Server;
+/// use lightws::endpoint::EndpointBuilder;
+/// fn run() -> std::io::Result<()> {
+///     let listener = TcpListener::bind("127.0.0.1:8080")?;
+///     let mut builder = EndpointBuilder::new()
+///         .max_message_size(4096)
+///         .strict(true)
+///         .auto_pong(true);
+///     for tcp in listener.incoming() {
+///         let ws = builder.accept::<_, Server>(tcp?, "example.com", "/ws")?;
+///     }
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct EndpointBuilder<const N: usize = MAX_ALLOW_HEADERS> {
+    buf: Vec<u8>,
+    stream: StreamBuilder<(), ()>,
+}
+
+impl EndpointBuilder {
+    /// Constructor, with [`MAX_ALLOW_HEADERS`] and default settings.
+    #[inline]
+    pub fn new() -> Self { Self::new_custom() }
+}
+
+impl Default for EndpointBuilder {
+    fn default() -> Self { Self::new() }
+}
+
+impl<const N: usize> EndpointBuilder<N> {
+    /// Constructor, with default settings.
+    #[inline]
+    pub fn new_custom() -> Self {
+        Self {
+            buf: vec![0; DEFAULT_BUFFER_SIZE],
+            stream: StreamBuilder::new((), ()),
+        }
+    }
+
+    /// Set the size of the handshake buffer, default is [`DEFAULT_BUFFER_SIZE`].
+    #[inline]
+    pub fn buffer_size(mut self, size: usize) -> Self {
+        self.buf.resize(size, 0);
+        self
+    }
+
+    /// Set the max payload length of an incoming data frame, default is unlimited.
+    ///
+    /// See [`Stream::set_max_message_size`].
+    #[inline]
+    pub fn max_message_size(mut self, size: u64) -> Self {
+        self.stream = self.stream.max_message_size(size);
+        self
+    }
+
+    /// Reject a frame length not in its shortest form,
+    /// see [`Stream::set_strict_length`].
+    #[inline]
+    pub fn strict_length(mut self, strict: bool) -> Self {
+        self.stream = self.stream.strict_length(strict);
+        self
+    }
+
+    /// Reject a frame without the expected mask,
+    /// see [`Stream::set_strict_mask`].
+    #[inline]
+    pub fn strict_mask(mut self, strict: bool) -> Self {
+        self.stream = self.stream.strict_mask(strict);
+        self
+    }
+
+    /// Reject an illegal control frame,
+    /// see [`Stream::set_strict_control`].
+    #[inline]
+    pub fn strict_control(mut self, strict: bool) -> Self {
+        self.stream = self.stream.strict_control(strict);
+        self
+    }
+
+    /// Enable or disable all the strict options,
+    /// see [`Stream::set_strict`].
+    #[inline]
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.stream = self.stream.strict(strict);
+        self
+    }
+
+    /// Accept `Text` messages, see [`Stream::set_accept_text`].
+    #[inline]
+    pub fn accept_text(mut self, enable: bool) -> Self {
+        self.stream = self.stream.accept_text(enable);
+        self
+    }
+
+    /// Answer pings automatically, see [`Stream::set_auto_pong`].
+    #[inline]
+    pub fn auto_pong(mut self, enable: bool) -> Self {
+        self.stream = self.stream.auto_pong(enable);
+        self
+    }
+
+    /// Write new messages as `Text` instead of `Binary`,
+    /// see [`Stream::write_opcode`].
+    #[inline]
+    pub fn text(mut self, enable: bool) -> Self {
+        self.stream = self.stream.text(enable);
+        self
+    }
+
+    /// Perform a client handshake, return a configured websocket stream.
+    ///
+    /// See [`Endpoint::connect`].
+    pub fn connect<IO, Role>(&mut self, io: IO, host: &str, path: &str) -> Result<Stream<IO, Role>>
+    where
+        IO: Read + Write,
+        Role: ClientRole,
+    {
+        let mut other_headers = HttpHeader::new_custom_storage::<N>();
+        let stream =
+            Endpoint::connect_with_storage(io, &mut self.buf, host, path, &mut other_headers)?;
+        Ok(self.stream.configure(stream))
+    }
+
+    /// Perform a server handshake, return a configured websocket stream.
+    ///
+    /// See [`Endpoint::accept`].
+    pub fn accept<IO, Role>(&mut self, io: IO, host: &str, path: &str) -> Result<Stream<IO, Role>>
+    where
+        IO: Read + Write,
+        Role: ServerRole,
+    {
+        let mut other_headers = HttpHeader::new_custom_storage::<N>();
+        let stream = Endpoint::accept_with_storage(
+            io,
+            &mut self.buf,
+            &mut other_headers,
+            &mut [],
+            |request, _| detail::check_request(request, host, path).map(|_| 0),
+        )?;
+        Ok(self.stream.configure(stream))
+    }
+}
+
+cfg_if::cfg_if! {
+    if #[cfg(feature = "async")] {
+        use std::time::Duration;
+        use tokio::io::{AsyncRead, AsyncWrite};
+
+        impl<const N: usize> EndpointBuilder<N> {
+            /// Send a ping once the stream is idle for the given period,
+            /// see [`Stream::set_ping_interval`].
+            #[inline]
+            pub fn ping_interval(mut self, period: Duration) -> Self {
+                self.stream = self.stream.ping_interval(period);
+                self
+            }
+
+            /// Async version of [`connect`](Self::connect).
+            pub async fn connect_async<IO, Role>(
+                &mut self,
+                io: IO,
+                host: &str,
+                path: &str,
+            ) -> Result<Stream<IO, Role>>
+            where
+                IO: AsyncRead + AsyncWrite + Unpin,
+                Role: ClientRole,
+            {
+                let mut other_headers = HttpHeader::new_custom_storage::<N>();
+                let stream = Endpoint::connect_with_storage_async(
+                    io,
+                    &mut self.buf,
+                    host,
+                    path,
+                    &mut other_headers,
+                )
+                .await?;
+                Ok(self.stream.configure(stream))
+            }
+
+            /// Async version of [`accept`](Self::accept).
+            pub async fn accept_async<IO, Role>(
+                &mut self,
+                io: IO,
+                host: &str,
+                path: &str,
+            ) -> Result<Stream<IO, Role>>
+            where
+                IO: AsyncRead + AsyncWrite + Unpin,
+                Role: ServerRole,
+            {
+                let mut other_headers = HttpHeader::new_custom_storage::<N>();
+                let stream = Endpoint::accept_with_storage_async(
+                    io,
+                    &mut self.buf,
+                    &mut other_headers,
+                    &mut [],
+                    |request, _| detail::check_request(request, host, path).map(|_| 0),
+                )
+                .await?;
+                Ok(self.stream.configure(stream))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::test::*;
+    use crate::frame::OpCode;
+    use crate::role::{Client, Server};
+
+    #[test]
+    fn builder_accept_connect() {
+        let mut builder = EndpointBuilder::<8>::new_custom()
+            .buffer_size(512)
+            .max_message_size(64)
+            .strict(true)
+            .auto_pong(true)
+            .text(true);
+
+        for _ in 0..4 {
+            let rw = LimitReadWriter {
+                rbuf: Vec::from(REQUEST),
+                wbuf: Vec::new(),
+                rlimit: 16,
+                wlimit: 16,
+                cursor: 0,
+            };
+            let stream = builder
+                .accept::<_, Server>(rw, "www.example.com", "/ws")
+                .unwrap();
+            assert_eq!(stream.as_ref().wbuf, RESPONSE);
+            assert_eq!(stream.max_message_size(), 64);
+            assert!(stream.is_strict() && stream.is_auto_pong());
+            assert_eq!(stream.write_opcode(), OpCode::Text);
+
+            let stream = builder
+                .connect::<_, Client>(MockServer::new(&[]), "example.com", "/")
+                .unwrap();
+            assert_eq!(stream.max_message_size(), 64);
+            assert!(stream.is_strict() && stream.is_auto_pong());
+            assert_eq!(stream.write_opcode(), OpCode::Text);
+        }
+    }
+}
@@ -5,8 +5,10 @@ use super::detail;
 use super::Endpoint;
 
 use crate::role::ClientRole;
-use crate::handshake::{HttpHeader, Request, Response};
+use crate::frame::new_mask_key;
+use crate::handshake::{HttpHeader, Request, Response, OwnedResponse};
 use crate::handshake::{new_sec_key, derive_accept_key};
+use crate::handshake::Negotiated;
 use crate::error::HandshakeError;
 use crate::stream::Stream;
 
@@ -45,6 +47,30 @@ impl<IO: Read + Write, Role: ClientRole> Endpoint<IO, Role> {
         buf: &mut [u8],
         response: &mut Response<'h, 'b, N>,
     ) -> Result<usize> {
+        Self::recv_response_parsed(io, buf, response).map(|(_, n)| n)
+    }
+
+    /// Safe version of [`recv_response`](Self::recv_response), return the received response
+    /// along with the number of bytes transmitted.
+    ///
+    /// Required fields are copied into [`OwnedResponse`], so the buffer
+    /// could be reused once it returns, while optional headers are dropped.
+    /// At most [`MAX_ALLOW_HEADERS`](crate::handshake::MAX_ALLOW_HEADERS) headers are decoded.
+    pub fn recv_response_safe(io: &mut IO, buf: &mut [u8]) -> Result<(OwnedResponse, usize)> {
+        let mut other_headers = HttpHeader::new_storage();
+        let mut response = Response::new_storage(&mut other_headers);
+        // this is safe since response is dropped before buf is used again.
+        let n = unsafe { Self::recv_response(io, buf, &mut response) }?;
+        Ok((OwnedResponse::from(&response), n))
+    }
+
+    /// Same as [`recv_response`](Self::recv_response), but also return
+    /// the number of bytes parsed as [`Response`].
+    unsafe fn recv_response_parsed<'h, 'b: 'h, const N: usize>(
+        io: &mut IO,
+        buf: &mut [u8],
+        response: &mut Response<'h, 'b, N>,
+    ) -> Result<(usize, usize)> {
         match detail::recv_response(io, buf, response, |io, buf| io.read(buf).into()) {
             Poll::Ready(x) => x,
             Poll::Pending => unreachable!(),
@@ -56,26 +82,163 @@ impl<IO: Read + Write, Role: ClientRole> Endpoint<IO, Role> {
     /// This function is a combination of [`send_request`](Self::send_request)
     /// and [`recv_response`](Self::recv_response), without accessing [`Response`].
     /// It will block until the handshake completes, or an error occurs.
-    pub fn connect(mut io: IO, buf: &mut [u8], host: &str, path: &str) -> Result<Stream<IO, Role>> {
+    pub fn connect(io: IO, buf: &mut [u8], host: &str, path: &str) -> Result<Stream<IO, Role>> {
+        Self::connect_with(io, buf, host, path, |_| {})
+    }
+
+    /// Same as [`connect`](Self::connect), but extra headers of the response
+    /// are passed to `on_headers` before they are dropped,
+    /// where needed values could be copied out.
+    pub fn connect_with<F>(
+        io: IO,
+        buf: &mut [u8],
+        host: &str,
+        path: &str,
+        on_headers: F,
+    ) -> Result<Stream<IO, Role>>
+    where
+        F: FnOnce(&[HttpHeader]),
+    {
+        let mut other_headers = HttpHeader::new_storage();
+        Self::handshake(
+            io,
+            buf,
+            host,
+            path,
+            &[],
+            None,
+            &mut other_headers,
+            on_headers,
+        )
+    }
+
+    /// Same as [`connect`](Self::connect), but decode response headers
+    /// into the provided storage, whose size is the max decode header size.
+    ///
+    /// Extra headers of the response are left in the storage,
+    /// unused slots remain empty.
+    pub fn connect_with_storage<'b, const N: usize>(
+        io: IO,
+        buf: &'b mut [u8],
+        host: &str,
+        path: &str,
+        other_headers: &mut [HttpHeader<'b>; N],
+    ) -> Result<Stream<IO, Role>> {
+        Self::handshake(io, buf, host, path, &[], None, other_headers, |_| {})
+    }
+
+    /// Same as [`connect`](Self::connect), but offer subprotocols and extensions,
+    /// and require the server to select one of each (if not empty).
+    ///
+    /// If a requirement is not met, the handshake fails with
+    /// `HandshakeError::Manual("subprotocol not negotiated")`
+    /// or `HandshakeError::Manual("extension not negotiated")`.
+    pub fn connect_with_requirements(
+        io: IO,
+        buf: &mut [u8],
+        host: &str,
+        path: &str,
+        protocols: &[&str],
+        extensions: &[&str],
+    ) -> Result<Stream<IO, Role>> {
+        let (protocol, extension) = (protocols.join(", "), extensions.join(", "));
+        let offer = detail::offer_headers(&protocol, &extension);
+
+        let mut other_headers = HttpHeader::new_storage();
+        let stream = Self::handshake(
+            io,
+            buf,
+            host,
+            path,
+            &offer,
+            None,
+            &mut other_headers,
+            |_| {},
+        )?;
+
+        detail::check_requirements(stream.negotiated(), protocols, extensions)?;
+        Ok(stream)
+    }
+
+    /// Same as [`connect`](Self::connect), but send a binary frame of `payload`
+    /// right after the request, with a single write if IO accepts it,
+    /// so that the server could receive them together.
+    ///
+    /// The request and the frame are encoded to the provided buffer,
+    /// which fails with `HandshakeError::NotEnoughCapacity` if it is not long enough.
+    /// Payload is masked with a new random key if `Role` holds one,
+    /// which is then kept by the returned stream.
+    pub fn connect_with_payload(
+        io: IO,
+        buf: &mut [u8],
+        host: &str,
+        path: &str,
+        payload: &[u8],
+    ) -> Result<Stream<IO, Role>> {
+        let mut other_headers = HttpHeader::new_storage();
+        Self::handshake(
+            io,
+            buf,
+            host,
+            path,
+            &[],
+            Some(payload),
+            &mut other_headers,
+            |_| {},
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn handshake<'b, const N: usize, F>(
+        mut io: IO,
+        buf: &'b mut [u8],
+        host: &str,
+        path: &str,
+        offer: &[HttpHeader<'_>],
+        payload: Option<&[u8]>,
+        other_headers: &mut [HttpHeader<'b>; N],
+        on_headers: F,
+    ) -> Result<Stream<IO, Role>>
+    where
+        F: FnOnce(&[HttpHeader]),
+    {
         let sec_key = new_sec_key();
         let sec_accept = derive_accept_key(&sec_key);
 
         // send
-        let request = Request::new(path.as_bytes(), host.as_bytes(), &sec_key);
-        let _ = Self::send_request(&mut io, buf, &request)?;
+        let mut offer = offer.to_vec();
+        let request =
+            Request::new_with_headers(path.as_bytes(), host.as_bytes(), &sec_key, &mut offer);
+        let mut role = Role::new();
+        match payload {
+            Some(payload) => {
+                role.set_mask_key(new_mask_key());
+                let n = detail::encode_request_with_frame(buf, &request, role.mask_key(), payload)?;
+                io.write_all(&buf[..n])?;
+            }
+            None => {
+                let _ = Self::send_request(&mut io, buf, &request)?;
+            }
+        }
 
         // recv
-        let mut other_headers = HttpHeader::new_storage();
-        let mut response = Response::new_storage(&mut other_headers);
+        let mut response = Response::<N>::new_custom_storage(other_headers);
         // this is safe since we do not modify response.
-        let _ = unsafe { Self::recv_response(&mut io, buf, &mut response) }?;
+        let (parsed_n, read_n) =
+            unsafe { Self::recv_response_parsed(&mut io, buf, &mut response) }?;
 
         // check
         if response.sec_accept != sec_accept {
             return Err(HandshakeError::SecWebSocketAccept.into());
         }
 
-        Ok(Stream::new(io, Role::new()))
+        on_headers(response.other_headers);
+
+        let negotiated = Negotiated::from_headers(response.other_headers);
+
+        let mut stream = Stream::new_with_leftover(io, role, Box::from(&buf[parsed_n..read_n]));
+        stream.set_negotiated(negotiated);
+        Ok(stream)
     }
 }
 
@@ -134,7 +297,6 @@ mod test {
 
             assert_eq!(recv_n, RESPONSE.len());
             assert_eq!(response.sec_accept, b"s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
-            drop(response);
             assert_eq!(&buf[..recv_n], RESPONSE);
         }
 
@@ -143,6 +305,32 @@ mod test {
         }
     }
 
+    #[test]
+    fn recv_upgrade_response_safe() {
+        let mut rw = LimitReadWriter {
+            rbuf: Vec::from(RESPONSE),
+            wbuf: Vec::new(),
+            rlimit: 1,
+            wlimit: 0,
+            cursor: 0,
+        };
+
+        let mut buf = vec![0u8; 1024];
+
+        let (response, recv_n) =
+            Endpoint::<_, Client>::recv_response_safe(&mut rw, &mut buf).unwrap();
+        assert_eq!(recv_n, RESPONSE.len());
+        assert_eq!(&buf[..recv_n], RESPONSE);
+
+        // the buffer is free to reuse
+        buf.fill(0);
+        assert_eq!(&*response.sec_accept, b"s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+
+        let mut encoded = vec![0u8; 1024];
+        let n = response.as_response().encode(&mut encoded).unwrap();
+        assert_eq!(&encoded[..n], RESPONSE);
+    }
+
     #[test]
     fn client_connect() {
         // use std::error::Error;
@@ -165,4 +353,224 @@ mod test {
             assert_eq!(*e, HandshakeError::SecWebSocketAccept);
         }
     }
+
+    #[test]
+    fn client_connect_negotiated() {
+        let mut buf = vec![0u8; 1024];
+
+        let io = MockServer::new(&[]);
+        let stream = Endpoint::<_, Client>::connect(io, &mut buf, "example.com", "/").unwrap();
+        let negotiated = stream.negotiated();
+        assert_eq!(negotiated.protocol(), None);
+        assert_eq!(negotiated.extensions(), None);
+        assert_eq!(negotiated.deflate(), None);
+        assert_eq!(stream.extensions(), None);
+
+        let io = MockServer::new(&[
+            (b"sec-websocket-protocol", b"chat"),
+            (
+                b"sec-websocket-extensions",
+                b"permessage-deflate; client_no_context_takeover",
+            ),
+        ]);
+        let stream = Endpoint::<_, Client>::connect(io, &mut buf, "example.com", "/").unwrap();
+        let negotiated = stream.negotiated();
+        assert_eq!(negotiated.protocol(), Some(b"chat".as_slice()));
+        assert_eq!(
+            negotiated.extensions(),
+            Some(b"permessage-deflate; client_no_context_takeover".as_slice())
+        );
+        assert!(negotiated.deflate().unwrap().client_no_context_takeover);
+        assert_eq!(stream.protocol(), negotiated.protocol());
+        assert_eq!(stream.extensions(), negotiated.extensions());
+
+        // the role takes negotiated parameters
+        use crate::role::{DeflateClient, DeflateRole};
+        let io = MockServer::new(&[(
+            b"sec-websocket-extensions",
+            b"permessage-deflate; client_no_context_takeover",
+        )]);
+        let stream =
+            Endpoint::<_, DeflateClient>::connect(io, &mut buf, "example.com", "/").unwrap();
+        assert!(stream.role().write_no_context_takeover());
+        assert!(!stream.role().read_no_context_takeover());
+    }
+
+    #[test]
+    fn client_connect_with_requirements() {
+        fn run(
+            headers: &[(&'static [u8], &'static [u8])],
+            protocols: &[&str],
+            extensions: &[&str],
+        ) -> Result<Vec<u8>> {
+            let mut buf = vec![0u8; 1024];
+            let mut io = MockServer::new(headers);
+            Endpoint::<_, Client>::connect_with_requirements(
+                &mut io,
+                &mut buf,
+                "example.com",
+                "/",
+                protocols,
+                extensions,
+            )?;
+            Ok(io.wbuf)
+        }
+
+        fn manual(e: std::io::Error) -> &'static str {
+            match e.source().unwrap().downcast_ref::<HandshakeError>() {
+                Some(HandshakeError::Manual(s)) => s,
+                _ => unreachable!(),
+            }
+        }
+
+        // nothing required
+        let request = run(&[], &[], &[]).unwrap();
+        for name in [
+            b"sec-websocket-protocol".as_slice(),
+            b"sec-websocket-extensions",
+        ] {
+            assert!(!request.windows(name.len()).any(|w| w == name));
+        }
+
+        // server omits the protocol
+        let e = run(&[], &["chat", "superchat"], &[]).unwrap_err();
+        assert_eq!(manual(e), "subprotocol not negotiated");
+
+        let e = run(&[(b"sec-websocket-protocol", b"other")], &["chat"], &[]).unwrap_err();
+        assert_eq!(manual(e), "subprotocol not negotiated");
+
+        let request = run(
+            &[(b"sec-websocket-protocol", b"superchat")],
+            &["chat", "superchat"],
+            &[],
+        )
+        .unwrap();
+        let offer = b"sec-websocket-protocol: chat, superchat\r\n";
+        assert!(request.windows(offer.len()).any(|w| w == offer));
+
+        // server omits the extension
+        let e = run(&[], &[], &["permessage-deflate"]).unwrap_err();
+        assert_eq!(manual(e), "extension not negotiated");
+
+        run(
+            &[(
+                b"sec-websocket-extensions",
+                b"Permessage-Deflate; client_no_context_takeover",
+            )],
+            &[],
+            &["x-custom", "permessage-deflate; client_max_window_bits"],
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn client_connect_with_payload() {
+        use crate::frame::{FrameReader, OpCode, Mask, apply_mask4};
+        use crate::role::StandardClient;
+
+        fn run<R: ClientRole>(payload: &[u8]) -> Vec<u8> {
+            let mut buf = vec![0u8; 1024];
+            let mut io = MockServer::new(&[]);
+            let mask = Endpoint::<_, R>::connect_with_payload(
+                &mut io,
+                &mut buf,
+                "example.com",
+                "/",
+                payload,
+            )
+            .unwrap()
+            .mask_key();
+
+            let mut storage = HttpHeader::new_storage();
+            let mut request = Request::new_storage(&mut storage);
+            let n = request.decode(&io.wbuf).unwrap();
+
+            let mut reader = FrameReader::new(&io.wbuf[n..]);
+            let (head, data) = reader.next().unwrap().unwrap();
+            assert_eq!(head.opcode, OpCode::Binary);
+            assert!(reader.remaining().is_empty());
+
+            // masked with the key used by the stream
+            let mut data = data.to_vec();
+            match (mask, head.mask) {
+                (Mask::Key(k), Mask::Key(key)) => {
+                    assert_eq!(k, key);
+                    apply_mask4(key, &mut data);
+                }
+                (Mask::Skip, Mask::Skip) => {}
+                _ => unreachable!(),
+            }
+            data
+        }
+
+        let payload = b"hello";
+        assert_eq!(run::<Client>(payload), payload);
+        assert_eq!(run::<StandardClient>(payload), payload);
+
+        // request and frame do not fit
+        let mut buf = vec![0u8; REQUEST.len() + 2];
+        let e = Endpoint::<_, Client>::connect_with_payload(
+            MockServer::new(&[]),
+            &mut buf,
+            "www.example.com",
+            "/ws",
+            payload,
+        )
+        .map(drop)
+        .unwrap_err();
+        let e: &HandshakeError = e.source().unwrap().downcast_ref().unwrap();
+        assert_eq!(*e, HandshakeError::NotEnoughCapacity);
+    }
+
+    #[test]
+    fn client_connect_with_storage() {
+        let mut buf = vec![0u8; 1024];
+        let mut other_headers = HttpHeader::new_custom_storage::<4>();
+
+        let io = MockServer::new(&[(b"sec-websocket-protocol", b"chat")]);
+        let stream = Endpoint::<_, Client>::connect_with_storage(
+            io,
+            &mut buf,
+            "example.com",
+            "/",
+            &mut other_headers,
+        )
+        .unwrap();
+
+        assert_eq!(stream.negotiated().protocol(), Some(b"chat".as_slice()));
+        assert_eq!(
+            other_headers[0],
+            HttpHeader::new(b"sec-websocket-protocol", b"chat")
+        );
+        assert_eq!(other_headers[1], HttpHeader::default());
+    }
+
+    #[test]
+    fn client_connect_with_headers() {
+        let mut buf = vec![0u8; 1024];
+        let mut server = Vec::new();
+
+        let io = MockServer::new(&[(b"server", b"lightws")]);
+        let _ = Endpoint::<_, Client>::connect_with(io, &mut buf, "example.com", "/", |headers| {
+            server.extend_from_slice(headers[0].value);
+        })
+        .unwrap();
+
+        assert_eq!(server, b"lightws");
+    }
+
+    #[test]
+    fn client_connect_pipelined() {
+        use std::io::Read;
+
+        let mut buf = vec![0u8; 1024];
+
+        // server sends a frame right after the response
+        let mut io = MockServer::new(&[]);
+        io.trailer = vec![0x82, 0x03, b'a', b'b', b'c'];
+        let mut stream = Endpoint::<_, Client>::connect(io, &mut buf, "example.com", "/").unwrap();
+
+        let n = stream.read(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"abc");
+    }
 }
@@ -1,20 +1,22 @@
-use std::io::Result;
+use std::io::{Error, Result};
 use std::task::{Poll, ready};
 
-use crate::handshake::Request;
-use crate::handshake::Response;
+use crate::handshake::{HttpHeader, Request};
+use crate::handshake::response::encode_response;
+use crate::handshake::static_headers::HEADER_ORIGIN_NAME;
 use crate::error::HandshakeError;
 
-pub fn send_response<'h, 'b: 'h, F, IO, const N: usize>(
+pub fn send_response<F, IO>(
     io: &mut IO,
     buf: &mut [u8],
-    response: &Response<'h, 'b, N>,
+    sec_accept: &[u8],
+    other_headers: &[HttpHeader],
     mut write: F,
 ) -> Poll<Result<usize>>
 where
     F: FnMut(&mut IO, &[u8]) -> Poll<Result<usize>>,
 {
-    let total = match response.encode(buf) {
+    let total = match encode_response(buf, sec_accept, other_headers) {
         Ok(n) => n,
         Err(e) => return Poll::Ready(Err(e.into())),
     };
@@ -30,12 +32,33 @@ where
     Poll::Ready(Ok(total))
 }
 
+pub fn send_reject<F, IO>(io: &mut IO, reject: &[u8], mut write: F) -> Poll<Result<usize>>
+where
+    F: FnMut(&mut IO, &[u8]) -> Poll<Result<usize>>,
+{
+    let total = reject.len();
+    let mut offset = 0;
+
+    while offset < total {
+        let n = ready!(write(io, &reject[offset..]))?;
+
+        // could not send more, give up
+        if n == 0 {
+            break;
+        }
+
+        offset += n;
+    }
+
+    Poll::Ready(Ok(offset))
+}
+
 pub unsafe fn recv_request<'h, 'b: 'h, F, IO, const N: usize>(
     io: &mut IO,
     buf: &mut [u8],
     request: &mut Request<'h, 'b, N>,
     mut read: F,
-) -> Poll<Result<usize>>
+) -> Poll<Result<(usize, usize)>>
 where
     F: FnMut(&mut IO, &mut [u8]) -> Poll<Result<usize>>,
 {
@@ -57,7 +80,7 @@ where
         offset += n;
 
         match request.decode(&buf_const[..offset]) {
-            Ok(_) => return Poll::Ready(Ok(offset)),
+            Ok(n) => return Poll::Ready(Ok((n, offset))),
             Err(ref e) if *e == HandshakeError::NotEnoughData => continue,
             Err(e) => return Poll::Ready(Err(e.into())),
         }
@@ -66,3 +89,53 @@ where
     // provided buffer is filled, however it could not accommodate the response.
     Poll::Ready(Err(HandshakeError::NotEnoughCapacity.into()))
 }
+
+/// Check if a request is rejected for an unsupported version,
+/// which should be replied with `426 Upgrade Required`.
+pub fn is_unsupported_version(e: &Error) -> bool {
+    matches!(
+        e.get_ref().and_then(|e| e.downcast_ref()),
+        Some(crate::error::Error::Handshake(
+            HandshakeError::UnsupportedVersion(_)
+        ))
+    )
+}
+
+/// Check host and path of a request.
+pub fn check_request<const N: usize>(
+    request: &Request<'_, '_, N>,
+    host: &str,
+    path: &str,
+) -> std::result::Result<(), HandshakeError> {
+    if request.host != host.as_bytes() {
+        return Err(HandshakeError::Manual("host mismatch"));
+    }
+
+    if request.path != path.as_bytes() {
+        return Err(HandshakeError::Manual("path mismatch"));
+    }
+
+    Ok(())
+}
+
+/// Check origin of a request against allowed origins (case insensitive).
+pub fn check_origin<const N: usize>(
+    request: &Request<'_, '_, N>,
+    allowed_origins: &[&str],
+) -> std::result::Result<(), HandshakeError> {
+    let origin = request
+        .other_headers
+        .iter()
+        .find(|h| h.name.eq_ignore_ascii_case(HEADER_ORIGIN_NAME))
+        .map(|h| h.value)
+        .ok_or(HandshakeError::Origin)?;
+
+    if allowed_origins
+        .iter()
+        .any(|allowed| allowed.as_bytes().eq_ignore_ascii_case(origin))
+    {
+        Ok(())
+    } else {
+        Err(HandshakeError::Origin)
+    }
+}
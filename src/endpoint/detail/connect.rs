@@ -1,8 +1,11 @@
 use std::io::Result;
 use std::task::{Poll, ready};
 
-use crate::handshake::Request;
-use crate::handshake::Response;
+use crate::handshake::{HttpHeader, Request, Response, Negotiated};
+use crate::handshake::static_headers::{
+    HEADER_SEC_WEBSOCKET_PROTOCOL_NAME, HEADER_SEC_WEBSOCKET_EXTENSIONS_NAME,
+};
+use crate::frame::{FrameHead, Fin, OpCode, Mask, PayloadLen, apply_mask4};
 use crate::error::HandshakeError;
 
 pub fn send_request<'h, 'b: 'h, F, IO, const N: usize>(
@@ -30,12 +33,38 @@ where
     Poll::Ready(Ok(total))
 }
 
+/// Encode the request followed by a fin binary frame of `payload`,
+/// return the number of written bytes.
+pub fn encode_request_with_frame<const N: usize>(
+    buf: &mut [u8],
+    request: &Request<'_, '_, N>,
+    mask: Mask,
+    payload: &[u8],
+) -> Result<usize> {
+    let request_n = request.encode(buf)?;
+
+    let length = PayloadLen::from_num(payload.len() as u64);
+    let head = FrameHead::new(Fin::Y, OpCode::Binary, mask, length);
+    let total = request_n + head.encoded_len() + payload.len();
+    if buf.len() < total {
+        return Err(HandshakeError::NotEnoughCapacity.into());
+    }
+
+    let head_n = head.encode(&mut buf[request_n..]).unwrap();
+    let data = &mut buf[request_n + head_n..total];
+    data.copy_from_slice(payload);
+    if let Mask::Key(key) = mask {
+        apply_mask4(key, data);
+    }
+    Ok(total)
+}
+
 pub unsafe fn recv_response<'h, 'b: 'h, F, IO, const N: usize>(
     io: &mut IO,
     buf: &mut [u8],
     response: &mut Response<'h, 'b, N>,
     mut read: F,
-) -> Poll<Result<usize>>
+) -> Poll<Result<(usize, usize)>>
 where
     F: FnMut(&mut IO, &mut [u8]) -> Poll<Result<usize>>,
 {
@@ -57,7 +86,7 @@ where
         offset += n;
 
         match response.decode(&buf_const[..offset]) {
-            Ok(_) => return Poll::Ready(Ok(offset)),
+            Ok(n) => return Poll::Ready(Ok((n, offset))),
             Err(ref e) if *e == HandshakeError::NotEnoughData => continue,
             Err(e) => return Poll::Ready(Err(e.into())),
         }
@@ -66,3 +95,52 @@ where
     // provided buffer is filled, however it could not accommodate the response.
     Poll::Ready(Err(HandshakeError::NotEnoughCapacity.into()))
 }
+
+/// Headers to offer subprotocols and extensions, empty values are skipped.
+pub fn offer_headers<'a>(protocol: &'a str, extension: &'a str) -> Vec<HttpHeader<'a>> {
+    [
+        HttpHeader::new(HEADER_SEC_WEBSOCKET_PROTOCOL_NAME, protocol.as_bytes()),
+        HttpHeader::new(HEADER_SEC_WEBSOCKET_EXTENSIONS_NAME, extension.as_bytes()),
+    ]
+    .into_iter()
+    .filter(|h| !h.value.is_empty())
+    .collect()
+}
+
+/// Check that the server selects one of the required subprotocols (case sensitive),
+/// and one of the required extensions (by name, case insensitive).
+/// Empty requirements are always met.
+pub fn check_requirements(
+    negotiated: &Negotiated,
+    protocols: &[&str],
+    extensions: &[&str],
+) -> std::result::Result<(), HandshakeError> {
+    let protocol_ok = protocols.is_empty()
+        || negotiated
+            .protocol()
+            .is_some_and(|selected| protocols.iter().any(|p| p.as_bytes() == selected));
+    if !protocol_ok {
+        return Err(HandshakeError::Manual("subprotocol not negotiated"));
+    }
+
+    let extension_ok = extensions.is_empty()
+        || negotiated.extensions_iter().any(|selected| {
+            extensions.iter().any(|e| {
+                extension_name(e.as_bytes()).eq_ignore_ascii_case(extension_name(selected))
+            })
+        });
+    if !extension_ok {
+        return Err(HandshakeError::Manual("extension not negotiated"));
+    }
+
+    Ok(())
+}
+
+/// Name of an extension, without parameters.
+#[inline]
+fn extension_name(ext: &[u8]) -> &[u8] {
+    ext.split(|b| *b == b';')
+        .next()
+        .unwrap_or_default()
+        .trim_ascii()
+}
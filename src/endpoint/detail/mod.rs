@@ -1,5 +1,7 @@
 mod accept;
 mod connect;
 
-pub(super) use accept::{recv_request, send_response};
-pub(super) use connect::{recv_response, send_request};
+pub(super) use accept::{recv_request, send_response, send_reject};
+pub(super) use accept::{check_request, check_origin, is_unsupported_version};
+pub(super) use connect::{recv_response, send_request, encode_request_with_frame};
+pub(super) use connect::{offer_headers, check_requirements};
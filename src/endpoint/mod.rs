@@ -5,14 +5,47 @@
 //!
 //! To open or accept a connection directly, use [`Endpoint::connect`],
 //! [`Endpoint::accept`], or their async version.
+//! To validate the request with custom rules before accepting it,
+//! use [`Endpoint::accept_with`], or [`Endpoint::accept_with_origin`]
+//! to allow-list the `origin` header.
 //!
 //! To have detailed control over a handshake, use [`Endpoint::send_request`],
 //! [`Endpoint::recv_response`], [`Endpoint::recv_request`], [`Endpoint::send_response`],
-//! or their async version.
+//! or their async version. [`Endpoint::recv_response_safe`] and
+//! [`Endpoint::recv_request_safe`] copy the required fields out of the buffer
+//! instead of being `unsafe`.
+//!
+//! If the IO source is split into a reader and a writer,
+//! use [`Endpoint::connect_split`], which joins them as [`ReadWrite`].
+//!
+//! To reuse a handshake buffer and apply the same settings to each new stream,
+//! use [`EndpointBuilder`].
+//!
+//! ## TLS
+//!
+//! [`Endpoint`] is generic over IO, so `wss://` only requires
+//! a TLS stream to be established first, which then becomes the IO type.
+//! With the `tls` feature, [`Endpoint::connect_tls`] or its async version performs
+//! both handshakes over `rustls`, whose config types are re-exported in [`tls`].
+//!
+//! ```ignore
+//! let tcp = TcpStream::connect("example.com:443")?;
+//! let ws = Endpoint::<_, Client>::connect_tls(
+//!     tcp, config, "example.com", &mut buf, "example.com", "/ws",
+//! )?;
+//! ```
 
 mod detail;
 mod client;
 mod server;
+mod builder;
+mod split;
+
+pub use builder::{EndpointBuilder, DEFAULT_BUFFER_SIZE};
+pub use split::ReadWrite;
+
+#[cfg(feature = "tls")]
+pub mod tls;
 
 cfg_if::cfg_if! {
     if #[cfg(feature = "tokio")] {
@@ -32,6 +65,7 @@ pub struct Endpoint<IO, Role> {
 #[cfg(test)]
 mod test {
     use std::io::{Read, Write, Result};
+    use crate::handshake::{HttpHeader, Request, Response, derive_accept_key};
 
     pub const REQUEST: &[u8] = b"\
     GET /ws HTTP/1.1\r\n\
@@ -83,4 +117,62 @@ mod test {
 
         fn flush(&mut self) -> Result<()> { Ok(()) }
     }
+
+    /// Reply a client request with a matching accept key, and extra headers,
+    /// followed by trailing data.
+    pub struct MockServer {
+        pub rbuf: Vec<u8>,
+        pub wbuf: Vec<u8>,
+        pub headers: Vec<(&'static [u8], &'static [u8])>,
+        pub trailer: Vec<u8>,
+        pub cursor: usize,
+    }
+
+    impl MockServer {
+        pub fn new(headers: &[(&'static [u8], &'static [u8])]) -> Self {
+            Self {
+                rbuf: Vec::new(),
+                wbuf: Vec::new(),
+                headers: headers.to_vec(),
+                trailer: Vec::new(),
+                cursor: 0,
+            }
+        }
+
+        fn respond(&mut self) {
+            let mut storage = HttpHeader::new_storage();
+            let mut request = Request::new_storage(&mut storage);
+            request.decode(&self.wbuf).unwrap();
+
+            let sec_accept = derive_accept_key(request.sec_key);
+            let mut headers: Vec<HttpHeader> = self
+                .headers
+                .iter()
+                .map(|(name, value)| HttpHeader::new(name, value))
+                .collect();
+            let response = Response::new_with_headers(&sec_accept, &mut headers);
+
+            let mut buf = vec![0u8; 1024];
+            let n = response.encode(&mut buf).unwrap();
+            self.rbuf.extend_from_slice(&buf[..n]);
+            self.rbuf.extend_from_slice(&self.trailer);
+        }
+    }
+
+    impl Read for MockServer {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            if self.rbuf.is_empty() {
+                self.respond();
+            }
+            let n = (&self.rbuf[self.cursor..]).read(buf)?;
+            self.cursor += n;
+            Ok(n)
+        }
+    }
+
+    impl Write for MockServer {
+        fn write(&mut self, buf: &[u8]) -> Result<usize> { self.wbuf.write(buf) }
+
+        fn flush(&mut self) -> Result<()> { Ok(()) }
+    }
 }
@@ -5,8 +5,9 @@ use super::detail;
 use super::Endpoint;
 
 use crate::role::ServerRole;
-use crate::handshake::{HttpHeader, Request, Response};
+use crate::handshake::{HttpHeader, Request, Response, Negotiated, OwnedRequest};
 use crate::handshake::derive_accept_key;
+use crate::handshake::{HTTP_FORBIDDEN, HTTP_UPGRADE_REQUIRED};
 use crate::error::HandshakeError;
 use crate::stream::Stream;
 
@@ -22,7 +23,48 @@ impl<IO: Read + Write, Role: ServerRole> Endpoint<IO, Role> {
         buf: &mut [u8],
         response: &Response<'_, '_, N>,
     ) -> Result<usize> {
-        match detail::send_response(io, buf, response, |io, buf| io.write(buf).into()) {
+        Self::send_response_parts(io, buf, response.sec_accept, response.other_headers)
+    }
+
+    /// Same as [`send_response`](Self::send_response), but take the fields of [`Response`].
+    fn send_response_parts(
+        io: &mut IO,
+        buf: &mut [u8],
+        sec_accept: &[u8],
+        other_headers: &[HttpHeader],
+    ) -> Result<usize> {
+        match detail::send_response(io, buf, sec_accept, other_headers, |io, buf| {
+            io.write(buf).into()
+        }) {
+            Poll::Ready(x) => x,
+            Poll::Pending => unreachable!(),
+        }
+    }
+
+    /// Send `426 Upgrade Required` to IO source, with the supported version `13`,
+    /// return the number of bytes transmitted.
+    ///
+    /// This should be used to reply a request which is rejected with
+    /// [`HandshakeError::UnsupportedVersion`].
+    /// It will block until all data are written to IO source,
+    /// IO source could not accept more data, or an error occurs.
+    pub fn send_upgrade_required(io: &mut IO) -> Result<usize> {
+        match detail::send_reject(io, HTTP_UPGRADE_REQUIRED, |io, buf| io.write(buf).into()) {
+            Poll::Ready(x) => x,
+            Poll::Pending => unreachable!(),
+        }
+    }
+
+    /// Send a response which rejects the upgrade to IO source, e.g. `400 Bad Request`,
+    /// with a `content-length` header and the body, return the number of bytes transmitted.
+    /// Response data are encoded to the provided buffer,
+    /// see [`Response::encode_reject`].
+    ///
+    /// It will block until all data are written to IO source,
+    /// IO source could not accept more data, or an error occurs.
+    pub fn send_reject(io: &mut IO, buf: &mut [u8], status: &[u8], body: &[u8]) -> Result<usize> {
+        let n = Response::encode_reject(buf, status, body)?;
+        match detail::send_reject(io, &buf[..n], |io, buf| io.write(buf).into()) {
             Poll::Ready(x) => x,
             Poll::Pending => unreachable!(),
         }
@@ -45,6 +87,30 @@ impl<IO: Read + Write, Role: ServerRole> Endpoint<IO, Role> {
         buf: &mut [u8],
         request: &mut Request<'h, 'b, N>,
     ) -> Result<usize> {
+        Self::recv_request_parsed(io, buf, request).map(|(_, n)| n)
+    }
+
+    /// Safe version of [`recv_request`](Self::recv_request), return the received request
+    /// along with the number of bytes transmitted.
+    ///
+    /// Required fields are copied into [`OwnedRequest`], so the buffer
+    /// could be reused once it returns, while optional headers are dropped.
+    /// At most [`MAX_ALLOW_HEADERS`](crate::handshake::MAX_ALLOW_HEADERS) headers are decoded.
+    pub fn recv_request_safe(io: &mut IO, buf: &mut [u8]) -> Result<(OwnedRequest, usize)> {
+        let mut other_headers = HttpHeader::new_storage();
+        let mut request = Request::new_storage(&mut other_headers);
+        // this is safe since request is dropped before buf is used again.
+        let n = unsafe { Self::recv_request(io, buf, &mut request) }?;
+        Ok((OwnedRequest::from(&request), n))
+    }
+
+    /// Same as [`recv_request`](Self::recv_request), but also return
+    /// the number of bytes parsed as [`Request`].
+    unsafe fn recv_request_parsed<'h, 'b: 'h, const N: usize>(
+        io: &mut IO,
+        buf: &mut [u8],
+        request: &mut Request<'h, 'b, N>,
+    ) -> Result<(usize, usize)> {
         match detail::recv_request(io, buf, request, |io, buf| io.read(buf).into()) {
             Poll::Ready(x) => x,
             Poll::Pending => unreachable!(),
@@ -55,29 +121,174 @@ impl<IO: Read + Write, Role: ServerRole> Endpoint<IO, Role> {
     ///
     /// This function is a combination of [`recv_request`](Self::recv_request)
     /// and [`send_response`](Self::send_response), without accessing [`Request`].
-    /// It will block until the handshake completes, or an error occurs.    
-    pub fn accept(mut io: IO, buf: &mut [u8], host: &str, path: &str) -> Result<Stream<IO, Role>> {
-        // recv
+    /// It will block until the handshake completes, or an error occurs.
+    pub fn accept(io: IO, buf: &mut [u8], host: &str, path: &str) -> Result<Stream<IO, Role>> {
+        Self::accept_with(io, buf, |request| {
+            detail::check_request(request, host, path)
+        })
+    }
+
+    /// Same as [`accept`](Self::accept), but also check the `origin` header
+    /// against allowed origins (case insensitive).
+    ///
+    /// A request with a missing or disallowed origin is rejected with `403 Forbidden`,
+    /// and a [`HandshakeError::Origin`] error is returned.
+    pub fn accept_with_origin(
+        io: IO,
+        buf: &mut [u8],
+        host: &str,
+        path: &str,
+        allowed_origins: &[&str],
+    ) -> Result<Stream<IO, Role>> {
         let mut other_headers = HttpHeader::new_storage();
-        let mut request = Request::new_storage(&mut other_headers);
+        let validate = |request: &Request, _: &mut [HttpHeader]| {
+            detail::check_request(request, host, path)?;
+            detail::check_origin(request, allowed_origins).map(|_| 0)
+        };
+        Self::accept_checked(io, buf, &mut other_headers, &mut [], validate, true)
+    }
+
+    /// Perform a websocket server handshake, return a new websocket stream.
+    ///
+    /// The parsed [`Request`] is passed to `validate` before sending the response,
+    /// where path, host, and other headers could be checked,
+    /// or copied out before they are dropped.
+    /// An error returned by `validate` aborts the handshake.
+    /// A request with an unsupported version is rejected with `426 Upgrade Required`.
+    /// It will block until the handshake completes, or an error occurs.
+    pub fn accept_with<F>(io: IO, buf: &mut [u8], validate: F) -> Result<Stream<IO, Role>>
+    where
+        F: FnOnce(&Request) -> std::result::Result<(), HandshakeError>,
+    {
+        let mut other_headers = HttpHeader::new_storage();
+        Self::accept_with_storage(io, buf, &mut other_headers, &mut [], |request, _| {
+            validate(request).map(|_| 0)
+        })
+    }
+
+    /// Same as [`accept_with`](Self::accept_with), but decode request headers
+    /// into the provided storage, whose size is the max decode header size,
+    /// and select response headers, e.g. the subprotocol or extensions.
+    ///
+    /// `negotiate` checks the request like `validate`, then fills `response_headers`
+    /// and returns the number of selected headers, which are sent with the response.
+    /// They are recorded as [`Negotiated`] results, which are also applied to the role,
+    /// see [`RoleHelper::set_negotiated`](crate::role::RoleHelper::set_negotiated).
+    ///
+    /// The buffer is reused to send the response, so selected headers
+    /// could not borrow from the request.
+    pub fn accept_with_storage<'b, 's, const N: usize, F>(
+        io: IO,
+        buf: &'b mut [u8],
+        other_headers: &mut [HttpHeader<'b>; N],
+        response_headers: &mut [HttpHeader<'s>],
+        negotiate: F,
+    ) -> Result<Stream<IO, Role>>
+    where
+        F: FnOnce(
+            &Request<'_, '_, N>,
+            &mut [HttpHeader<'s>],
+        ) -> std::result::Result<usize, HandshakeError>,
+    {
+        Self::accept_checked(io, buf, other_headers, response_headers, negotiate, false)
+    }
+
+    /// Shared by [`accept_with_storage`](Self::accept_with_storage) and
+    /// [`accept_with_origin`](Self::accept_with_origin), where a
+    /// [`HandshakeError::Origin`] error is rejected with `403 Forbidden`
+    /// if `reject_origin` is set.
+    fn accept_checked<'b, 's, const N: usize, F>(
+        mut io: IO,
+        buf: &'b mut [u8],
+        other_headers: &mut [HttpHeader<'b>; N],
+        response_headers: &mut [HttpHeader<'s>],
+        negotiate: F,
+        reject_origin: bool,
+    ) -> Result<Stream<IO, Role>>
+    where
+        F: FnOnce(
+            &Request<'_, '_, N>,
+            &mut [HttpHeader<'s>],
+        ) -> std::result::Result<usize, HandshakeError>,
+    {
+        // recv
+        let mut request = Request::<N>::new_custom_storage(other_headers);
         // this is safe since we do not modify request.
-        let _ = unsafe { Self::recv_request(&mut io, buf, &mut request) }?;
+        let (parsed_n, read_n) =
+            match unsafe { Self::recv_request_parsed(&mut io, buf, &mut request) } {
+                Ok(x) => x,
+                Err(e) => {
+                    // tell the client which version is supported
+                    if detail::is_unsupported_version(&e) {
+                        let _ = Self::send_upgrade_required(&mut io);
+                    }
+                    return Err(e);
+                }
+            };
 
-        // check
-        if request.host != host.as_bytes() {
-            return Err(HandshakeError::Manual("host mismatch").into());
-        }
+        // check, reject a disallowed origin
+        let selected_n = match negotiate(&request, response_headers) {
+            Ok(n) => n,
+            Err(e) => {
+                if reject_origin && e == HandshakeError::Origin {
+                    let _ = match detail::send_reject(&mut io, HTTP_FORBIDDEN, |io, buf| {
+                        io.write(buf).into()
+                    }) {
+                        Poll::Ready(x) => x,
+                        Poll::Pending => unreachable!(),
+                    };
+                }
+                return Err(e.into());
+            }
+        };
+        let selected = &response_headers[..selected_n];
 
-        if request.path != path.as_bytes() {
-            return Err(HandshakeError::Manual("path mismatch").into());
-        }
+        // data read past the request, buffer is reused below
+        let leftover = Box::from(&buf[parsed_n..read_n]);
 
         // send
         let sec_accept = derive_accept_key(request.sec_key);
-        let response = Response::new(&sec_accept);
-        let _ = Self::send_response(&mut io, buf, &response)?;
+        let _ = Self::send_response_parts(&mut io, buf, &sec_accept, selected)?;
+
+        let mut stream = Stream::new_with_leftover(io, Role::new(), leftover);
+        stream.set_negotiated(Negotiated::from_headers(selected));
+        Ok(stream)
+    }
+
+    /// Complete a websocket server handshake with a [`Request`] which has been
+    /// received and validated by the caller, e.g. an existing http server.
+    ///
+    /// Only the response is sent, encoded to the provided buffer.
+    /// Data read past the request should be passed as `leftover`,
+    /// which are read by the stream before IO, e.g. pipelined frames.
+    /// It will block until the response is sent, or an error occurs.
+    pub fn accept_prepared<const N: usize>(
+        io: IO,
+        request: &Request<'_, '_, N>,
+        leftover: &[u8],
+        buf: &mut [u8],
+    ) -> Result<Stream<IO, Role>> {
+        Self::accept_prepared_with_headers(io, request, leftover, &[], buf)
+    }
+
+    /// Same as [`accept_prepared`](Self::accept_prepared), but also send other headers,
+    /// e.g. the selected subprotocol or extensions.
+    ///
+    /// These headers are recorded as [`Negotiated`] results, which are
+    /// also applied to the role, see [`RoleHelper::set_negotiated`](crate::role::RoleHelper::set_negotiated).
+    pub fn accept_prepared_with_headers<const N: usize>(
+        mut io: IO,
+        request: &Request<'_, '_, N>,
+        leftover: &[u8],
+        other_headers: &[HttpHeader],
+        buf: &mut [u8],
+    ) -> Result<Stream<IO, Role>> {
+        let sec_accept = derive_accept_key(request.sec_key);
+        let _ = Self::send_response_parts(&mut io, buf, &sec_accept, other_headers)?;
 
-        Ok(Stream::new(io, Role::new()))
+        let mut stream = Stream::new_with_leftover(io, Role::new(), Box::from(leftover));
+        stream.set_negotiated(Negotiated::from_headers(other_headers));
+        Ok(stream)
     }
 }
 
@@ -114,6 +325,34 @@ mod test {
         }
     }
 
+    #[test]
+    fn recv_upgrade_request_safe() {
+        let mut rw = LimitReadWriter {
+            rbuf: Vec::from(REQUEST),
+            wbuf: Vec::new(),
+            rlimit: 1,
+            wlimit: 0,
+            cursor: 0,
+        };
+
+        let mut buf = vec![0u8; 1024];
+
+        let (request, recv_n) =
+            Endpoint::<_, Server>::recv_request_safe(&mut rw, &mut buf).unwrap();
+        assert_eq!(recv_n, REQUEST.len());
+        assert_eq!(&buf[..recv_n], REQUEST);
+
+        // the buffer is free to reuse
+        buf.fill(0);
+        assert_eq!(&*request.host, b"www.example.com");
+        assert_eq!(&*request.path, b"/ws");
+        assert_eq!(&*request.sec_key, b"dGhlIHNhbXBsZSBub25jZQ==");
+
+        let mut encoded = vec![0u8; 1024];
+        let n = request.as_request().encode(&mut encoded).unwrap();
+        assert_eq!(&encoded[..n], REQUEST);
+    }
+
     #[test]
     fn recv_upgrade_request() {
         fn run_limit(limit: usize) {
@@ -137,7 +376,6 @@ mod test {
             assert_eq!(request.host, b"www.example.com");
             assert_eq!(request.path, b"/ws");
             assert_eq!(request.sec_key, b"dGhlIHNhbXBsZSBub25jZQ==");
-            drop(request);
             assert_eq!(&buf[..recv_n], REQUEST);
         }
 
@@ -161,4 +399,356 @@ mod test {
 
         let _ = Endpoint::<_, Server>::accept(&mut rw, &mut buf, "www.example.com", "/ws");
     }
+
+    #[test]
+    fn server_accept_prepared() {
+        use std::io::Read;
+
+        // the caller has read a frame past the request
+        let mut data = Vec::from(REQUEST);
+        data.extend_from_slice(&[0x82, 0x83, 0, 0, 0, 0, b'a', b'b', b'c']);
+
+        let mut headers = HttpHeader::new_storage();
+        let mut request = Request::new_storage(&mut headers);
+        let parsed_n = request.decode(&data).unwrap();
+        assert_eq!(parsed_n, REQUEST.len());
+
+        let rw = LimitReadWriter {
+            rbuf: Vec::new(),
+            wbuf: Vec::new(),
+            rlimit: 0,
+            wlimit: 1,
+            cursor: 0,
+        };
+
+        let mut buf = vec![0u8; 1024];
+
+        let stream =
+            Endpoint::<_, Server>::accept_prepared(rw, &request, &data[parsed_n..], &mut buf)
+                .unwrap();
+        assert_eq!(stream.as_ref().wbuf, RESPONSE);
+        assert_eq!(stream.leftover_len(), 9);
+
+        let mut stream = stream.guard();
+        let n = stream.read(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"abc");
+        assert_eq!(stream.leftover_len(), 0);
+    }
+
+    #[test]
+    fn server_accept_prepared_negotiated() {
+        use crate::role::{DeflateServer, DeflateRole};
+
+        let mut headers = HttpHeader::new_storage();
+        let mut request = Request::new_storage(&mut headers);
+        assert_eq!(request.decode(REQUEST).unwrap(), REQUEST.len());
+
+        let mut rw = LimitReadWriter {
+            rbuf: Vec::new(),
+            wbuf: Vec::new(),
+            rlimit: 0,
+            wlimit: 1,
+            cursor: 0,
+        };
+
+        let mut buf = vec![0u8; 1024];
+        let other_headers = [
+            HttpHeader::new(b"sec-websocket-protocol", b"chat"),
+            HttpHeader::new(
+                b"sec-websocket-extensions",
+                b"permessage-deflate; server_max_window_bits=10",
+            ),
+        ];
+
+        let stream = Endpoint::<_, DeflateServer>::accept_prepared_with_headers(
+            &mut rw,
+            &request,
+            &[],
+            &other_headers,
+            &mut buf,
+        )
+        .unwrap();
+
+        assert_eq!(stream.protocol(), Some(b"chat".as_slice()));
+        let role = stream.role();
+        assert_eq!(role.write_window_bits(), 10);
+        assert_eq!(role.read_window_bits(), 15);
+        assert_eq!(Some(role.deflate()), stream.negotiated().deflate());
+
+        drop(stream);
+        let response = String::from_utf8(rw.wbuf).unwrap();
+        assert!(response.starts_with("HTTP/1.1 101 Switching Protocols\r\n"));
+        assert!(response.ends_with(
+            "sec-websocket-protocol: chat\r\n\
+            sec-websocket-extensions: permessage-deflate; server_max_window_bits=10\r\n\r\n"
+        ));
+    }
+
+    #[test]
+    fn server_accept_with() {
+        use std::error::Error;
+
+        fn run(
+            validate: impl FnOnce(&Request) -> std::result::Result<(), HandshakeError>,
+        ) -> Result<()> {
+            let mut rw = LimitReadWriter {
+                rbuf: Vec::from(REQUEST),
+                wbuf: Vec::new(),
+                rlimit: 1,
+                wlimit: 1,
+                cursor: 0,
+            };
+
+            let mut buf = vec![0u8; 1024];
+
+            Endpoint::<_, Server>::accept_with(&mut rw, &mut buf, validate)?;
+            assert_eq!(rw.wbuf, RESPONSE);
+            Ok(())
+        }
+
+        run(|request| {
+            assert_eq!(request.host, b"www.example.com");
+            assert_eq!(request.path, b"/ws");
+            assert!(request.other_headers.is_empty());
+            Ok(())
+        })
+        .unwrap();
+
+        let e = run(|request| {
+            if request.path.starts_with(b"/api") {
+                Ok(())
+            } else {
+                Err(HandshakeError::Manual("no route"))
+            }
+        })
+        .unwrap_err();
+        let e = e.source().unwrap();
+        let e: &HandshakeError = e.downcast_ref().unwrap();
+        assert_eq!(*e, HandshakeError::Manual("no route"));
+    }
+
+    #[test]
+    fn server_accept_negotiated() {
+        use crate::role::{DeflateServer, DeflateRole};
+
+        let mut rbuf = Vec::from(&REQUEST[..REQUEST.len() - 2]);
+        rbuf.extend_from_slice(b"sec-websocket-protocol: superchat, chat\r\n");
+        rbuf.extend_from_slice(b"sec-websocket-extensions: permessage-deflate\r\n\r\n");
+
+        let mut rw = LimitReadWriter {
+            rbuf,
+            wbuf: Vec::new(),
+            rlimit: 1,
+            wlimit: 1,
+            cursor: 0,
+        };
+
+        let mut buf = vec![0u8; 1024];
+        let mut other_headers = HttpHeader::new_storage();
+        let mut response_headers = HttpHeader::new_custom_storage::<2>();
+
+        let stream = Endpoint::<_, DeflateServer>::accept_with_storage(
+            &mut rw,
+            &mut buf,
+            &mut other_headers,
+            &mut response_headers,
+            |request, selected| {
+                let offer = |name: &[u8]| {
+                    request
+                        .other_headers
+                        .iter()
+                        .find(|h| h.name.eq_ignore_ascii_case(name))
+                        .map(|h| h.value)
+                };
+                let mut n = 0;
+                if offer(b"sec-websocket-protocol").is_some_and(|v| v.ends_with(b"chat")) {
+                    selected[n] = HttpHeader::new(b"sec-websocket-protocol", b"chat");
+                    n += 1;
+                }
+                if offer(b"sec-websocket-extensions").is_some() {
+                    selected[n] = HttpHeader::new(
+                        b"sec-websocket-extensions",
+                        b"permessage-deflate; server_max_window_bits=10",
+                    );
+                    n += 1;
+                }
+                Ok(n)
+            },
+        )
+        .unwrap();
+
+        assert_eq!(stream.protocol(), Some(b"chat".as_slice()));
+        assert_eq!(stream.role().write_window_bits(), 10);
+        assert_eq!(Some(stream.role().deflate()), stream.negotiated().deflate());
+
+        drop(stream);
+        let response = String::from_utf8(rw.wbuf).unwrap();
+        assert!(response.ends_with(
+            "sec-websocket-protocol: chat\r\n\
+            sec-websocket-extensions: permessage-deflate; server_max_window_bits=10\r\n\r\n"
+        ));
+
+        // nothing is negotiated by a plain accept
+        let mut rw = LimitReadWriter {
+            rbuf: Vec::from(REQUEST),
+            wbuf: Vec::new(),
+            rlimit: 1024,
+            wlimit: 1024,
+            cursor: 0,
+        };
+        let stream =
+            Endpoint::<_, DeflateServer>::accept(&mut rw, &mut buf, "www.example.com", "/ws")
+                .unwrap();
+        assert_eq!(stream.protocol(), None);
+        assert_eq!(stream.negotiated().deflate(), None);
+    }
+
+    #[test]
+    fn server_accept_pipelined() {
+        use std::io::Read;
+
+        // client sends a frame right after the request
+        let mut rbuf = Vec::from(REQUEST);
+        rbuf.extend_from_slice(&[0x82, 0x83, 0, 0, 0, 0, b'a', b'b', b'c']);
+
+        for limit in [1, 16, 1024] {
+            let rw = LimitReadWriter {
+                rbuf: rbuf.clone(),
+                wbuf: Vec::new(),
+                rlimit: limit,
+                wlimit: 1024,
+                cursor: 0,
+            };
+
+            let mut buf = vec![0u8; 1024];
+            let stream =
+                Endpoint::<_, Server>::accept(rw, &mut buf, "www.example.com", "/ws").unwrap();
+            assert_eq!(stream.as_ref().wbuf, RESPONSE);
+            let read_past = stream.as_ref().cursor - REQUEST.len();
+            assert_eq!(stream.leftover_len(), read_past);
+            if limit == 1024 {
+                assert_eq!(read_past, 9);
+            }
+
+            let mut stream = stream.guard();
+            let mut data = Vec::new();
+            while data.len() < 3 {
+                let n = stream.read(&mut buf).unwrap();
+                data.extend_from_slice(&buf[..n]);
+            }
+            assert_eq!(data, b"abc");
+            assert_eq!(stream.leftover_len(), 0);
+        }
+    }
+
+    #[test]
+    fn server_accept_with_origin() {
+        use std::error::Error;
+
+        fn run(origin: Option<&str>, allowed_origins: &[&str]) -> (Result<()>, Vec<u8>) {
+            let mut rbuf = Vec::from(&REQUEST[..REQUEST.len() - 2]);
+            if let Some(origin) = origin {
+                rbuf.extend_from_slice(format!("origin: {}\r\n", origin).as_bytes());
+            }
+            rbuf.extend_from_slice(b"\r\n");
+
+            let mut rw = LimitReadWriter {
+                rbuf,
+                wbuf: Vec::new(),
+                rlimit: 1,
+                wlimit: 1,
+                cursor: 0,
+            };
+
+            let mut buf = vec![0u8; 1024];
+
+            let res = Endpoint::<_, Server>::accept_with_origin(
+                &mut rw,
+                &mut buf,
+                "www.example.com",
+                "/ws",
+                allowed_origins,
+            )
+            .map(|_| ());
+            (res, rw.wbuf)
+        }
+
+        let allowed = ["https://example.com", "https://www.example.com"];
+
+        let (res, wbuf) = run(Some("https://WWW.example.com"), &allowed);
+        res.unwrap();
+        assert_eq!(wbuf, RESPONSE);
+
+        for origin in [None, Some("https://evil.com")] {
+            let (res, wbuf) = run(origin, &allowed);
+            let e = res.unwrap_err();
+            let e: &HandshakeError = e.source().unwrap().downcast_ref().unwrap();
+            assert_eq!(*e, HandshakeError::Origin);
+            assert_eq!(wbuf, crate::handshake::HTTP_FORBIDDEN);
+        }
+
+        // plain accept sends nothing on a mismatch
+        let mut rw = LimitReadWriter {
+            rbuf: REQUEST.to_vec(),
+            wbuf: Vec::new(),
+            rlimit: 1,
+            wlimit: 1,
+            cursor: 0,
+        };
+        let mut buf = vec![0u8; 1024];
+        let e =
+            Endpoint::<_, Server>::accept(&mut rw, &mut buf, "www.example.org", "/ws").unwrap_err();
+        let e: &HandshakeError = e.source().unwrap().downcast_ref().unwrap();
+        assert_eq!(*e, HandshakeError::Manual("host mismatch"));
+        assert!(rw.wbuf.is_empty());
+    }
+
+    #[test]
+    fn server_accept_unsupported_version() {
+        use std::error::Error;
+
+        let request = String::from_utf8(REQUEST.to_vec())
+            .unwrap()
+            .replace("sec-websocket-version: 13", "sec-websocket-version: 8");
+        let mut rw = LimitReadWriter {
+            rbuf: request.into_bytes(),
+            wbuf: Vec::new(),
+            rlimit: 1,
+            wlimit: 1,
+            cursor: 0,
+        };
+
+        let mut buf = vec![0u8; 1024];
+
+        let e =
+            Endpoint::<_, Server>::accept(&mut rw, &mut buf, "www.example.com", "/ws").unwrap_err();
+        let e: &HandshakeError = e.source().unwrap().downcast_ref().unwrap();
+        assert_eq!(*e, HandshakeError::UnsupportedVersion(8));
+        assert_eq!(rw.wbuf, Response::upgrade_required());
+    }
+
+    #[test]
+    fn send_reject_response() {
+        let body = b"<html><body>path mismatch</body></html>";
+        let mut rw = LimitReadWriter {
+            rbuf: Vec::new(),
+            wbuf: Vec::new(),
+            rlimit: 0,
+            wlimit: 7,
+            cursor: 0,
+        };
+
+        let mut buf = vec![0u8; 1024];
+
+        let send_n =
+            Endpoint::<_, Server>::send_reject(&mut rw, &mut buf, b"404 Not Found", body).unwrap();
+        assert_eq!(send_n, rw.wbuf.len());
+
+        let head = format!(
+            "HTTP/1.1 404 Not Found\r\ncontent-length: {}\r\n\r\n",
+            body.len()
+        );
+        assert_eq!(&rw.wbuf[..head.len()], head.as_bytes());
+        assert_eq!(&rw.wbuf[head.len()..], body);
+    }
 }
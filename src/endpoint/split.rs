@@ -0,0 +1,104 @@
+use std::io::{Read, Write, IoSlice, IoSliceMut, Result};
+
+use super::Endpoint;
+
+use crate::role::ClientRole;
+use crate::stream::Stream;
+
+/// Join a reader and a writer as a duplex IO source,
+/// where `Read` and `Write` are delegated to each of them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ReadWrite<R, W> {
+    /// Read half.
+    pub reader: R,
+    /// Write half.
+    pub writer: W,
+}
+
+impl<R, W> ReadWrite<R, W> {
+    /// Constructor, take the reader and the writer.
+    #[inline]
+    pub const fn new(reader: R, writer: W) -> Self { Self { reader, writer } }
+
+    /// Get the reader and the writer back.
+    #[inline]
+    pub fn into_inner(self) -> (R, W) { (self.reader, self.writer) }
+}
+
+impl<R: Read, W> Read for ReadWrite<R, W> {
+    #[inline]
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> { self.reader.read(buf) }
+
+    #[inline]
+    fn read_vectored(&mut self, bufs: &mut [IoSliceMut<'_>]) -> Result<usize> {
+        self.reader.read_vectored(bufs)
+    }
+}
+
+impl<R, W: Write> Write for ReadWrite<R, W> {
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> Result<usize> { self.writer.write(buf) }
+
+    #[inline]
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> Result<usize> {
+        self.writer.write_vectored(bufs)
+    }
+
+    #[inline]
+    fn flush(&mut self) -> Result<()> { self.writer.flush() }
+}
+
+impl<R: Read, W: Write, Role: ClientRole> Endpoint<ReadWrite<R, W>, Role> {
+    /// Same as [`connect`](Self::connect), but read from and write to
+    /// separate halves of the IO source, which are joined as [`ReadWrite`].
+    pub fn connect_split(
+        reader: R,
+        writer: W,
+        buf: &mut [u8],
+        host: &str,
+        path: &str,
+    ) -> Result<Stream<ReadWrite<R, W>, Role>> {
+        Self::connect(ReadWrite::new(reader, writer), buf, host, path)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::RefCell;
+    use super::*;
+    use super::super::test::MockServer;
+    use crate::role::Client;
+
+    struct Half<'a>(&'a RefCell<MockServer>);
+
+    impl Read for Half<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> { self.0.borrow_mut().read(buf) }
+    }
+
+    impl Write for Half<'_> {
+        fn write(&mut self, buf: &[u8]) -> Result<usize> { self.0.borrow_mut().write(buf) }
+
+        fn flush(&mut self) -> Result<()> { Ok(()) }
+    }
+
+    #[test]
+    fn client_connect_split() {
+        let mut buf = vec![0u8; 1024];
+        let server = RefCell::new(MockServer::new(&[(b"sec-websocket-protocol", b"chat")]));
+
+        let stream = Endpoint::<_, Client>::connect_split(
+            Half(&server),
+            Half(&server),
+            &mut buf,
+            "example.com",
+            "/",
+        )
+        .unwrap();
+        assert_eq!(stream.protocol(), Some(b"chat".as_slice()));
+
+        let (io, _) = stream.into_inner();
+        let (reader, writer) = io.into_inner();
+        assert!(std::ptr::eq(reader.0, writer.0));
+        assert!(server.borrow().wbuf.starts_with(b"GET / HTTP/1.1\r\n"));
+    }
+}
@@ -0,0 +1,79 @@
+//! TLS client glue over `rustls`, enabled by the `tls` feature.
+//!
+//! The TLS stream becomes the IO type of [`Stream`], e.g.
+//! [`StreamOwned<ClientConnection, TcpStream>`](StreamOwned), or
+//! [`TlsStream<TcpStream>`] with `async`.
+//!
+//! No root certificate is bundled, they should be added to
+//! the [`RootCertStore`] of [`ClientConfig`].
+//! Other types, e.g. server configs, are available from the re-exported crates.
+
+use std::io::{Read, Write, Result, Error, ErrorKind};
+use std::sync::Arc;
+
+pub use rustls;
+pub use rustls::{ClientConfig, ClientConnection, RootCertStore, StreamOwned};
+pub use rustls::pki_types::ServerName;
+
+use super::Endpoint;
+
+use crate::role::ClientRole;
+use crate::stream::Stream;
+
+impl<IO: Read + Write, Role: ClientRole> Endpoint<StreamOwned<ClientConnection, IO>, Role> {
+    /// Perform a TLS handshake with `domain`, then a websocket client handshake
+    /// over the TLS stream, return a new websocket stream.
+    ///
+    /// See [`connect`](Endpoint::connect).
+    /// It will block until both handshakes complete, or an error occurs.
+    pub fn connect_tls(
+        mut tcp: IO,
+        connector: Arc<ClientConfig>,
+        domain: &str,
+        buf: &mut [u8],
+        host: &str,
+        path: &str,
+    ) -> Result<Stream<StreamOwned<ClientConnection, IO>, Role>> {
+        let mut conn = ClientConnection::new(connector, server_name(domain)?)
+            .map_err(|e| Error::new(ErrorKind::InvalidInput, e))?;
+        while conn.is_handshaking() {
+            conn.complete_io(&mut tcp)?;
+        }
+
+        Self::connect(StreamOwned::new(conn, tcp), buf, host, path)
+    }
+}
+
+/// Parse a domain or an ip address.
+fn server_name(domain: &str) -> Result<ServerName<'static>> {
+    ServerName::try_from(domain.to_owned()).map_err(|e| Error::new(ErrorKind::InvalidInput, e))
+}
+
+cfg_if::cfg_if! {
+    if #[cfg(feature = "async")] {
+        use tokio::io::{AsyncRead, AsyncWrite};
+
+        pub use tokio_rustls;
+        pub use tokio_rustls::TlsConnector;
+        pub use tokio_rustls::client::TlsStream;
+
+        impl<IO, Role> Endpoint<TlsStream<IO>, Role>
+        where
+            IO: AsyncRead + AsyncWrite + Unpin,
+            Role: ClientRole,
+        {
+            /// Async version of [`connect_tls`](Endpoint::connect_tls).
+            pub async fn connect_tls_async(
+                tcp: IO,
+                connector: &TlsConnector,
+                domain: &str,
+                buf: &mut [u8],
+                host: &str,
+                path: &str,
+            ) -> Result<Stream<TlsStream<IO>, Role>> {
+                let tls = connector.connect(server_name(domain)?, tcp).await?;
+                Self::connect_async(tls, buf, host, path).await
+            }
+        }
+    }
+}
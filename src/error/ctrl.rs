@@ -1,18 +1,30 @@
-use std::fmt::{Display, Formatter};
+use core::fmt::{Display, Formatter};
 
 #[derive(Debug, PartialEq, Eq)]
 pub enum CtrlError {
     SetMaskInWrite,
+    SetMaskNotSupported,
+    CloseReasonTooLong,
+    NotDataOpcode,
+    MessageInProgress,
+    IncompleteFrame,
+    WriteAfterClose,
 }
 
 impl Display for CtrlError {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         use CtrlError::*;
         match self {
             SetMaskInWrite => write!(f, "Set mask during an incomplete write"),
+            SetMaskNotSupported => write!(f, "Set mask for a role without mask key"),
+            CloseReasonTooLong => write!(f, "Close reason exceeds 123 bytes"),
+            NotDataOpcode => write!(f, "Write a data frame with a control opcode"),
+            MessageInProgress => write!(f, "Write a new message during an unfinished one"),
+            IncompleteFrame => write!(f, "Finish a message during an incomplete frame"),
+            WriteAfterClose => write!(f, "Write a data frame after a close frame"),
         }
     }
 }
 
 // use default impl
-impl std::error::Error for CtrlError {}
+impl core::error::Error for CtrlError {}
@@ -1,4 +1,4 @@
-use std::fmt::{Display, Formatter};
+use core::fmt::{Display, Formatter};
 
 #[derive(Debug, PartialEq, Eq)]
 pub enum FrameError {
@@ -10,30 +10,46 @@ pub enum FrameError {
 
     IllegalData,
 
+    IllegalLength,
+
     NotEnoughData,
 
     NotEnoughCapacity,
 
-    UnsupportedOpcode,
+    UnsupportedOpcode(u8),
+
+    FrameTooLarge,
+
+    UnmaskedClientFrame,
+
+    MaskedServerFrame,
+
+    InvalidUtf8,
 }
 
 impl Display for FrameError {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         use FrameError::*;
         match self {
             IllegalFin => write!(f, "Illegal fin value"),
             IllegalMask => write!(f, "Illegal mask value"),
             IllegalOpCode => write!(f, "Illegal opcode value"),
             IllegalData => write!(f, "Illegal data"),
+            IllegalLength => write!(f, "Illegal payload length"),
             NotEnoughData => write!(f, "Not enough data to parse"),
             NotEnoughCapacity => write!(f, "Not enough space to write to"),
-            UnsupportedOpcode => write!(
+            UnsupportedOpcode(b) => write!(
                 f,
-                "Unsupported opcode, only support binary, ping, pong, close"
+                "Unsupported opcode {:#04x}, only support binary, ping, pong, close",
+                b
             ),
+            FrameTooLarge => write!(f, "Frame length exceeds the max message size"),
+            UnmaskedClientFrame => write!(f, "Unmasked frame from a client"),
+            MaskedServerFrame => write!(f, "Masked frame from a server"),
+            InvalidUtf8 => write!(f, "Invalid UTF-8 in text payload"),
         }
     }
 }
 
 // use default impl
-impl std::error::Error for FrameError {}
+impl core::error::Error for FrameError {}
@@ -1,4 +1,4 @@
-use std::fmt::{Display, Formatter};
+use core::fmt::{Display, Formatter};
 
 #[derive(Debug, PartialEq, Eq)]
 pub enum HandshakeError {
@@ -22,11 +22,17 @@ pub enum HandshakeError {
 
     SecWebSocketVersion,
 
+    UnsupportedVersion(u8),
+
+    Origin,
+
     // other error
 
     // read
     NotEnoughData,
 
+    TooManyHeaders,
+
     // write
     NotEnoughCapacity,
 
@@ -36,7 +42,7 @@ pub enum HandshakeError {
 }
 
 impl Display for HandshakeError {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         use HandshakeError::*;
         match self {
             // http error
@@ -65,9 +71,15 @@ impl Display for HandshakeError {
                 write!(f, "Missing or illegal sec-websocket-version")
             }
 
+            UnsupportedVersion(v) => write!(f, "Unsupported sec-websocket-version: {}", v),
+
+            Origin => write!(f, "Missing or disallowed origin header"),
+
             // other error
             NotEnoughData => write!(f, "Not enough data to parse"),
 
+            TooManyHeaders => write!(f, "Too many headers to parse"),
+
             NotEnoughCapacity => write!(f, "Not enough space to write to"),
 
             Httparse(e) => write!(f, "Http parse error: {}", e),
@@ -78,15 +90,20 @@ impl Display for HandshakeError {
 }
 
 impl From<httparse::Error> for HandshakeError {
-    fn from(e: httparse::Error) -> Self { HandshakeError::Httparse(e) }
+    fn from(e: httparse::Error) -> Self {
+        match e {
+            httparse::Error::TooManyHeaders => HandshakeError::TooManyHeaders,
+            e => HandshakeError::Httparse(e),
+        }
+    }
 }
 
-impl std::error::Error for HandshakeError {
-    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
-        if let HandshakeError::Httparse(e) = self {
-            Some(e)
-        } else {
-            None
+impl core::error::Error for HandshakeError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            #[cfg(feature = "std")]
+            HandshakeError::Httparse(e) => Some(e),
+            _ => None,
         }
     }
 }
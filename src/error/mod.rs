@@ -9,7 +9,7 @@ pub use ctrl::CtrlError;
 pub use frame::FrameError;
 pub use handshake::HandshakeError;
 
-use std::fmt::{Display, Formatter};
+use core::fmt::{Display, Formatter};
 
 #[derive(Debug)]
 pub enum Error {
@@ -29,7 +29,7 @@ impl From<HandshakeError> for Error {
 }
 
 impl Display for Error {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         use Error::*;
         match self {
             Ctrl(e) => write!(f, "Control error: {}", e),
@@ -39,8 +39,8 @@ impl Display for Error {
     }
 }
 
-impl std::error::Error for Error {
-    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+impl core::error::Error for Error {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
         use Error::*;
 
         match self {
@@ -51,21 +51,25 @@ impl std::error::Error for Error {
     }
 }
 
+#[cfg(feature = "std")]
 impl From<Error> for std::io::Error {
     fn from(e: Error) -> Self {
-        use std::io::{Error, ErrorKind};
-        Error::new(ErrorKind::Other, e)
+        use std::io::Error;
+        Error::other(e)
     }
 }
 
+#[cfg(feature = "std")]
 impl From<CtrlError> for std::io::Error {
     fn from(e: CtrlError) -> Self { Error::Ctrl(e).into() }
 }
 
+#[cfg(feature = "std")]
 impl From<FrameError> for std::io::Error {
     fn from(e: FrameError) -> Self { Error::Frame(e).into() }
 }
 
+#[cfg(feature = "std")]
 impl From<HandshakeError> for std::io::Error {
     fn from(e: HandshakeError) -> Self { Error::Handshake(e).into() }
 }
@@ -41,6 +41,24 @@ impl Fin {
         };
         Ok(fin)
     }
+
+    /// Get the flag byte.
+    #[inline]
+    pub const fn to_flag(&self) -> u8 { *self as u8 }
+}
+
+/// Convert from the exact flag value, e.g. `Fin::Y as u8`.
+impl TryFrom<u8> for Fin {
+    type Error = FrameError;
+
+    #[inline]
+    fn try_from(b: u8) -> Result<Self, Self::Error> {
+        match b {
+            0x80 => Ok(Fin::Y),
+            0x00 => Ok(Fin::N),
+            _ => Err(FrameError::IllegalFin),
+        }
+    }
 }
 
 impl OpCode {
@@ -59,6 +77,45 @@ impl OpCode {
         };
         Ok(opcode)
     }
+
+    /// Parse from byte, like [`from_flag`](Self::from_flag),
+    /// but return the raw opcode as an error if it is reserved
+    /// (0x03 - 0x07 for data frames, 0x0b - 0x0f for control frames),
+    /// which may be used by an extension.
+    #[inline]
+    pub const fn from_flag_lenient(b: u8) -> Result<Self, u8> {
+        match Self::from_flag(b) {
+            Ok(opcode) => Ok(opcode),
+            Err(_) => Err(b & 0x0f),
+        }
+    }
+
+    /// Get the flag byte.
+    #[inline]
+    pub const fn to_flag(&self) -> u8 { *self as u8 }
+
+    /// Close, Ping or Pong.
+    #[inline]
+    pub const fn is_control(&self) -> bool {
+        matches!(self, OpCode::Close | OpCode::Ping | OpCode::Pong)
+    }
+
+    /// Continue, Text or Binary.
+    #[inline]
+    pub const fn is_data(&self) -> bool { !self.is_control() }
+}
+
+/// Convert from a raw opcode number, e.g. `2` for `Binary`.
+impl TryFrom<u8> for OpCode {
+    type Error = FrameError;
+
+    #[inline]
+    fn try_from(b: u8) -> Result<Self, Self::Error> {
+        if b > 0x0f {
+            return Err(FrameError::IllegalOpCode);
+        }
+        Self::from_flag(b)
+    }
 }
 
 #[cfg(test)]
@@ -70,6 +127,8 @@ mod test {
             $(
                 let v = $class::from_flag($v).unwrap();
                 assert_eq!(v as u8, $v);
+                assert_eq!(v.to_flag(), $v);
+                assert_eq!($class::try_from($v as u8), Ok(v));
             )+
         };
     }
@@ -83,4 +142,43 @@ mod test {
     fn opcode() {
         enc_dec!(OpCode, 0x00, 0x01, 0x02, 0x08, 0x09, 0x0a);
     }
+
+    #[test]
+    fn opcode_category() {
+        use OpCode::*;
+        for opcode in [Continue, Text, Binary] {
+            assert!(opcode.is_data());
+            assert!(!opcode.is_control());
+        }
+        for opcode in [Close, Ping, Pong] {
+            assert!(opcode.is_control());
+            assert!(!opcode.is_data());
+        }
+    }
+
+    #[test]
+    fn opcode_reserved() {
+        for b in 0..=0x0f_u8 {
+            let flag = 0x80 | b;
+            match OpCode::from_flag(flag) {
+                Ok(opcode) => assert_eq!(OpCode::from_flag_lenient(flag), Ok(opcode)),
+                Err(_) => assert_eq!(OpCode::from_flag_lenient(flag), Err(b)),
+            }
+        }
+        assert_eq!(OpCode::from_flag_lenient(0x03), Err(0x03));
+        assert_eq!(OpCode::from_flag_lenient(0x8b), Err(0x0b));
+    }
+
+    #[test]
+    fn try_from_u8() {
+        assert_eq!(OpCode::try_from(2_u8), Ok(OpCode::Binary));
+        assert_eq!(Fin::try_from(0x80_u8), Ok(Fin::Y));
+
+        for b in [0x03_u8, 0x07, 0x0b, 0x0f, 0x10, 0x82, 0xff] {
+            assert_eq!(OpCode::try_from(b), Err(FrameError::IllegalOpCode));
+        }
+        for b in [0x01_u8, 0x40, 0x81, 0x8f, 0xff] {
+            assert_eq!(Fin::try_from(b), Err(FrameError::IllegalFin));
+        }
+    }
 }
@@ -1,5 +1,7 @@
 //! Payload length.
 
+use crate::error::FrameError;
+
 /// Payload length.
 ///
 /// Could be 7 bits, 7+16 bits, or 7+64 bits.
@@ -14,7 +16,16 @@ pub enum PayloadLen {
 }
 
 impl PayloadLen {
+    /// Max payload length, `2^63 - 1`, since the most significant bit
+    /// of a 64-bit length must be 0.
+    ///
+    /// [RFC-6455 Section 5.2](https://datatracker.ietf.org/doc/html/rfc6455#section-5.2)
+    pub const MAX: u64 = (1 << 63) - 1;
+
     /// Parse from number.
+    ///
+    /// Caller should ensure `n <= MAX`, otherwise the length can not be encoded,
+    /// see [`try_from_num`](Self::try_from_num).
     #[inline]
     pub const fn from_num(n: u64) -> Self {
         if n < 126 {
@@ -26,6 +37,27 @@ impl PayloadLen {
         }
     }
 
+    /// Parse from number, fail with [`FrameError::IllegalLength`] if `n > MAX`.
+    #[inline]
+    pub const fn try_from_num(n: u64) -> Result<Self, FrameError> {
+        if n > Self::MAX {
+            return Err(FrameError::IllegalLength);
+        }
+        Ok(Self::from_num(n))
+    }
+
+    /// Check if the length is in its shortest form,
+    /// e.g. a 16-bit length must not be less than 126.
+    #[inline]
+    pub const fn is_minimal(&self) -> bool {
+        use PayloadLen::*;
+        match self {
+            Standard(_) => true,
+            Extended1(v) => *v >= 126,
+            Extended2(v) => *v >= 65536,
+        }
+    }
+
     /// Convert to number.
     #[inline]
     pub const fn to_num(self) -> u64 {
@@ -98,6 +130,23 @@ impl PayloadLen {
     }
 }
 
+impl From<u64> for PayloadLen {
+    #[inline]
+    fn from(n: u64) -> Self { Self::from_num(n) }
+}
+
+impl From<usize> for PayloadLen {
+    #[inline]
+    fn from(n: usize) -> Self { Self::from_num(n as u64) }
+}
+
+/// Print the numeric length.
+impl core::fmt::Display for PayloadLen {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.to_num())
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -134,4 +183,34 @@ mod test {
             assert_eq!(a.to_num(), b.to_num());
         }
     }
+
+    #[test]
+    fn convert() {
+        for v in [0_usize, 125, 126, 65535, 65536, 100000] {
+            let a = PayloadLen::from_num(v as u64);
+            assert_eq!(PayloadLen::from(v), a);
+            assert_eq!(PayloadLen::from(v as u64), a);
+            assert_eq!(a.to_string(), v.to_string());
+        }
+    }
+
+    #[test]
+    fn checked() {
+        for v in [0, 125, 126, 65535, 65536, PayloadLen::MAX] {
+            assert_eq!(PayloadLen::try_from_num(v), Ok(PayloadLen::from_num(v)));
+        }
+        for v in [PayloadLen::MAX + 1, u64::MAX] {
+            assert_eq!(PayloadLen::try_from_num(v), Err(FrameError::IllegalLength));
+        }
+    }
+
+    #[test]
+    fn minimal() {
+        for v in [0, 125, 126, 65535, 65536, PayloadLen::MAX] {
+            assert!(PayloadLen::from_num(v).is_minimal());
+        }
+        assert!(!PayloadLen::Extended1(125).is_minimal());
+        assert!(!PayloadLen::Extended2(0).is_minimal());
+        assert!(!PayloadLen::Extended2(65535).is_minimal());
+    }
 }
@@ -48,6 +48,7 @@ impl Mask {
 }
 
 /// Generate a new random mask key.
+#[cfg(feature = "std")]
 #[inline]
 pub fn new_mask_key() -> [u8; 4] { rand::random::<[u8; 4]>() }
 
@@ -85,6 +86,17 @@ pub fn apply_mask4(key: [u8; 4], buf: &mut [u8]) {
     apply_mask(key4.to_ne_bytes(), suffix);
 }
 
+/// Mask the buffer, 4 bytes at a time, starting from `offset` of the key.
+///
+/// This continues to mask a payload that is split into several buffers,
+/// where `offset` is the number of bytes masked before.
+#[inline]
+pub fn apply_mask_with_offset(key: [u8; 4], buf: &mut [u8], offset: usize) {
+    let mut key = key;
+    key.rotate_left(offset & 3);
+    apply_mask4(key, buf);
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -99,7 +111,7 @@ mod test {
     #[test]
     fn mask_byte() {
         let key: [u8; 4] = rand::random();
-        let buf: Vec<u8> = std::iter::repeat(rand::random::<u8>()).take(1024).collect();
+        let buf: Vec<u8> = std::iter::repeat_n(rand::random::<u8>(), 1024).collect();
 
         assert_eq!(buf.len(), 1024);
 
@@ -114,7 +126,7 @@ mod test {
     fn mask_byte4() {
         for i in 0..4096 {
             let key: [u8; 4] = rand::random();
-            let buf: Vec<u8> = std::iter::repeat(rand::random::<u8>()).take(i).collect();
+            let buf: Vec<u8> = std::iter::repeat_n(rand::random::<u8>(), i).collect();
 
             assert_eq!(buf.len(), i);
 
@@ -125,4 +137,30 @@ mod test {
             assert_eq!(buf, buf2);
         }
     }
+
+    #[test]
+    fn mask_with_offset() {
+        for i in 0..1024 {
+            let key: [u8; 4] = rand::random();
+            let buf: Vec<u8> = (0..i).map(|_| rand::random::<u8>()).collect();
+
+            let mut buf2 = buf.clone();
+            apply_mask4(key, &mut buf2);
+
+            for split in [1, 2, 3, 5, 6, 7].into_iter().filter(|x| *x <= i) {
+                let mut buf3 = buf.clone();
+                let (left, right) = buf3.split_at_mut(split);
+                apply_mask4(key, left);
+                apply_mask_with_offset(key, right, split);
+                assert_eq!(buf2, buf3);
+            }
+
+            // byte by byte
+            let mut buf3 = buf.clone();
+            for (offset, b) in buf3.iter_mut().enumerate() {
+                apply_mask_with_offset(key, std::slice::from_mut(b), offset);
+            }
+            assert_eq!(buf2, buf3);
+        }
+    }
 }
@@ -27,10 +27,15 @@
 pub mod flag;
 pub mod length;
 pub mod mask;
+pub mod reader;
 
 pub use flag::{Fin, OpCode};
 pub use length::PayloadLen;
-pub use mask::{Mask, new_mask_key, apply_mask4};
+pub use mask::{Mask, apply_mask4, apply_mask_with_offset};
+pub use reader::{FrameReader, decode_all};
+
+#[cfg(feature = "std")]
+pub use mask::new_mask_key;
 
 /// Websocket frame head.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -57,15 +62,144 @@ impl FrameHead {
         }
     }
 
+    /// Max payload length of a control frame.
+    pub const MAX_CONTROL_LEN: u64 = 125;
+
+    /// Create the head of a final control frame, a length over
+    /// [`MAX_CONTROL_LEN`](Self::MAX_CONTROL_LEN) is refused with [`FrameError::IllegalLength`].
+    #[inline]
+    const fn control(opcode: OpCode, mask: Mask, len: u64) -> Result<Self, FrameError> {
+        if len > Self::MAX_CONTROL_LEN {
+            return Err(FrameError::IllegalLength);
+        }
+        Ok(Self::new(
+            Fin::Y,
+            opcode,
+            mask,
+            PayloadLen::Standard(len as u8),
+        ))
+    }
+
+    /// Create the head of a ping frame, see [`validate`](Self::validate).
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use lightws::frame::{FrameHead, Fin, OpCode, Mask, PayloadLen};
+    /// let head = FrameHead::ping(Mask::None, 5).unwrap();
+    ///
+    /// assert_eq!(head, FrameHead::new(Fin::Y, OpCode::Ping, Mask::None, PayloadLen::from_num(5)));
+    /// assert!(FrameHead::ping(Mask::None, 126).is_err());
+    /// ```
+    #[inline]
+    pub const fn ping(mask: Mask, len: u64) -> Result<Self, FrameError> {
+        Self::control(OpCode::Ping, mask, len)
+    }
+
+    /// Create the head of a pong frame, see [`validate`](Self::validate).
+    #[inline]
+    pub const fn pong(mask: Mask, len: u64) -> Result<Self, FrameError> {
+        Self::control(OpCode::Pong, mask, len)
+    }
+
+    /// Create the head of a close frame, see [`validate`](Self::validate).
+    #[inline]
+    pub const fn close(mask: Mask, len: u64) -> Result<Self, FrameError> {
+        Self::control(OpCode::Close, mask, len)
+    }
+
+    /// Create the head of a final text frame.
+    #[inline]
+    pub const fn text(mask: Mask, len: u64) -> Self {
+        Self::new(Fin::Y, OpCode::Text, mask, PayloadLen::from_num(len))
+    }
+
+    /// Create the head of a final binary frame.
+    #[inline]
+    pub const fn binary(mask: Mask, len: u64) -> Self {
+        Self::new(Fin::Y, OpCode::Binary, mask, PayloadLen::from_num(len))
+    }
+
+    /// Check if this head could be sent.
+    ///
+    /// A control frame must be final, otherwise it fails with [`FrameError::IllegalFin`],
+    /// and must not carry more than [`MAX_CONTROL_LEN`](Self::MAX_CONTROL_LEN) bytes,
+    /// otherwise it fails with [`FrameError::IllegalLength`].
+    /// A length over [`PayloadLen::MAX`] also fails with [`FrameError::IllegalLength`].
+    #[inline]
+    pub const fn validate(&self) -> Result<(), FrameError> {
+        let len = self.payload_len();
+        if len > PayloadLen::MAX {
+            return Err(FrameError::IllegalLength);
+        }
+        if self.is_control() {
+            if !self.is_fin() {
+                return Err(FrameError::IllegalFin);
+            }
+            if len > Self::MAX_CONTROL_LEN {
+                return Err(FrameError::IllegalLength);
+            }
+        }
+        Ok(())
+    }
+
+    /// Get the count of bytes to encode, from 2 to 14.
+    #[inline]
+    pub const fn encoded_len(&self) -> usize {
+        let ext_len = match self.length {
+            PayloadLen::Standard(_) => 0,
+            PayloadLen::Extended1(_) => 2,
+            PayloadLen::Extended2(_) => 8,
+        };
+        let mask_len = match self.mask {
+            Mask::Key(_) | Mask::Skip => 4,
+            Mask::None => 0,
+        };
+        2 + ext_len + mask_len
+    }
+
+    /// Get the payload length as a number.
+    ///
+    /// Example:
+    ///
+    /// ```
+    /// use lightws::frame::{FrameHead, OpCode};
+    /// let buf = [0x82, 0x7e, 0x01, 0x00];
+    /// let (head, n) = FrameHead::decode(&buf).unwrap();
+    ///
+    /// assert_eq!(n, 4);
+    /// assert_eq!(head.opcode, OpCode::Binary);
+    /// assert_eq!(head.payload_len(), 256);
+    /// assert!(head.is_fin());
+    /// assert!(!head.is_control());
+    /// ```
+    #[inline]
+    pub const fn payload_len(&self) -> u64 { self.length.to_num() }
+
+    /// Check if this is the last frame of a message.
+    #[inline]
+    pub const fn is_fin(&self) -> bool { matches!(self.fin, Fin::Y) }
+
+    /// Check if this is a control frame, see [`OpCode::is_control`].
+    #[inline]
+    pub const fn is_control(&self) -> bool { self.opcode.is_control() }
+
     /// Encode to provided buffer, return the count of written bytes.
     ///
     /// Caller should ensure there is enough space to write,
+    /// see [`encoded_len`](Self::encoded_len),
     /// otherwise a [`FrameError::NotEnoughCapacity`] error will be returned.
+    ///
+    /// A length over [`PayloadLen::MAX`] is refused with [`FrameError::IllegalLength`].
     pub fn encode(&self, buf: &mut [u8]) -> Result<usize, FrameError> {
         if buf.len() < 2 {
             return Err(FrameError::NotEnoughCapacity);
         }
 
+        if self.length.to_num() > PayloadLen::MAX {
+            return Err(FrameError::IllegalLength);
+        }
+
         let mut writer = Writer::new(buf);
 
         macro_rules! writex {
@@ -81,7 +215,7 @@ impl FrameHead {
         }
 
         // fin, opcode
-        let b1 = self.fin as u8 | self.opcode as u8;
+        let b1 = self.fin.to_flag() | self.opcode.to_flag();
 
         // mask, payload length
         let b2 = self.mask.to_flag() | self.length.to_flag();
@@ -105,7 +239,7 @@ impl FrameHead {
         Ok(writer.pos())
     }
 
-    /// Unchecked version of [`encode`](Self::encode).
+    /// Unchecked version of [`encode`](Self::encode), where the length is not checked either.
     ///
     /// # Safety
     ///
@@ -121,7 +255,7 @@ impl FrameHead {
         }
 
         // fin, opcode
-        let b1 = self.fin as u8 | self.opcode as u8;
+        let b1 = self.fin.to_flag() | self.opcode.to_flag();
 
         // mask, payload length
         let b2 = self.mask.to_flag() | self.length.to_flag();
@@ -145,11 +279,91 @@ impl FrameHead {
         writer.pos()
     }
 
+    /// Encode to a fixed array in const context, e.g. to build a `static` frame head.
+    ///
+    /// `N` must be equal to [`encoded_len`](Self::encoded_len),
+    /// and the length must not exceed [`PayloadLen::MAX`],
+    /// otherwise it panics, which fails to compile in const context.
+    pub const fn encode_const<const N: usize>(&self) -> [u8; N] {
+        assert!(N == self.encoded_len(), "array length mismatches");
+        assert!(self.length.to_num() <= PayloadLen::MAX, "illegal length");
+
+        let mut buf = [0_u8; N];
+
+        // fin, opcode
+        buf[0] = self.fin.to_flag() | self.opcode.to_flag();
+
+        // mask, payload length
+        buf[1] = self.mask.to_flag() | self.length.to_flag();
+
+        macro_rules! writex {
+            ($pos: expr, $src: expr) => {{
+                let src = $src;
+                let mut i = 0;
+                while i < src.len() {
+                    buf[$pos + i] = src[i];
+                    i += 1;
+                }
+                $pos + i
+            }};
+        }
+
+        // extended payload length
+        let pos = match self.length {
+            PayloadLen::Standard(_) => 2,
+            PayloadLen::Extended1(v) => writex!(2, v.to_be_bytes()),
+            PayloadLen::Extended2(v) => writex!(2, v.to_be_bytes()),
+        };
+
+        // mask key, zeroed if skipped
+        if let Mask::Key(k) = self.mask {
+            let _ = writex!(pos, k);
+        }
+
+        buf
+    }
+
     /// Parse from provided buffer, returns [`FrameHead`] and the count of read bytes.
     ///
     /// If there is not enough data to parse, a [`FrameError::NotEnoughData`] error
     /// will be returned.
+    #[inline]
     pub fn decode(buf: &[u8]) -> Result<(Self, usize), FrameError> {
+        FrameHeadRef::decode(buf).map(|(head, n)| (head.into(), n))
+    }
+
+    /// Parse from provided buffer, returns [`FrameHeadRef`] and the count of read bytes.
+    ///
+    /// See [`FrameHeadRef::decode`].
+    #[inline]
+    pub fn decode_ref(buf: &[u8]) -> Result<(FrameHeadRef<'_>, usize), FrameError> {
+        FrameHeadRef::decode(buf)
+    }
+
+    /// Strict version of [`decode`](Self::decode),
+    /// see [`FrameHeadRef::decode_strict`].
+    #[inline]
+    pub fn decode_strict(buf: &[u8]) -> Result<(Self, usize), FrameError> {
+        FrameHeadRef::decode_strict(buf).map(|(head, n)| (head.into(), n))
+    }
+}
+
+/// Websocket frame head, which borrows the mask key from the parsed buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameHeadRef<'a> {
+    pub fin: Fin,
+    pub opcode: OpCode,
+    pub mask_key: Option<&'a [u8; 4]>,
+    pub length: PayloadLen,
+}
+
+impl<'a> FrameHeadRef<'a> {
+    /// Parse from provided buffer, returns [`FrameHeadRef`] and the count of read bytes.
+    ///
+    /// If there is not enough data to parse, a [`FrameError::NotEnoughData`] error
+    /// will be returned. A length over [`PayloadLen::MAX`] is rejected with
+    /// [`FrameError::IllegalLength`].
+    pub fn decode(buf: &'a [u8]) -> Result<(Self, usize), FrameError> {
         if buf.len() < 2 {
             return Err(FrameError::NotEnoughData);
         }
@@ -165,62 +379,97 @@ impl FrameHead {
         let fin = Fin::from_flag(b1)?;
         let opcode = OpCode::from_flag(b1)?;
 
-        let mut mask = Mask::from_flag(b2)?;
+        let mask = Mask::from_flag(b2)?;
         let mut length = PayloadLen::from_flag(b2);
 
         match length {
             PayloadLen::Standard(_) => {}
             PayloadLen::Extended1(_) => {
-                if buf.len() - n < 2 {
+                if buf.len() < n + 2 {
                     return Err(FrameError::NotEnoughData);
                 }
 
-                length =
-                    PayloadLen::from_byte2(unsafe { *slice_to_array::<_, 2>(slice(buf, 2, 4)) });
+                length = PayloadLen::from_byte2(unsafe {
+                    *slice_to_array::<_, 2>(slice(buf, n, n + 2))
+                });
 
                 n += 2;
             }
             PayloadLen::Extended2(_) => {
-                if buf.len() - n < 8 {
+                if buf.len() < n + 8 {
                     return Err(FrameError::NotEnoughData);
                 }
 
-                length =
-                    PayloadLen::from_byte8(unsafe { *slice_to_array::<_, 8>(slice(buf, 2, 10)) });
+                length = PayloadLen::from_byte8(unsafe {
+                    *slice_to_array::<_, 8>(slice(buf, n, n + 8))
+                });
+
+                // the most significant bit must be 0
+                if length.to_num() > PayloadLen::MAX {
+                    return Err(FrameError::IllegalLength);
+                }
 
                 n += 8;
             }
         };
 
-        match mask {
-            Mask::None => {}
+        let mask_key = match mask {
+            Mask::None => None,
             _ => {
-                if buf.len() - n < 4 {
+                if buf.len() < n + 4 {
                     return Err(FrameError::NotEnoughData);
                 }
 
-                let key = *unsafe { slice_to_array::<_, 4>(slice(buf, n, n + 4)) };
-
-                if key.into_iter().all(|b| b == 0) {
-                    mask = Mask::Skip
-                } else {
-                    mask = Mask::Key(key)
-                }
+                let key = unsafe { slice_to_array::<_, 4>(slice(buf, n, n + 4)) };
 
                 n += 4;
+
+                Some(key)
             }
-        }
+        };
 
         Ok((
-            FrameHead {
+            FrameHeadRef {
                 fin,
                 opcode,
-                mask,
+                mask_key,
                 length,
             },
             n,
         ))
     }
+
+    /// Same as [`decode`](Self::decode), but a length which is not in its
+    /// shortest form (see [`PayloadLen::is_minimal`]) is rejected with
+    /// [`FrameError::IllegalLength`].
+    ///
+    /// [RFC-6455 Section 5.2](https://datatracker.ietf.org/doc/html/rfc6455#section-5.2)
+    pub fn decode_strict(buf: &'a [u8]) -> Result<(Self, usize), FrameError> {
+        let (head, n) = Self::decode(buf)?;
+        if !head.length.is_minimal() {
+            return Err(FrameError::IllegalLength);
+        }
+        Ok((head, n))
+    }
+}
+
+impl From<FrameHeadRef<'_>> for FrameHead {
+    /// Copy the mask key, use [`Mask::Skip`] if the key is 0.
+    #[inline]
+    fn from(head: FrameHeadRef<'_>) -> Self {
+        let mask = match head.mask_key {
+            None => Mask::None,
+            Some(key) if key.iter().all(|b| *b == 0) => Mask::Skip,
+            Some(key) => Mask::Key(*key),
+        };
+
+        FrameHead {
+            fin: head.fin,
+            opcode: head.opcode,
+            mask,
+            length: head.length,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -232,14 +481,14 @@ mod test {
         let head = FrameHead {
             fin: Fin::Y,
             opcode: OpCode::Binary,
-            mask: Mask::Key(mask::new_mask_key()),
+            mask: Mask::Key(rand::random()),
             length: PayloadLen::from_num(4096),
         };
 
         let head2 = FrameHead {
             fin: Fin::N,
             opcode: OpCode::Binary,
-            mask: Mask::Key(mask::new_mask_key()),
+            mask: Mask::Key(rand::random()),
             length: PayloadLen::from_num(64),
         };
 
@@ -262,4 +511,240 @@ mod test {
             assert_eq!(&buf[0..encode_n], &buf2[0..encode_n2]);
         }
     }
+
+    #[test]
+    fn frame_head_encode_const() {
+        const PING: [u8; 2] =
+            FrameHead::new(Fin::Y, OpCode::Ping, Mask::None, PayloadLen::from_num(0))
+                .encode_const();
+        assert_eq!(PING, [0x89, 0x00]);
+
+        macro_rules! check {
+            ($n: expr, $mask: expr, $len: expr) => {{
+                let head =
+                    FrameHead::new(Fin::N, OpCode::Binary, $mask, PayloadLen::from_num($len));
+                let mut buf = [0_u8; 14];
+                let n = head.encode(&mut buf).unwrap();
+                assert_eq!(n, $n);
+                assert_eq!(head.encode_const::<$n>(), &buf[..n]);
+            }};
+        }
+
+        check!(2, Mask::None, 125);
+        check!(6, Mask::Key(rand::random()), 125);
+        check!(6, Mask::Skip, 125);
+        check!(4, Mask::None, 126);
+        check!(8, Mask::Key(rand::random()), 0xffff);
+        check!(10, Mask::None, 0x10000);
+        check!(14, Mask::Key(rand::random()), PayloadLen::MAX);
+    }
+
+    #[test]
+    #[should_panic]
+    fn frame_head_encode_const_mismatch() {
+        let head = FrameHead::new(Fin::Y, OpCode::Ping, Mask::None, PayloadLen::from_num(0));
+        let _ = head.encode_const::<6>();
+    }
+
+    #[test]
+    fn frame_head_ref() {
+        let key = rand::random();
+        let head = FrameHead {
+            fin: Fin::Y,
+            opcode: OpCode::Text,
+            mask: Mask::Key(key),
+            length: PayloadLen::from_num(300),
+        };
+
+        let mut buf = vec![0; 32];
+        let encode_n = head.encode(&mut buf).unwrap();
+
+        let (head_ref, decode_n) = FrameHead::decode_ref(&buf).unwrap();
+        assert_eq!(encode_n, decode_n);
+        assert_eq!(head_ref.mask_key, Some(&key));
+        assert!(core::ptr::eq(
+            head_ref.mask_key.unwrap().as_ptr(),
+            buf[encode_n - 4..].as_ptr()
+        ));
+        assert_eq!(FrameHead::from(head_ref), head);
+
+        // all-zero key is skipped once copied
+        let head = FrameHead {
+            mask: Mask::Skip,
+            ..head
+        };
+        head.encode(&mut buf).unwrap();
+        let (head_ref, _) = FrameHead::decode_ref(&buf).unwrap();
+        assert_eq!(head_ref.mask_key, Some(&[0; 4]));
+        assert_eq!(FrameHead::from(head_ref), head);
+
+        assert!(matches!(
+            FrameHead::decode_ref(&buf[..encode_n - 1]),
+            Err(FrameError::NotEnoughData)
+        ));
+    }
+
+    #[test]
+    fn frame_head_decode_arbitrary() {
+        use rand::prelude::*;
+
+        // every head flag, with every truncated length
+        for b1 in [
+            0x00, 0x01, 0x02, 0x08, 0x09, 0x0a, 0x0f, 0x70, 0x81, 0x82, 0xff,
+        ] {
+            for b2 in 0..=u8::MAX {
+                let mut buf = [0_u8; 14];
+                buf[0] = b1;
+                buf[1] = b2;
+                for n in 0..=buf.len() {
+                    let _ = FrameHead::decode(&buf[..n]);
+                }
+            }
+        }
+
+        // random bytes, mirrors fuzz/fuzz_targets/frame_head.rs
+        let mut buf = [0_u8; 16];
+        for _ in 0..0x10000 {
+            thread_rng().fill_bytes(&mut buf);
+            let n = thread_rng().gen_range(0..=buf.len());
+            if let Ok((head, parse_n)) = FrameHead::decode(&buf[..n]) {
+                assert!(parse_n <= n);
+                let mut buf2 = [0_u8; 14];
+                assert_eq!(head.encode(&mut buf2), Ok(parse_n));
+            }
+        }
+    }
+
+    #[test]
+    fn frame_head_encoded_len() {
+        for mask in [Mask::Key([1, 2, 3, 4]), Mask::Skip, Mask::None] {
+            for len in [0, 125, 126, 65535, 65536, PayloadLen::MAX] {
+                let head = FrameHead::new(Fin::Y, OpCode::Binary, mask, PayloadLen::from_num(len));
+
+                // exact buffer
+                let mut buf = vec![0; head.encoded_len()];
+                assert_eq!(head.encode(&mut buf), Ok(buf.len()));
+
+                let mut buf = vec![0; head.encoded_len() - 1];
+                assert_eq!(head.encode(&mut buf), Err(FrameError::NotEnoughCapacity));
+            }
+        }
+    }
+
+    #[test]
+    fn frame_head_boundary_round_trip() {
+        for mask in [Mask::Key([1, 2, 3, 4]), Mask::Skip, Mask::None] {
+            for (len, ext_len) in [(125, 0), (126, 2), (65535, 2), (65536, 8), (1 << 32, 8)] {
+                let head = FrameHead::new(Fin::Y, OpCode::Binary, mask, PayloadLen::from_num(len));
+                let mask_len = if matches!(mask, Mask::None) { 0 } else { 4 };
+                assert_eq!(head.encoded_len(), 2 + ext_len + mask_len);
+
+                // trailing bytes are not consumed
+                let mut buf = vec![0xff; head.encoded_len() + 8];
+                let encode_n = head.encode(&mut buf).unwrap();
+                assert_eq!(encode_n, head.encoded_len());
+
+                for decode in [FrameHead::decode, FrameHead::decode_strict] {
+                    let (head2, decode_n) = decode(&buf).unwrap();
+                    assert_eq!(decode_n, encode_n);
+                    assert_eq!(head2, head);
+                    assert_eq!(head2.payload_len(), len);
+                    assert_eq!(decode(&buf[..encode_n - 1]), Err(FrameError::NotEnoughData));
+                }
+
+                let (head_ref, decode_n) = FrameHead::decode_ref(&buf).unwrap();
+                assert_eq!(decode_n, encode_n);
+                assert_eq!(FrameHead::from(head_ref), head);
+            }
+        }
+    }
+
+    #[test]
+    fn frame_head_illegal_length() {
+        for len in [PayloadLen::MAX + 1, u64::MAX] {
+            let head = FrameHead::new(
+                Fin::Y,
+                OpCode::Binary,
+                Mask::None,
+                PayloadLen::from_num(len),
+            );
+            let mut buf = vec![0; head.encoded_len()];
+            assert_eq!(head.encode(&mut buf), Err(FrameError::IllegalLength));
+
+            unsafe { head.encode_unchecked(&mut buf) };
+            assert_eq!(FrameHead::decode(&buf), Err(FrameError::IllegalLength));
+        }
+    }
+
+    #[test]
+    fn frame_head_decode_strict() {
+        // 16-bit length of 125, 64-bit length of 65535
+        let buf = [0x82, 0x7e, 0x00, 0x7d];
+        assert!(FrameHead::decode(&buf).is_ok());
+        assert_eq!(
+            FrameHead::decode_strict(&buf),
+            Err(FrameError::IllegalLength)
+        );
+
+        let buf = [0x82, 0x7f, 0, 0, 0, 0, 0, 0, 0xff, 0xff];
+        assert!(FrameHead::decode(&buf).is_ok());
+        assert_eq!(
+            FrameHead::decode_strict(&buf),
+            Err(FrameError::IllegalLength)
+        );
+
+        for len in [0, 125, 126, 65535, 65536] {
+            let head = FrameHead::new(
+                Fin::Y,
+                OpCode::Binary,
+                Mask::None,
+                PayloadLen::from_num(len),
+            );
+            let mut buf = vec![0; head.encoded_len()];
+            let n = head.encode(&mut buf).unwrap();
+            assert_eq!(FrameHead::decode_strict(&buf), Ok((head, n)));
+        }
+    }
+
+    #[test]
+    fn frame_head_typed() {
+        type Ctor = fn(Mask, u64) -> Result<FrameHead, FrameError>;
+        let controls: [(Ctor, OpCode); 3] = [
+            (FrameHead::ping, OpCode::Ping),
+            (FrameHead::pong, OpCode::Pong),
+            (FrameHead::close, OpCode::Close),
+        ];
+
+        for mask in [Mask::Key([1, 2, 3, 4]), Mask::Skip, Mask::None] {
+            for (ctor, opcode) in controls {
+                for len in [0, 2, 125] {
+                    let head = ctor(mask, len).unwrap();
+                    let expect = FrameHead::new(Fin::Y, opcode, mask, PayloadLen::from_num(len));
+                    assert_eq!(head, expect);
+                    assert!(head.is_control());
+                    assert_eq!(head.validate(), Ok(()));
+                }
+                assert_eq!(ctor(mask, 126), Err(FrameError::IllegalLength));
+            }
+
+            for len in [0, 125, 126, 65536] {
+                let head = FrameHead::text(mask, len);
+                assert_eq!(head.opcode, OpCode::Text);
+                assert!(head.is_fin());
+                assert_eq!(head.payload_len(), len);
+                assert_eq!(head.validate(), Ok(()));
+                assert_eq!(FrameHead::binary(mask, len).opcode, OpCode::Binary);
+            }
+        }
+
+        // invalid heads built by hand
+        let len = PayloadLen::from_num(126);
+        let head = FrameHead::new(Fin::Y, OpCode::Ping, Mask::None, len);
+        assert_eq!(head.validate(), Err(FrameError::IllegalLength));
+        let head = FrameHead::new(Fin::N, OpCode::Close, Mask::None, PayloadLen::from_num(2));
+        assert_eq!(head.validate(), Err(FrameError::IllegalFin));
+        let len = PayloadLen::from_num(PayloadLen::MAX + 1);
+        let head = FrameHead::new(Fin::Y, OpCode::Binary, Mask::None, len);
+        assert_eq!(head.validate(), Err(FrameError::IllegalLength));
+    }
 }
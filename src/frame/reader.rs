@@ -0,0 +1,192 @@
+//! Frame iterator over a static buffer.
+
+use core::ops::Range;
+use alloc::vec::Vec;
+
+use super::FrameHead;
+use crate::error::FrameError;
+
+/// Iterate frames out of an in-memory buffer, e.g. a captured traffic dump.
+///
+/// Each item is a frame head and its payload, which is **not** unmasked.
+/// Iteration stops at a partial trailing frame,
+/// which could be retrieved by [`remaining`](Self::remaining).
+/// Iteration also stops after an error is returned.
+///
+/// Example:
+///
+/// ```
+/// use lightws::frame::{FrameReader, OpCode};
+/// let buf = [0x82, 0x01, b'a', 0x89, 0x00, 0x82, 0x02, b'b'];
+/// let mut reader = FrameReader::new(&buf);
+///
+/// let (head, payload) = reader.next().unwrap().unwrap();
+/// assert_eq!((head.opcode, payload), (OpCode::Binary, b"a".as_slice()));
+///
+/// let (head, payload) = reader.next().unwrap().unwrap();
+/// assert_eq!((head.opcode, payload), (OpCode::Ping, b"".as_slice()));
+///
+/// assert!(reader.next().is_none());
+/// assert_eq!(reader.remaining(), [0x82, 0x02, b'b']);
+/// ```
+#[derive(Debug, Clone)]
+pub struct FrameReader<'a> {
+    buf: &'a [u8],
+    error: bool,
+}
+
+impl<'a> FrameReader<'a> {
+    /// Constructor, take the provided buffer.
+    #[inline]
+    pub const fn new(buf: &'a [u8]) -> Self { Self { buf, error: false } }
+
+    /// Get the unparsed data, starting from the next frame head.
+    #[inline]
+    pub const fn remaining(&self) -> &'a [u8] { self.buf }
+
+    /// Check if the iteration is stopped by an error.
+    #[inline]
+    pub const fn is_error(&self) -> bool { self.error }
+}
+
+impl<'a> Iterator for FrameReader<'a> {
+    type Item = Result<(FrameHead, &'a [u8]), FrameError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.error {
+            return None;
+        }
+
+        let (head, parse_n) = match FrameHead::decode(self.buf) {
+            Ok(x) => x,
+            Err(FrameError::NotEnoughData) => return None,
+            Err(e) => {
+                self.error = true;
+                return Some(Err(e));
+            }
+        };
+
+        // partial payload
+        let payload_len = head.length.to_num();
+        if payload_len > (self.buf.len() - parse_n) as u64 {
+            return None;
+        }
+
+        let (payload, buf) = self.buf[parse_n..].split_at(payload_len as usize);
+        self.buf = buf;
+
+        Some(Ok((head, payload)))
+    }
+}
+
+/// Decode all complete frames out of a buffer, return each frame head
+/// and the range of its payload, which is **not** unmasked.
+///
+/// A partial trailing frame is ignored, while an illegal frame head fails.
+/// This never panics on arbitrary input, and allocates at most
+/// one item per two bytes.
+pub fn decode_all(buf: &[u8]) -> Result<Vec<(FrameHead, Range<usize>)>, FrameError> {
+    let mut reader = FrameReader::new(buf);
+    let mut frames = Vec::new();
+    while let Some(item) = reader.next() {
+        let (head, payload) = item?;
+        let beg = buf.len() - reader.remaining().len() - payload.len();
+        frames.push((head, beg..beg + payload.len()));
+    }
+    Ok(frames)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::frame::{Fin, Mask, OpCode, PayloadLen};
+
+    #[test]
+    fn read_frames() {
+        let mut buf = Vec::new();
+        let mut frames = Vec::new();
+        for (i, len) in [0_usize, 1, 125, 126, 65535, 65536].into_iter().enumerate() {
+            let mask = if i % 2 == 0 {
+                Mask::None
+            } else {
+                Mask::Key([1, 2, 3, 4])
+            };
+            let head = FrameHead::new(
+                Fin::Y,
+                OpCode::Binary,
+                mask,
+                PayloadLen::from_num(len as u64),
+            );
+            let payload = vec![i as u8; len];
+
+            let mut tmp = [0_u8; 14];
+            let n = head.encode(&mut tmp).unwrap();
+            buf.extend_from_slice(&tmp[..n]);
+            buf.extend_from_slice(&payload);
+            frames.push((head, payload));
+        }
+
+        // every truncated buffer yields complete frames only
+        for end in [0, 1, 2, 3, 128, 131, 132, buf.len() - 1, buf.len()] {
+            let mut reader = FrameReader::new(&buf[..end]);
+            let mut consumed = 0;
+            for (item, (head, payload)) in reader.by_ref().zip(frames.iter()) {
+                let (head2, payload2) = item.unwrap();
+                assert_eq!(&head2, head);
+                assert_eq!(payload2, payload.as_slice());
+                consumed += head.length.to_num() as usize;
+            }
+            assert!(!reader.is_error());
+            assert!(consumed <= end);
+            assert_eq!(
+                reader.remaining().as_ptr(),
+                buf[end - reader.remaining().len()..].as_ptr()
+            );
+        }
+
+        // partial mask key
+        let buf = [0x82, 0x00, 0x82, 0x80, 0x00];
+        let mut reader = FrameReader::new(&buf);
+        assert!(reader.next().unwrap().is_ok());
+        assert!(reader.next().is_none());
+        assert_eq!(reader.remaining(), &buf[2..]);
+
+        // stop after an error
+        let buf = [0x82, 0x00, 0xf2, 0x00, 0x82, 0x00];
+        let mut reader = FrameReader::new(&buf);
+        assert!(reader.next().unwrap().is_ok());
+        assert!(reader.next().unwrap().is_err());
+        assert!(reader.is_error());
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn decode_all_frames() {
+        let buf = [
+            0x82, 0x01, b'a', 0x89, 0x00, 0x82, 0x82, 1, 2, 3, 4, b'b', b'c', 0x82,
+        ];
+        let frames = decode_all(&buf).unwrap();
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[0].1, 2..3);
+        assert_eq!(frames[1].1, 5..5);
+        assert_eq!(frames[2].0.mask, Mask::Key([1, 2, 3, 4]));
+        assert_eq!(&buf[frames[2].1.clone()], b"bc");
+
+        assert_eq!(decode_all(&[]), Ok(Vec::new()));
+        assert_eq!(
+            decode_all(&[0x82, 0x01, b'a', 0xf2, 0x00]),
+            Err(FrameError::IllegalFin)
+        );
+
+        // arbitrary input
+        for _ in 0..1024 {
+            let len = rand::random::<u8>() as usize;
+            let buf: Vec<u8> = (0..len).map(|_| rand::random()).collect();
+            if let Ok(frames) = decode_all(&buf) {
+                for (_, range) in frames {
+                    assert!(range.end <= buf.len());
+                }
+            }
+        }
+    }
+}
@@ -4,17 +4,34 @@ use super::GUID;
 use base64::Engine;
 use base64::engine::general_purpose::STANDARD;
 use sha1::{Digest, Sha1};
+use rand::RngCore;
 
 /// Generate a new `sec-websocket-key`.
+#[cfg(feature = "std")]
 #[inline]
-pub fn new_sec_key() -> [u8; 24] {
-    let input: [u8; 16] = rand::random();
+pub fn new_sec_key() -> [u8; 24] { new_sec_key_with(&mut rand::thread_rng()) }
+
+/// Generate a new `sec-websocket-key` with the provided random number generator.
+#[inline]
+pub fn new_sec_key_with<R: RngCore + ?Sized>(rng: &mut R) -> [u8; 24] {
+    let mut input = [0_u8; 16];
+    rng.fill_bytes(&mut input);
     let mut output = [0_u8; 24];
     Engine::encode_slice(&STANDARD, input, &mut output).unwrap();
     output
 }
 
 /// Derive `sec-websocket-accept` from `sec-websocket-key`.
+///
+/// Example from [RFC-6455 Section 1.3](https://datatracker.ietf.org/doc/html/rfc6455#section-1.3):
+///
+/// ```
+/// use lightws::handshake::derive_accept_key;
+/// assert_eq!(
+///     &derive_accept_key(b"dGhlIHNhbXBsZSBub25jZQ=="),
+///     b"s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+/// );
+/// ```
 #[inline]
 pub fn derive_accept_key(sec_key: &[u8]) -> [u8; 28] {
     let mut sha1 = Sha1::default();
@@ -31,6 +48,7 @@ mod test {
     use super::*;
 
     #[test]
+    #[cfg(feature = "std")]
     fn generate_sec_key() {
         for _ in 0..=1024 {
             // should not panic
@@ -38,6 +56,14 @@ mod test {
         }
     }
 
+    #[test]
+    fn generate_sec_key_with_rng() {
+        use rand::rngs::mock::StepRng;
+        let key = new_sec_key_with(&mut StepRng::new(0, 0));
+        assert_eq!(&key, b"AAAAAAAAAAAAAAAAAAAAAA==");
+        assert_eq!(key, new_sec_key_with(&mut StepRng::new(0, 0)));
+    }
+
     #[test]
     fn derive_sec_key() {
         assert_eq!(
@@ -3,10 +3,17 @@
 pub mod key;
 pub mod request;
 pub mod response;
+pub mod negotiated;
 
-pub use request::Request;
-pub use response::Response;
-pub use key::{new_sec_key, derive_accept_key};
+pub use request::{Request, OwnedRequest};
+pub use response::{Response, OwnedResponse};
+pub use key::{derive_accept_key, new_sec_key_with};
+pub use negotiated::{Negotiated, Deflate};
+
+use crate::error::HandshakeError;
+
+#[cfg(feature = "std")]
+pub use key::new_sec_key;
 
 /// 32
 pub const MAX_ALLOW_HEADERS: usize = 32;
@@ -32,6 +39,14 @@ pub const HTTP_HEADER_SP: &[u8] = b": ";
 /// HTTP/1.1 101 Switching Protocols
 pub const HTTP_STATUS_LINE: &[u8] = b"HTTP/1.1 101 Switching Protocols";
 
+/// HTTP/1.1 403 Forbidden, without headers
+pub const HTTP_FORBIDDEN: &[u8] = b"HTTP/1.1 403 Forbidden\r\ncontent-length: 0\r\n\r\n";
+
+/// HTTP/1.1 426 Upgrade Required, with the supported websocket version
+pub const HTTP_UPGRADE_REQUIRED: &[u8] = b"HTTP/1.1 426 Upgrade Required\r\n\
+    sec-websocket-version: 13\r\n\
+    content-length: 0\r\n\r\n";
+
 /// Http header, take two references
 #[allow(clippy::len_without_is_empty)]
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
@@ -40,11 +55,6 @@ pub struct HttpHeader<'h> {
     pub value: &'h [u8],
 }
 
-// compile time computation
-trait HeaderHelper {
-    const SIZE: usize;
-}
-
 impl<'h> HttpHeader<'h> {
     /// Constructor, take provided name and value.
     #[inline]
@@ -73,9 +83,9 @@ impl Default for HttpHeader<'static> {
     fn default() -> Self { EMPTY_HEADER }
 }
 
-impl<'h> std::fmt::Display for HttpHeader<'h> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        use std::str::from_utf8_unchecked;
+impl<'h> core::fmt::Display for HttpHeader<'h> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        use core::str::from_utf8_unchecked;
         write!(
             f,
             "{}: {}",
@@ -137,17 +147,36 @@ macro_rules! handshake_check {
             return Err($e);
         }
     };
+    ($hdr: expr, token $value: expr, $e: expr) => {
+        // header value here is a comma separated token list
+        // ref: https://datatracker.ietf.org/doc/html/rfc7230#section-6.1
+        if !contains_token($hdr.value, $value) {
+            return Err($e);
+        }
+    };
 }
 
 use write_header;
 use handshake_check;
 
+/// Check if a comma separated list contains the token, case insensitive.
+#[inline]
+fn contains_token(list: &[u8], token: &[u8]) -> bool {
+    list.split(|b| *b == b',')
+        .any(|x| x.trim_ascii().eq_ignore_ascii_case(token))
+}
+
+/// Save required headers, and store the others in order.
+/// Header names are kept as they are on the wire, including required ones.
+///
+/// Return [`HandshakeError::NotEnoughCapacity`] if there is no space left
+/// to store other headers.
 #[inline]
 fn filter_header<'h>(
     all: &[httparse::Header<'h>],
     required: &mut [HttpHeader<'h>],
     other: &mut [HttpHeader<'h>],
-) {
+) -> Result<(), HandshakeError> {
     let mut other_iter = other.iter_mut();
     for hdr in all.iter() {
         let name = hdr.name.as_bytes();
@@ -157,13 +186,15 @@ fn filter_header<'h>(
             .filter(|h| h.value.is_empty())
             .find(|h| h.name.eq_ignore_ascii_case(name))
         {
+            h.name = name;
             h.value = hdr.value;
         } else {
-            let other_hdr = other_iter.next().unwrap();
+            let other_hdr = other_iter.next().ok_or(HandshakeError::NotEnoughCapacity)?;
             other_hdr.name = name;
             other_hdr.value = hdr.value;
         }
     }
+    Ok(())
 }
 
 /// Static http headers
@@ -204,6 +235,14 @@ pub mod static_headers {
         (HEADER_SEC_WEBSOCKET_ACCEPT_NAME => b"sec-websocket-accept");
 
         (HEADER_SEC_WEBSOCKET_VERSION_NAME => b"sec-websocket-version");
+
+        (HEADER_SEC_WEBSOCKET_PROTOCOL_NAME => b"sec-websocket-protocol");
+
+        (HEADER_SEC_WEBSOCKET_EXTENSIONS_NAME => b"sec-websocket-extensions");
+
+        (HEADER_ORIGIN_NAME => b"origin");
+
+        (HEADER_CONTENT_LENGTH_NAME => b"content-length");
     }
 
     // header value
@@ -213,6 +252,8 @@ pub mod static_headers {
         (HEADER_CONNECTION_VALUE => b"upgrade");
 
         (HEADER_SEC_WEBSOCKET_VERSION_VALUE => b"13");
+
+        (HEADER_PERMESSAGE_DEFLATE_VALUE => b"permessage-deflate");
     }
 }
 
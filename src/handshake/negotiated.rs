@@ -0,0 +1,253 @@
+//! Negotiated results.
+//!
+//! A client may offer subprotocols (`sec-websocket-protocol`) and extensions
+//! (`sec-websocket-extensions`) in its request, and the server selects some of them
+//! in its response. [`Negotiated`] records what has been selected.
+//!
+//! Example:
+//!
+//! ```text
+//! sec-websocket-protocol: chat
+//! sec-websocket-extensions: permessage-deflate; client_max_window_bits=10
+//! ```
+//!
+
+use alloc::boxed::Box;
+
+use super::HttpHeader;
+use super::static_headers::*;
+
+/// Default max window bits of `permessage-deflate`.
+pub const DEFLATE_MAX_WINDOW_BITS: u8 = 15;
+
+/// Min window bits of `permessage-deflate`.
+pub const DEFLATE_MIN_WINDOW_BITS: u8 = 8;
+
+/// Parameters of `permessage-deflate`.
+///
+/// [RFC-7692 Section 7](https://datatracker.ietf.org/doc/html/rfc7692#section-7)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Deflate {
+    pub server_no_context_takeover: bool,
+    pub client_no_context_takeover: bool,
+    pub server_max_window_bits: u8,
+    pub client_max_window_bits: u8,
+}
+
+impl Deflate {
+    /// Constructor, with default parameters.
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            server_no_context_takeover: false,
+            client_no_context_takeover: false,
+            server_max_window_bits: DEFLATE_MAX_WINDOW_BITS,
+            client_max_window_bits: DEFLATE_MAX_WINDOW_BITS,
+        }
+    }
+
+    /// Parse from an extension offer, e.g.
+    /// `permessage-deflate; client_max_window_bits=10`.
+    ///
+    /// Return `None` if this is not `permessage-deflate`, or if a window bits
+    /// value is not a number in `8..=15`. A window bits parameter
+    /// without a value keeps the default.
+    pub fn parse(ext: &[u8]) -> Option<Self> {
+        let mut params = ext.split(|b| *b == b';').map(<[u8]>::trim_ascii);
+
+        if !params
+            .next()?
+            .eq_ignore_ascii_case(HEADER_PERMESSAGE_DEFLATE_VALUE)
+        {
+            return None;
+        }
+
+        let mut deflate = Self::new();
+
+        for param in params {
+            let (name, value) = match param.iter().position(|b| *b == b'=') {
+                Some(i) => (param[..i].trim_ascii(), Some(param[i + 1..].trim_ascii())),
+                None => (param, None),
+            };
+
+            match name {
+                b"server_no_context_takeover" => deflate.server_no_context_takeover = true,
+                b"client_no_context_takeover" => deflate.client_no_context_takeover = true,
+                b"server_max_window_bits" => deflate.server_max_window_bits = parse_bits(value)?,
+                b"client_max_window_bits" => deflate.client_max_window_bits = parse_bits(value)?,
+                _ => {}
+            }
+        }
+
+        Some(deflate)
+    }
+}
+
+/// Parse window bits, which could be a quoted string.
+fn parse_bits(value: Option<&[u8]>) -> Option<u8> {
+    let Some(v) = value else {
+        return Some(DEFLATE_MAX_WINDOW_BITS);
+    };
+    let v = v.strip_prefix(b"\"").unwrap_or(v);
+    let v = v.strip_suffix(b"\"").unwrap_or(v);
+    let bits = core::str::from_utf8(v).ok()?.parse::<u8>().ok()?;
+    (DEFLATE_MIN_WINDOW_BITS..=DEFLATE_MAX_WINDOW_BITS)
+        .contains(&bits)
+        .then_some(bits)
+}
+
+impl Default for Deflate {
+    fn default() -> Self { Self::new() }
+}
+
+/// Negotiated subprotocol, extensions, and compression parameters.
+///
+/// Values are copied out of the handshake headers,
+/// so that they outlive the handshake buffer.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Negotiated {
+    protocol: Option<Box<[u8]>>,
+    extensions: Option<Box<[u8]>>,
+    deflate: Option<Deflate>,
+}
+
+impl Negotiated {
+    /// Constructor, nothing is negotiated.
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            protocol: None,
+            extensions: None,
+            deflate: None,
+        }
+    }
+
+    /// Collect negotiated results from headers.
+    pub fn from_headers(headers: &[HttpHeader]) -> Self {
+        let find = |name: &[u8]| {
+            headers
+                .iter()
+                .find(|h| h.name.eq_ignore_ascii_case(name))
+                .map(|h| h.value)
+        };
+
+        let protocol = find(HEADER_SEC_WEBSOCKET_PROTOCOL_NAME);
+        let extensions = find(HEADER_SEC_WEBSOCKET_EXTENSIONS_NAME);
+        let deflate =
+            extensions.and_then(|exts| exts.split(|b| *b == b',').find_map(Deflate::parse));
+
+        Self {
+            protocol: protocol.map(Into::into),
+            extensions: extensions.map(Into::into),
+            deflate,
+        }
+    }
+
+    /// Selected subprotocol.
+    #[inline]
+    pub fn protocol(&self) -> Option<&[u8]> { self.protocol.as_deref() }
+
+    /// Selected extensions, the raw header value.
+    #[inline]
+    pub fn extensions(&self) -> Option<&[u8]> { self.extensions.as_deref() }
+
+    /// Iterate over each selected extension.
+    #[inline]
+    pub fn extensions_iter(&self) -> impl Iterator<Item = &[u8]> {
+        self.extensions()
+            .into_iter()
+            .flat_map(|exts| exts.split(|b| *b == b','))
+            .map(<[u8]>::trim_ascii)
+            .filter(|ext| !ext.is_empty())
+    }
+
+    /// Selected `permessage-deflate` parameters.
+    #[inline]
+    pub const fn deflate(&self) -> Option<&Deflate> { self.deflate.as_ref() }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn negotiated_headers() {
+        let headers = [
+            HttpHeader::new(b"Sec-WebSocket-Protocol", b"chat"),
+            HttpHeader::new(
+                b"sec-websocket-extensions",
+                b"foo, permessage-deflate; client_max_window_bits=10; server_no_context_takeover",
+            ),
+        ];
+        let negotiated = Negotiated::from_headers(&headers);
+
+        assert_eq!(negotiated.protocol(), Some(b"chat".as_slice()));
+        assert_eq!(
+            negotiated.extensions_iter().collect::<Vec<_>>(),
+            [
+                b"foo".as_slice(),
+                b"permessage-deflate; client_max_window_bits=10; server_no_context_takeover"
+            ]
+        );
+        assert_eq!(
+            negotiated.deflate(),
+            Some(&Deflate {
+                server_no_context_takeover: true,
+                client_no_context_takeover: false,
+                server_max_window_bits: 15,
+                client_max_window_bits: 10,
+            })
+        );
+
+        let negotiated = Negotiated::from_headers(&[]);
+        assert_eq!(negotiated, Negotiated::new());
+        assert_eq!(negotiated.extensions_iter().count(), 0);
+    }
+
+    #[test]
+    fn deflate_params() {
+        assert_eq!(Deflate::parse(b"x-webkit-deflate-frame"), None);
+        assert_eq!(
+            Deflate::parse(b" permessage-deflate "),
+            Some(Deflate::new())
+        );
+        assert_eq!(
+            Deflate::parse(
+                b"permessage-deflate;client_no_context_takeover;server_max_window_bits=\"9\""
+            ),
+            Some(Deflate {
+                server_no_context_takeover: false,
+                client_no_context_takeover: true,
+                server_max_window_bits: 9,
+                client_max_window_bits: 15,
+            })
+        );
+        assert_eq!(
+            Deflate::parse(b"permessage-deflate; client_max_window_bits; server_max_window_bits=8"),
+            Some(Deflate {
+                server_max_window_bits: 8,
+                ..Deflate::new()
+            })
+        );
+    }
+
+    #[test]
+    fn deflate_invalid_window_bits() {
+        for bits in ["7", "16", "0", "255", "256", "-1", "", "\"\"", "ten", "9.5"] {
+            let server = format!("permessage-deflate; server_max_window_bits={}", bits);
+            let client = format!("permessage-deflate; client_max_window_bits={}", bits);
+            assert_eq!(Deflate::parse(server.as_bytes()), None);
+            assert_eq!(Deflate::parse(client.as_bytes()), None);
+        }
+
+        // the next offer is selected
+        let headers = [HttpHeader::new(
+            b"sec-websocket-extensions",
+            b"permessage-deflate; client_max_window_bits=16, permessage-deflate",
+        )];
+        assert_eq!(
+            Negotiated::from_headers(&headers).deflate(),
+            Some(&Deflate::new())
+        );
+    }
+}
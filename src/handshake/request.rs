@@ -23,9 +23,11 @@
 //! ```
 //!
 
-use super::{HttpHeader, HeaderHelper};
+use alloc::boxed::Box;
+
+use super::HttpHeader;
 use super::{write_header, filter_header};
-use super::handshake_check;
+use super::{handshake_check, contains_token};
 use super::MAX_ALLOW_HEADERS;
 use super::{HTTP_METHOD, HTTP_VERSION, HTTP_LINE_BREAK, HTTP_HEADER_SP};
 use super::static_headers::*;
@@ -41,8 +43,33 @@ pub struct Request<'h, 'b: 'h, const N: usize = MAX_ALLOW_HEADERS> {
     pub other_headers: &'h mut [HttpHeader<'b>],
 }
 
-impl<'h, 'b: 'h, const N: usize> HeaderHelper for Request<'h, 'b, N> {
-    const SIZE: usize = N;
+/// Required fields of a [`Request`], copied out of the buffer,
+/// so that the buffer could be reused once the request is received.
+///
+/// Optional headers are not kept.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OwnedRequest {
+    pub path: Box<[u8]>,
+    pub host: Box<[u8]>,
+    pub sec_key: Box<[u8]>,
+}
+
+impl OwnedRequest {
+    /// Borrow as a [`Request`] without extra headers.
+    #[inline]
+    pub fn as_request(&self) -> Request<'_, '_> {
+        Request::new(&self.path, &self.host, &self.sec_key)
+    }
+}
+
+impl<const N: usize> From<&Request<'_, '_, N>> for OwnedRequest {
+    fn from(request: &Request<'_, '_, N>) -> Self {
+        Self {
+            path: request.path.into(),
+            host: request.host.into(),
+            sec_key: request.sec_key.into(),
+        }
+    }
 }
 
 impl<'h, 'b: 'h> Request<'h, 'b> {
@@ -105,6 +132,37 @@ impl<'h, 'b: 'h, const N: usize> Request<'h, 'b, N> {
         }
     }
 
+    /// Get the value of an optional header, the name is case insensitive.
+    ///
+    /// Only the first one is returned if there are multiple headers with the same name.
+    #[inline]
+    pub fn header(&self, name: &[u8]) -> Option<&'b [u8]> {
+        self.other_headers
+            .iter()
+            .find(|hdr| hdr.name.eq_ignore_ascii_case(name))
+            .map(|hdr| hdr.value)
+    }
+
+    /// Get the path without the query string, split on the first `?`.
+    #[inline]
+    pub fn path_only(&self) -> &'b [u8] {
+        match self.path.iter().position(|&b| b == b'?') {
+            Some(i) => &self.path[..i],
+            None => self.path,
+        }
+    }
+
+    /// Get the query string after the first `?`, which could be empty.
+    ///
+    /// Return `None` if there is no `?` in the path.
+    #[inline]
+    pub fn query(&self) -> Option<&'b [u8]> {
+        self.path
+            .iter()
+            .position(|&b| b == b'?')
+            .map(|i| &self.path[i + 1..])
+    }
+
     /// Encode to a provided buffer, return the number of written bytes.
     ///
     /// Necessary headers, including `host`, `upgrade`, `connection`,
@@ -171,13 +229,16 @@ impl<'h, 'b: 'h, const N: usize> Request<'h, 'b, N> {
     /// Caller should make sure there is enough space
     /// (default is [`MAX_ALLOW_HEADERS`]) to store headers,
     /// which could be specified by the const generic paramater.
+    /// If there are more headers than that, a [`HandshakeError::TooManyHeaders`]
+    /// error will be returned, and the caller could retry with a larger size.
+    /// If `other_headers` is too short to store the optional headers,
+    /// a [`HandshakeError::NotEnoughCapacity`] error will be returned.
     /// If the buffer does not contain a complete http request,
     /// a [`HandshakeError::NotEnoughData`] error will be returned.
     /// If the required headers(mentioned above) do not pass the check
-    /// (case insensitive), other corresponding errors will be returned.
+    /// (case insensitive), other corresponding errors will be returned,
+    /// where a version other than `13` leads to [`HandshakeError::UnsupportedVersion`].
     pub fn decode(&mut self, buf: &'b [u8]) -> Result<usize, HandshakeError> {
-        debug_assert!(self.other_headers.len() >= <Self as HeaderHelper>::SIZE);
-
         let mut headers = [httparse::EMPTY_HEADER; N];
         let mut request = httparse::Request::new(&mut headers);
 
@@ -212,7 +273,7 @@ impl<'h, 'b: 'h, const N: usize> Request<'h, 'b, N> {
         ];
 
         // filter required headers, save other headers
-        filter_header(headers, &mut required_headers, self.other_headers);
+        filter_header(headers, &mut required_headers, self.other_headers)?;
 
         let [host_hdr, upgrade_hdr, connection_hdr, sec_key_hdr, sec_version_hdr] =
             required_headers;
@@ -232,15 +293,24 @@ impl<'h, 'b: 'h, const N: usize> Request<'h, 'b, N> {
 
         handshake_check!(
             connection_hdr,
-            HEADER_CONNECTION_VALUE,
+            token HEADER_CONNECTION_VALUE,
             HandshakeError::Connection
         );
 
-        handshake_check!(
-            sec_version_hdr,
-            HEADER_SEC_WEBSOCKET_VERSION_VALUE,
-            HandshakeError::SecWebSocketVersion
-        );
+        // a valid but unsupported version should be replied with 426
+        // ref: https://datatracker.ietf.org/doc/html/rfc6455#section-4.4
+        if !sec_version_hdr
+            .value
+            .eq_ignore_ascii_case(HEADER_SEC_WEBSOCKET_VERSION_VALUE)
+        {
+            let version = core::str::from_utf8(sec_version_hdr.value)
+                .ok()
+                .and_then(|v| v.parse::<u8>().ok());
+            return Err(match version {
+                Some(v) => HandshakeError::UnsupportedVersion(v),
+                None => HandshakeError::SecWebSocketVersion,
+            });
+        }
 
         // save ref
         self.path = request.path.unwrap().as_bytes();
@@ -296,6 +366,11 @@ mod test {
                 }};
             }
             match_other!(b"sec-websocket-accept", b"s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+            assert_eq!(
+                request.header(b"Sec-WebSocket-Accept"),
+                Some(&b"s3pPLMBiTxaQ9kYGzzhZRbK+xOo="[..])
+            );
+            assert_eq!(request.header(b"sec-websocket-key"), None);
 
             let mut buf: Vec<u8> = vec![0; 0x4000];
             let encode_n = request.encode(&mut buf).unwrap();
@@ -344,4 +419,165 @@ mod test {
     }
 
     // catch errors ...
+
+    #[test]
+    fn client_handshake_too_many_headers() {
+        let headers = format!(
+            "GET / HTTP/1.1\r\n{}\r\n",
+            make_headers(8, 32, TEMPLATE_HEADERS)
+        );
+
+        let mut other_headers = HttpHeader::new_custom_storage::<8>();
+        let mut request = Request::<8>::new_custom_storage(&mut other_headers);
+        assert_eq!(
+            request.decode(headers.as_bytes()),
+            Err(HandshakeError::TooManyHeaders)
+        );
+
+        let mut other_headers = HttpHeader::new_custom_storage::<16>();
+        let mut request = Request::<16>::new_custom_storage(&mut other_headers);
+        assert_eq!(request.decode(headers.as_bytes()), Ok(headers.len()));
+    }
+
+    #[test]
+    fn client_handshake_small_storage() {
+        let headers = format!(
+            "GET / HTTP/1.1\r\n{}\r\n",
+            make_headers(8, 32, TEMPLATE_HEADERS)
+        );
+
+        // 9 optional headers, including sec-websocket-accept
+        let mut other_headers = HttpHeader::new_custom_storage::<8>();
+        let mut request = Request::<16>::new_custom_storage(&mut other_headers);
+        assert_eq!(
+            request.decode(headers.as_bytes()),
+            Err(HandshakeError::NotEnoughCapacity)
+        );
+
+        // decode again after shrinking
+        let mut other_headers = HttpHeader::new_custom_storage::<16>();
+        let mut request = Request::<16>::new_custom_storage(&mut other_headers);
+        let headers2 = format!(
+            "GET / HTTP/1.1\r\n{}\r\n",
+            make_headers(0, 32, TEMPLATE_HEADERS)
+        );
+        assert_eq!(request.decode(headers2.as_bytes()), Ok(headers2.len()));
+        assert_eq!(request.other_headers.len(), 1);
+        assert_eq!(
+            request.decode(headers.as_bytes()),
+            Err(HandshakeError::NotEnoughCapacity)
+        );
+    }
+
+    #[test]
+    fn client_handshake_connection() {
+        fn decode(connection: &str) -> Result<usize, HandshakeError> {
+            let headers = format!(
+                "GET / HTTP/1.1\r\n\
+                host: www.example.com\r\n\
+                upgrade: websocket\r\n\
+                connection: {}\r\n\
+                sec-websocket-key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+                sec-websocket-version: 13\r\n\r\n",
+                connection
+            );
+            let mut other_headers = HttpHeader::new_storage();
+            let mut request = Request::new_storage(&mut other_headers);
+            request.decode(headers.as_bytes())
+        }
+
+        for connection in [
+            "Upgrade",
+            "keep-alive, Upgrade",
+            "upgrade,keep-alive",
+            "a , UPGRADE ",
+        ] {
+            assert!(decode(connection).is_ok());
+        }
+        for connection in ["keep-alive", "upgrades", "keep-alive upgrade", ","] {
+            assert_eq!(decode(connection), Err(HandshakeError::Connection));
+        }
+    }
+
+    #[test]
+    fn client_handshake_version() {
+        fn decode(version: &str) -> Result<usize, HandshakeError> {
+            let headers = format!(
+                "GET / HTTP/1.1\r\n\
+                host: www.example.com\r\n\
+                upgrade: websocket\r\n\
+                connection: upgrade\r\n\
+                sec-websocket-key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+                sec-websocket-version: {}\r\n\r\n",
+                version
+            );
+            let mut other_headers = HttpHeader::new_storage();
+            let mut request = Request::new_storage(&mut other_headers);
+            request.decode(headers.as_bytes())
+        }
+
+        assert!(decode("13").is_ok());
+        assert_eq!(decode("8"), Err(HandshakeError::UnsupportedVersion(8)));
+        assert_eq!(decode("255"), Err(HandshakeError::UnsupportedVersion(255)));
+        for version in ["256", "-1", "v13"] {
+            assert_eq!(decode(version), Err(HandshakeError::SecWebSocketVersion));
+        }
+    }
+
+    #[test]
+    fn client_handshake_query() {
+        for (target, path, query) in [
+            ("/ws", "/ws", None),
+            ("/ws?token=abc", "/ws", Some("token=abc")),
+            ("/ws?", "/ws", Some("")),
+            ("/ws?a=1?b=2", "/ws", Some("a=1?b=2")),
+        ] {
+            let headers = format!(
+                "GET {} HTTP/1.1\r\n\
+                host: www.example.com\r\n\
+                upgrade: websocket\r\n\
+                connection: upgrade\r\n\
+                sec-websocket-key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+                sec-websocket-version: 13\r\n\r\n",
+                target
+            );
+            let mut other_headers = HttpHeader::new_storage();
+            let mut request = Request::new_storage(&mut other_headers);
+            assert_eq!(request.decode(headers.as_bytes()), Ok(headers.len()));
+            assert_eq!(request.path, target.as_bytes());
+            assert_eq!(request.path_only(), path.as_bytes());
+            assert_eq!(request.query(), query.map(str::as_bytes));
+        }
+    }
+
+    #[test]
+    fn client_handshake_header_case() {
+        use super::super::filter_header;
+
+        let headers = b"GET /ws HTTP/1.1\r\n\
+            Host: www.example.com\r\n\
+            Upgrade: websocket\r\n\
+            Connection: Upgrade\r\n\
+            Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+            Sec-WebSocket-Version: 13\r\n\
+            X-Forwarded-For: 127.0.0.1\r\n\r\n";
+
+        let mut other_headers = HttpHeader::new_storage();
+        let mut request = Request::new_storage(&mut other_headers);
+        assert_eq!(request.decode(headers), Ok(headers.len()));
+        assert_eq!(request.sec_key, b"dGhlIHNhbXBsZSBub25jZQ==");
+        assert_eq!(request.other_headers[0].name, b"X-Forwarded-For");
+
+        // required headers keep the on-wire name
+        let mut raw = [httparse::EMPTY_HEADER; 8];
+        let mut parsed = httparse::Request::new(&mut raw);
+        parsed.parse(headers).unwrap();
+        let mut required = [HEADER_SEC_WEBSOCKET_KEY, HEADER_HOST];
+        let mut other = HttpHeader::new_storage();
+        filter_header(parsed.headers, &mut required, &mut other).unwrap();
+        assert_eq!(required[0].name, b"Sec-WebSocket-Key");
+        assert_eq!(required[0].value, b"dGhlIHNhbXBsZSBub25jZQ==");
+        assert_eq!(required[1].name, b"Host");
+        assert_eq!(other[0].name, b"Upgrade");
+    }
 }
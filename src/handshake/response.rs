@@ -20,11 +20,14 @@
 //! ```
 //!
 
-use super::{HttpHeader, HeaderHelper};
+use alloc::boxed::Box;
+
+use super::HttpHeader;
 use super::{write_header, filter_header};
-use super::handshake_check;
+use super::{handshake_check, contains_token};
 use super::MAX_ALLOW_HEADERS;
-use super::{HTTP_STATUS_LINE, HTTP_LINE_BREAK, HTTP_HEADER_SP};
+use super::{HTTP_STATUS_LINE, HTTP_LINE_BREAK, HTTP_HEADER_SP, HTTP_UPGRADE_REQUIRED};
+use super::HTTP_VERSION;
 use super::static_headers::*;
 
 use crate::bleed::Writer;
@@ -36,8 +39,27 @@ pub struct Response<'h, 'b: 'h, const N: usize = MAX_ALLOW_HEADERS> {
     pub other_headers: &'h mut [HttpHeader<'b>],
 }
 
-impl<'h, 'b: 'h, const N: usize> HeaderHelper for Response<'h, 'b, N> {
-    const SIZE: usize = N;
+/// Required fields of a [`Response`], copied out of the buffer,
+/// so that the buffer could be reused once the response is received.
+///
+/// Optional headers are not kept.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OwnedResponse {
+    pub sec_accept: Box<[u8]>,
+}
+
+impl OwnedResponse {
+    /// Borrow as a [`Response`] without extra headers.
+    #[inline]
+    pub fn as_response(&self) -> Response<'_, '_> { Response::new(&self.sec_accept) }
+}
+
+impl<const N: usize> From<&Response<'_, '_, N>> for OwnedResponse {
+    fn from(response: &Response<'_, '_, N>) -> Self {
+        Self {
+            sec_accept: response.sec_accept.into(),
+        }
+    }
 }
 
 impl<'h, 'b: 'h> Response<'h, 'b> {
@@ -75,6 +97,62 @@ impl<'h, 'b: 'h> Response<'h, 'b> {
             other_headers,
         }
     }
+
+    /// Get the encoded `426 Upgrade Required` response,
+    /// which replies a request rejected with [`HandshakeError::UnsupportedVersion`],
+    /// see [`HTTP_UPGRADE_REQUIRED`].
+    ///
+    /// [RFC-6455 Section 4.4](https://datatracker.ietf.org/doc/html/rfc6455#section-4.4)
+    #[inline]
+    pub const fn upgrade_required() -> &'static [u8] { HTTP_UPGRADE_REQUIRED }
+
+    /// Encode a non-101 response which rejects the upgrade to a provided buffer,
+    /// return the number of written bytes.
+    ///
+    /// The status is written after the http version, e.g. `400 Bad Request`,
+    /// followed by a `content-length` header and the body.
+    ///
+    /// Caller should make sure there is enough space to write,
+    /// otherwise a [`HandshakeError::NotEnoughCapacity`] error will be returned.
+    pub fn encode_reject(
+        buf: &mut [u8],
+        status: &[u8],
+        body: &[u8],
+    ) -> Result<usize, HandshakeError> {
+        let mut w = Writer::new(buf);
+
+        // HTTP/1.1 {status}
+        let line_len = HTTP_VERSION.len() + 1 + status.len() + HTTP_LINE_BREAK.len();
+        if w.remaining() < line_len {
+            return Err(HandshakeError::NotEnoughCapacity);
+        }
+        unsafe {
+            w.write_unchecked(HTTP_VERSION);
+            w.write_byte_unchecked(b' ');
+            w.write_unchecked(status);
+            w.write_unchecked(HTTP_LINE_BREAK);
+        }
+
+        // content-length: {len}
+        let mut digits = [0_u8; 20];
+        let mut pos = digits.len();
+        let mut len = body.len();
+        loop {
+            pos -= 1;
+            digits[pos] = b'0' + (len % 10) as u8;
+            len /= 10;
+            if len == 0 {
+                break;
+            }
+        }
+        write_header!(w, HEADER_CONTENT_LENGTH_NAME, &digits[pos..]);
+
+        // finish with CRLF, then the body
+        w.write_or_err(HTTP_LINE_BREAK, || HandshakeError::NotEnoughCapacity)?;
+        w.write_or_err(body, || HandshakeError::NotEnoughCapacity)?;
+
+        Ok(w.pos())
+    }
 }
 
 impl<'h, 'b: 'h, const N: usize> Response<'h, 'b, N> {
@@ -90,6 +168,17 @@ impl<'h, 'b: 'h, const N: usize> Response<'h, 'b, N> {
         }
     }
 
+    /// Get the value of an optional header, the name is case insensitive.
+    ///
+    /// Only the first one is returned if there are multiple headers with the same name.
+    #[inline]
+    pub fn header(&self, name: &[u8]) -> Option<&'b [u8]> {
+        self.other_headers
+            .iter()
+            .find(|hdr| hdr.name.eq_ignore_ascii_case(name))
+            .map(|hdr| hdr.value)
+    }
+
     /// Encode to a provided buffer, return the number of written bytes.
     ///
     /// Necessary headers, including `upgrade`, `connection`, and
@@ -99,34 +188,7 @@ impl<'h, 'b: 'h, const N: usize> Response<'h, 'b, N> {
     /// Caller should make sure there is enough space to write,
     /// otherwise a [`HandshakeError::NotEnoughCapacity`] error will be returned.
     pub fn encode(&self, buf: &mut [u8]) -> Result<usize, HandshakeError> {
-        debug_assert!(buf.len() > 80);
-
-        let mut w = Writer::new(buf);
-
-        // HTTP/1.1 101 Switching Protocols
-        unsafe {
-            w.write_unchecked(HTTP_STATUS_LINE);
-            w.write_unchecked(HTTP_LINE_BREAK);
-        }
-
-        // upgrade: websocket
-        write_header!(w, HEADER_UPGRADE_NAME, HEADER_UPGRADE_VALUE);
-
-        // connection: upgrade
-        write_header!(w, HEADER_CONNECTION_NAME, HEADER_CONNECTION_VALUE);
-
-        // sec-websocket-accept: {sec_accept}
-        write_header!(w, HEADER_SEC_WEBSOCKET_ACCEPT_NAME, self.sec_accept);
-
-        // other headers
-        for hdr in self.other_headers.iter() {
-            write_header!(w, hdr)
-        }
-
-        // finish with CRLF
-        w.write_or_err(HTTP_LINE_BREAK, || HandshakeError::NotEnoughCapacity)?;
-
-        Ok(w.pos())
+        encode_response(buf, self.sec_accept, self.other_headers)
     }
 
     /// Parse from a provided buffer, save the results, and
@@ -142,13 +204,15 @@ impl<'h, 'b: 'h, const N: usize> Response<'h, 'b, N> {
     /// Caller should make sure there is enough space
     /// (default is [`MAX_ALLOW_HEADERS`]) to store headers,
     /// which could be specified by the const generic paramater.
+    /// If there are more headers than that, a [`HandshakeError::TooManyHeaders`]
+    /// error will be returned, and the caller could retry with a larger size.
+    /// If `other_headers` is too short to store the optional headers,
+    /// a [`HandshakeError::NotEnoughCapacity`] error will be returned.
     /// If the buffer does not contain a complete http request,
     /// a [`HandshakeError::NotEnoughData`] error will be returned.
     /// If the required headers(mentioned above) do not pass the check
     /// (case insensitive), other corresponding errors will be returned.
     pub fn decode(&mut self, buf: &'b [u8]) -> Result<usize, HandshakeError> {
-        debug_assert!(self.other_headers.len() >= <Self as HeaderHelper>::SIZE);
-
         let mut headers = [httparse::EMPTY_HEADER; N];
         let mut response = httparse::Response::new(&mut headers);
 
@@ -182,7 +246,7 @@ impl<'h, 'b: 'h, const N: usize> Response<'h, 'b, N> {
         ];
 
         // filter required headers, save other headers
-        filter_header(headers, &mut required_headers, self.other_headers);
+        filter_header(headers, &mut required_headers, self.other_headers)?;
 
         let [upgrade_hdr, connection_hdr, sec_accept_hdr] = required_headers;
 
@@ -199,7 +263,7 @@ impl<'h, 'b: 'h, const N: usize> Response<'h, 'b, N> {
 
         handshake_check!(
             connection_hdr,
-            HEADER_CONNECTION_VALUE,
+            token HEADER_CONNECTION_VALUE,
             HandshakeError::Connection
         );
 
@@ -219,6 +283,43 @@ impl<'h, 'b: 'h, const N: usize> Response<'h, 'b, N> {
     }
 }
 
+/// Same as [`Response::encode`], but take the fields as shared references,
+/// so that headers of any lifetime could be sent along with a derived key.
+pub(crate) fn encode_response(
+    buf: &mut [u8],
+    sec_accept: &[u8],
+    other_headers: &[HttpHeader],
+) -> Result<usize, HandshakeError> {
+    debug_assert!(buf.len() > 80);
+
+    let mut w = Writer::new(buf);
+
+    // HTTP/1.1 101 Switching Protocols
+    unsafe {
+        w.write_unchecked(HTTP_STATUS_LINE);
+        w.write_unchecked(HTTP_LINE_BREAK);
+    }
+
+    // upgrade: websocket
+    write_header!(w, HEADER_UPGRADE_NAME, HEADER_UPGRADE_VALUE);
+
+    // connection: upgrade
+    write_header!(w, HEADER_CONNECTION_NAME, HEADER_CONNECTION_VALUE);
+
+    // sec-websocket-accept: {sec_accept}
+    write_header!(w, HEADER_SEC_WEBSOCKET_ACCEPT_NAME, sec_accept);
+
+    // other headers
+    for hdr in other_headers.iter() {
+        write_header!(w, hdr)
+    }
+
+    // finish with CRLF
+    w.write_or_err(HTTP_LINE_BREAK, || HandshakeError::NotEnoughCapacity)?;
+
+    Ok(w.pos())
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -256,6 +357,8 @@ mod test {
             match_other!(b"host", b"www.example.com");
             match_other!(b"sec-websocket-version", b"13");
             match_other!(b"sec-websocket-key", b"dGhlIHNhbXBsZSBub25jZQ==");
+            assert_eq!(response.header(b"HOST"), Some(&b"www.example.com"[..]));
+            assert_eq!(response.header(b"sec-websocket-accept"), None);
 
             let mut buf: Vec<u8> = vec![0; 0x4000];
             let encode_n = response.encode(&mut buf).unwrap();
@@ -298,5 +401,62 @@ mod test {
         run!("xxxxxxxxx==");
     }
 
+    #[test]
+    fn server_handshake_connection() {
+        for connection in ["Upgrade", "keep-alive, Upgrade"] {
+            let headers = format!(
+                "HTTP/1.1 101 Switching Protocols\r\n\
+                upgrade: websocket\r\n\
+                connection: {}\r\n\
+                sec-websocket-accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n\r\n",
+                connection
+            );
+            let mut other_headers = HttpHeader::new_storage();
+            let mut response = Response::new_storage(&mut other_headers);
+            assert_eq!(response.decode(headers.as_bytes()), Ok(headers.len()));
+        }
+    }
+
+    #[test]
+    fn server_handshake_reject() {
+        let mut buf = vec![0; 1024];
+        for body in [
+            &b""[..],
+            b"bad",
+            b"{\"error\":\"path mismatch\"}",
+            &[b'x'; 500],
+        ] {
+            let n = Response::encode_reject(&mut buf, b"400 Bad Request", body).unwrap();
+            let expect = format!(
+                "HTTP/1.1 400 Bad Request\r\ncontent-length: {}\r\n\r\n",
+                body.len()
+            );
+            assert_eq!(&buf[..n - body.len()], expect.as_bytes());
+            assert_eq!(&buf[n - body.len()..n], body);
+
+            // not enough capacity
+            assert_eq!(
+                Response::encode_reject(&mut buf[..n - 1], b"400 Bad Request", body),
+                Err(HandshakeError::NotEnoughCapacity)
+            );
+        }
+    }
+
     // catch errors ...
+
+    #[test]
+    fn server_handshake_small_storage() {
+        let headers = format!(
+            "HTTP/1.1 101 Switching Protocols\r\n{}\r\n",
+            make_headers(8, 32, TEMPLATE_HEADERS)
+        );
+
+        // 11 optional headers, including host, sec-websocket-key and sec-websocket-version
+        let mut other_headers = HttpHeader::new_custom_storage::<8>();
+        let mut response = Response::<16>::new_custom_storage(&mut other_headers);
+        assert_eq!(
+            response.decode(headers.as_bytes()),
+            Err(HandshakeError::NotEnoughCapacity)
+        );
+    }
 }
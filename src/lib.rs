@@ -1,8 +1,6 @@
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 #![allow(incomplete_features)]
 #![allow(clippy::blocks_in_conditions)]
-#![feature(const_slice_from_raw_parts_mut)]
-#![feature(read_buf)]
-#![feature(core_io_borrowed_buf)]
 #![feature(specialization)]
 
 //! Lightweight websocket implement for stream transmission.
@@ -14,6 +12,12 @@
 //! - Use vectored-io if available.
 //! - Transparent Read/Write over the underlying IO source.
 //!
+//! ## No std
+//!
+//! Disable the default `std` feature to build with `no_std` + `alloc`,
+//! where only the low-level `frame` and `handshake` codecs are available,
+//! and random key generators are excluded.
+//!
 //! ## High-level API
 //!
 //! - [`role`]
@@ -104,11 +108,18 @@
 //! }
 //! ```
 
+extern crate alloc;
+
 mod bleed;
 
-pub mod role;
 pub mod error;
 pub mod frame;
-pub mod stream;
-pub mod endpoint;
 pub mod handshake;
+
+cfg_if::cfg_if! {
+    if #[cfg(feature = "std")] {
+        pub mod role;
+        pub mod stream;
+        pub mod endpoint;
+    }
+}
@@ -31,6 +31,8 @@ impl ClientRole for Client {}
 ///
 /// With `unsafe_auto_mask_write` feature enabled, it will automatically
 /// update its inner mask key and mask payload data before a write.
+///
+/// It is the same as [`RotateMaskClient<1>`].
 #[derive(Clone, Copy)]
 pub struct StandardClient([u8; 4]);
 
@@ -57,6 +59,8 @@ impl AutoMaskClientRole for StandardClient {
 ///
 /// With `unsafe_auto_mask_write` feature enabled, it will automatically
 /// mask payload data before a write, where its inner mask key is not updated.
+///
+/// It is the same as a [`RotateMaskClient`] which never rotates.
 #[derive(Clone, Copy)]
 pub struct FixedMaskClient([u8; 4]);
 
@@ -78,3 +82,52 @@ impl ClientRole for FixedMaskClient {}
 impl AutoMaskClientRole for FixedMaskClient {
     const UPDATE_MASK_KEY: bool = false;
 }
+
+/// Client using random mask key, which is updated every `N` writes.
+///
+/// With `unsafe_auto_mask_write` feature enabled, it will automatically
+/// mask payload data before a write, and update its inner mask key
+/// before the first write and every `N` writes after that,
+/// which saves random number generation when `N` is greater than `1`.
+///
+/// `N` should be greater than `0`, otherwise it is treated as `1`.
+#[derive(Clone, Copy)]
+pub struct RotateMaskClient<const N: u32> {
+    key: [u8; 4],
+    // writes since the last update
+    count: u32,
+}
+
+impl<const N: u32> RoleHelper for RotateMaskClient<N> {
+    client_consts!();
+
+    #[inline]
+    fn new() -> Self {
+        Self {
+            key: [0u8; 4],
+            count: 0,
+        }
+    }
+
+    #[inline]
+    fn mask_key(&self) -> Mask { Mask::Key(self.key) }
+
+    #[inline]
+    fn set_mask_key(&mut self, mask: [u8; 4]) { self.key = mask; }
+}
+
+impl<const N: u32> ClientRole for RotateMaskClient<N> {}
+
+impl<const N: u32> AutoMaskClientRole for RotateMaskClient<N> {
+    const UPDATE_MASK_KEY: bool = true;
+
+    #[inline]
+    fn update_mask_key(&mut self) -> bool {
+        let update = self.count == 0;
+        self.count += 1;
+        if self.count >= N {
+            self.count = 0;
+        }
+        update
+    }
+}
@@ -0,0 +1,154 @@
+use super::{RoleHelper, ClientRole, ServerRole, AutoMaskClientRole};
+use crate::frame::Mask;
+use crate::handshake::{Deflate, Negotiated};
+
+/// Role carrying negotiated `permessage-deflate` parameters.
+///
+/// The parameters are only stored for now, payload data is
+/// neither compressed nor decompressed.
+pub trait DeflateRole: RoleHelper {
+    /// Get the negotiated parameters.
+    fn deflate(&self) -> &Deflate;
+
+    /// Set the negotiated parameters, e.g. from [`Negotiated::deflate`](crate::handshake::Negotiated::deflate).
+    fn set_deflate(&mut self, deflate: Deflate);
+
+    /// Max window bits used to compress outgoing messages.
+    fn write_window_bits(&self) -> u8;
+
+    /// Max window bits used to decompress incoming messages.
+    fn read_window_bits(&self) -> u8;
+
+    /// Check if the compression context is reset after each outgoing message.
+    fn write_no_context_takeover(&self) -> bool;
+
+    /// Check if the decompression context is reset after each incoming message.
+    fn read_no_context_takeover(&self) -> bool;
+}
+
+/// Standard client with `permessage-deflate` parameters.
+///
+/// It masks payload data like [`StandardClient`](super::StandardClient),
+/// outgoing messages follow the `client_*` parameters,
+/// and incoming messages follow the `server_*` parameters.
+#[derive(Clone, Copy)]
+pub struct DeflateClient {
+    key: [u8; 4],
+    deflate: Deflate,
+}
+
+impl DeflateClient {
+    /// Constructor, take negotiated parameters.
+    #[inline]
+    pub const fn with_deflate(deflate: Deflate) -> Self {
+        Self {
+            key: [0u8; 4],
+            deflate,
+        }
+    }
+}
+
+impl RoleHelper for DeflateClient {
+    const SHORT_FRAME_HEAD_LEN: u8 = 2;
+    const COMMON_FRAME_HEAD_LEN: u8 = 2 + 2;
+    const LONG_FRAME_HEAD_LEN: u8 = 2 + 8;
+
+    #[inline]
+    fn new() -> Self { Self::with_deflate(Deflate::new()) }
+
+    #[inline]
+    fn mask_key(&self) -> Mask { Mask::Key(self.key) }
+
+    #[inline]
+    fn set_mask_key(&mut self, mask: [u8; 4]) { self.key = mask; }
+    /// Take negotiated `permessage-deflate` parameters, if any.
+    #[inline]
+    fn set_negotiated(&mut self, negotiated: &Negotiated) {
+        if let Some(deflate) = negotiated.deflate() {
+            self.deflate = *deflate;
+        }
+    }
+}
+
+impl ClientRole for DeflateClient {}
+
+impl AutoMaskClientRole for DeflateClient {
+    const UPDATE_MASK_KEY: bool = true;
+}
+
+impl DeflateRole for DeflateClient {
+    #[inline]
+    fn deflate(&self) -> &Deflate { &self.deflate }
+
+    #[inline]
+    fn set_deflate(&mut self, deflate: Deflate) { self.deflate = deflate; }
+
+    #[inline]
+    fn write_window_bits(&self) -> u8 { self.deflate.client_max_window_bits }
+
+    #[inline]
+    fn read_window_bits(&self) -> u8 { self.deflate.server_max_window_bits }
+
+    #[inline]
+    fn write_no_context_takeover(&self) -> bool { self.deflate.client_no_context_takeover }
+
+    #[inline]
+    fn read_no_context_takeover(&self) -> bool { self.deflate.server_no_context_takeover }
+}
+
+/// Standard server with `permessage-deflate` parameters.
+///
+/// Outgoing messages follow the `server_*` parameters,
+/// and incoming messages follow the `client_*` parameters.
+#[derive(Clone, Copy)]
+pub struct DeflateServer {
+    deflate: Deflate,
+}
+
+impl DeflateServer {
+    /// Constructor, take negotiated parameters.
+    #[inline]
+    pub const fn with_deflate(deflate: Deflate) -> Self { Self { deflate } }
+}
+
+impl RoleHelper for DeflateServer {
+    const SHORT_FRAME_HEAD_LEN: u8 = 2 + 4;
+    const COMMON_FRAME_HEAD_LEN: u8 = 2 + 2 + 4;
+    const LONG_FRAME_HEAD_LEN: u8 = 2 + 8 + 4;
+
+    #[inline]
+    fn new() -> Self { Self::with_deflate(Deflate::new()) }
+
+    /// Server should not mask the payload.
+    #[inline]
+    fn mask_key(&self) -> Mask { Mask::None }
+    /// Take negotiated `permessage-deflate` parameters, if any.
+    #[inline]
+    fn set_negotiated(&mut self, negotiated: &Negotiated) {
+        if let Some(deflate) = negotiated.deflate() {
+            self.deflate = *deflate;
+        }
+    }
+}
+
+impl ServerRole for DeflateServer {}
+
+impl DeflateRole for DeflateServer {
+    #[inline]
+    fn deflate(&self) -> &Deflate { &self.deflate }
+
+    #[inline]
+    fn set_deflate(&mut self, deflate: Deflate) { self.deflate = deflate; }
+
+    #[inline]
+    fn write_window_bits(&self) -> u8 { self.deflate.server_max_window_bits }
+
+    #[inline]
+    fn read_window_bits(&self) -> u8 { self.deflate.client_max_window_bits }
+
+    #[inline]
+    fn write_no_context_takeover(&self) -> bool { self.deflate.server_no_context_takeover }
+
+    #[inline]
+    fn read_no_context_takeover(&self) -> bool { self.deflate.client_no_context_takeover }
+}
@@ -6,24 +6,36 @@
 //! and returns `Stream<IO, Client>`; `Endpoint<IO, Server>` is used to accept
 //! a connection and returns `Stream<IO, Server>`.
 //!
-//! Both client and server meet [`RoleHelper`], which indicates frame head length
-//! (currently unused), and how to mask payload data. Only client meets [`ClientRole`],
+//! Both client and server meet [`RoleHelper`], which indicates the length of
+//! an incoming frame head, and how to mask payload data. Only client meets [`ClientRole`],
 //! and only server meets [`ServerRole`].
 //!
+//! Note that a frame head sent by a client carries a mask key, so a server
+//! expects 4 more bytes than a client does.
+//!
+//! [`DeflateClient`] and [`DeflateServer`] also meet [`DeflateRole`], which carries
+//! negotiated `permessage-deflate` parameters.
+//!
 //! Any type implements these traits will be treated as a `client` or `server`.
 
 use crate::frame::Mask;
+use crate::handshake::Negotiated;
 
 /// Client or Server marker.
 pub trait RoleHelper: Clone + Copy {
+    /// Length of an incoming frame head, with a 7-bit payload length.
     const SHORT_FRAME_HEAD_LEN: u8;
+    /// Length of an incoming frame head, with a 16-bit payload length.
     const COMMON_FRAME_HEAD_LEN: u8;
+    /// Length of an incoming frame head, with a 64-bit payload length.
     const LONG_FRAME_HEAD_LEN: u8;
 
     fn new() -> Self;
     fn mask_key(&self) -> Mask;
     // by default this is a no-op
     fn set_mask_key(&mut self, _: [u8; 4]) {}
+    /// Apply results negotiated during the handshake, by default this is a no-op.
+    fn set_negotiated(&mut self, _: &Negotiated) {}
 }
 
 /// Client marker.
@@ -39,10 +51,81 @@ pub trait ServerRole: RoleHelper {}
 /// Client marker.
 pub trait AutoMaskClientRole: ClientRole {
     const UPDATE_MASK_KEY: bool;
+
+    /// Called before each write, decide whether to update the mask key.
+    /// By default it follows [`UPDATE_MASK_KEY`](Self::UPDATE_MASK_KEY).
+    ///
+    /// Override this to apply a custom policy, e.g. update by time.
+    #[inline]
+    fn update_mask_key(&mut self) -> bool { Self::UPDATE_MASK_KEY }
 }
 
 mod server;
 mod client;
+mod deflate;
 
 pub use server::Server;
-pub use client::{Client, StandardClient, FixedMaskClient};
+pub use client::{Client, StandardClient, FixedMaskClient, RotateMaskClient};
+pub use deflate::{DeflateRole, DeflateClient, DeflateServer};
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::frame::{FrameHead, Fin, OpCode, PayloadLen};
+
+    #[test]
+    fn frame_head_len() {
+        fn check<R: RoleHelper, Peer: RoleHelper>() {
+            let head_len = |n| {
+                FrameHead::new(
+                    Fin::Y,
+                    OpCode::Binary,
+                    Peer::new().mask_key(),
+                    PayloadLen::from_num(n),
+                )
+                .encoded_len() as u8
+            };
+            assert_eq!(head_len(125), R::SHORT_FRAME_HEAD_LEN);
+            assert_eq!(head_len(65535), R::COMMON_FRAME_HEAD_LEN);
+            assert_eq!(head_len(65536), R::LONG_FRAME_HEAD_LEN);
+        }
+
+        check::<Server, Client>();
+        check::<Server, StandardClient>();
+        check::<Server, FixedMaskClient>();
+        check::<Server, RotateMaskClient<16>>();
+        check::<Client, Server>();
+        check::<StandardClient, Server>();
+        check::<FixedMaskClient, Server>();
+        check::<RotateMaskClient<16>, Server>();
+        check::<DeflateServer, DeflateClient>();
+        check::<DeflateClient, DeflateServer>();
+    }
+
+    #[test]
+    fn deflate_params() {
+        use crate::handshake::Deflate;
+
+        let deflate = Deflate {
+            server_no_context_takeover: true,
+            client_no_context_takeover: false,
+            server_max_window_bits: 9,
+            client_max_window_bits: 12,
+        };
+
+        let client = DeflateClient::with_deflate(deflate);
+        assert_eq!(client.deflate(), &deflate);
+        assert_eq!(client.write_window_bits(), 12);
+        assert_eq!(client.read_window_bits(), 9);
+        assert!(!client.write_no_context_takeover());
+        assert!(client.read_no_context_takeover());
+
+        let mut server = DeflateServer::new();
+        assert_eq!(server.deflate(), &Deflate::new());
+        server.set_deflate(deflate);
+        assert_eq!(server.write_window_bits(), 9);
+        assert_eq!(server.read_window_bits(), 12);
+        assert!(server.write_no_context_takeover());
+        assert!(!server.read_no_context_takeover());
+    }
+}
@@ -1,17 +1,16 @@
-use std::io::Result;
+use std::io::{Result, ErrorKind};
 use std::pin::Pin;
 use std::task::{Poll, Context};
 
 use tokio::io::AsyncRead;
 use tokio::io::ReadBuf;
 
-use super::{Stream, RoleHelper, Guarded};
+use super::{Stream, StreamProj, RoleHelper, Guarded};
 use super::detail::read_some;
 
 impl<IO, Role> AsyncRead for Stream<IO, Role>
 where
-    IO: AsyncRead + Unpin,
-    Stream<IO, Role>: Unpin,
+    IO: AsyncRead,
     Role: RoleHelper,
 {
     /// Async version of `Stream::read`.
@@ -21,46 +20,70 @@ where
         cx: &mut Context<'_>,
         buf: &mut ReadBuf<'_>,
     ) -> Poll<Result<()>> {
-        read_some(self.get_mut(), |io, buf| {
+        let StreamProj { mut io, state, .. } = self.project();
+        state.poll_keepalive(io.as_mut(), cx)?;
+        let ret = read_some(state, |buf| {
                 let mut buf = ReadBuf::new(buf);
-                Pin::new(io).poll_read(cx, &mut buf)
+                io.as_mut().poll_read(cx, &mut buf)
                 .map_ok(|_| buf.filled().len())
             },
             buf.initialize_unfilled(),
-        ).map_ok(|n| buf.advance(n))
+        );
+        if let Poll::Ready(Ok(n)) = ret {
+            state.touch();
+            if n == 0 {
+                state.poll_send_close(io, cx);
+            }
+        }
+        ret.map_ok(|n| buf.advance(n))
     }
 }
 
 impl<IO, Role> AsyncRead for Stream<IO, Role, Guarded>
 where
-    IO: AsyncRead + Unpin,
-    Stream<IO, Role, Guarded>: Unpin,
+    IO: AsyncRead,
     Role: RoleHelper,
 {
     /// Async version of `Stream::read`.
     /// Continue to read if frame head is not complete.
+    /// Return an error of [`ErrorKind::UnexpectedEof`] once `EOF` is reached
+    /// in the middle of a frame.
     fn poll_read(
         self: Pin<&mut Self>,
         cx: &mut Context<'_>,
         buf: &mut ReadBuf<'_>,
     ) -> Poll<Result<()>> {
-        let this = self.get_mut();
+        let StreamProj { mut io, state, .. } = self.project();
+
+        if buf.remaining() == 0 {
+            return Poll::Ready(Ok(()));
+        }
+
+        state.poll_keepalive(io.as_mut(), cx)?;
 
         loop {
             match read_some(
-                this,
-                |io, buf| {
+                state,
+                |buf| {
                     let mut buf = ReadBuf::new(buf);
-                    Pin::new(io)
+                    io.as_mut()
                         .poll_read(cx, &mut buf)
                         .map_ok(|_| buf.filled().len())
                 },
                 buf.initialize_unfilled(),
             ) {
-                Poll::Ready(Ok(0)) if this.is_read_partial_head() || !this.is_read_end() => {
-                    continue
+                Poll::Ready(Ok(0)) if state.is_read_truncated() => {
+                    return Poll::Ready(Err(ErrorKind::UnexpectedEof.into()))
+                }
+                Poll::Ready(Ok(0)) if state.is_read_partial_head() || !state.is_read_end() => {
+                    state.touch();
+                    continue;
                 }
                 Poll::Ready(Ok(n)) => {
+                    state.touch();
+                    if n == 0 {
+                        state.poll_send_close(io, cx);
+                    }
                     buf.advance(n);
                     return Poll::Ready(Ok(()));
                 }
@@ -1,63 +1,289 @@
-use std::io::Result;
+use std::io::{Result, ErrorKind, IoSlice};
 use std::pin::Pin;
-use std::task::{Poll, Context};
+use std::task::{Poll, Context, ready};
 
 use tokio::io::AsyncWrite;
 
-use super::{Stream, RoleHelper, Guarded};
-use super::detail::write_some;
+use super::{Stream, StreamProj, RoleHelper, Guarded};
+use super::detail::{write_some, write_frames_some, flush_head};
 
 impl<IO, Role> AsyncWrite for Stream<IO, Role>
 where
-    IO: AsyncWrite + Unpin,
-    Stream<IO, Role>: Unpin,
+    IO: AsyncWrite,
     Role: RoleHelper,
 {
     /// Async version of `Stream::write`.
     #[rustfmt::skip]
     fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<Result<usize>> {
-        write_some(self.get_mut(), |io, buf| Pin::new(io).poll_write_vectored(cx, buf), buf)
+        let StreamProj { mut io, state, .. } = self.project();
+        ready!(state.poll_flush_ping(io.as_mut(), cx))?;
+        let ret = write_some(state, |buf| io.as_mut().poll_write_vectored(cx, buf), buf);
+        if let Poll::Ready(Ok(1..)) = ret {
+            state.touch();
+        }
+        ret
+    }
+
+    /// Async version of `Stream::write_frames`.
+    /// Each buffer is written as a frame.
+    #[rustfmt::skip]
+    fn poll_write_vectored(self: Pin<&mut Self>, cx: &mut Context<'_>, bufs: &[IoSlice<'_>]) -> Poll<Result<usize>> {
+        let StreamProj { mut io, state, .. } = self.project();
+        ready!(state.poll_flush_ping(io.as_mut(), cx))?;
+        let ret = write_frames_some(state, |iovec| io.as_mut().poll_write_vectored(cx, iovec), bufs);
+        if let Poll::Ready(Ok(1..)) = ret {
+            state.touch();
+        }
+        ret
     }
 
-    /// This is a no-op since we do not buffer any data.
+    #[inline]
+    fn is_write_vectored(&self) -> bool { true }
+
+    /// Async version of `Stream::flush`.
+    #[rustfmt::skip]
     fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
-        Pin::new(&mut self.get_mut().io).poll_flush(cx)
+        let StreamProj { mut io, state, .. } = self.project();
+        ready!(state.poll_flush_ping(io.as_mut(), cx))?;
+        ready!(flush_head(state, |buf| io.as_mut().poll_write_vectored(cx, buf)))?;
+        io.poll_flush(cx)
     }
 
     /// Shutdown the underlying IO source.
     fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
-        Pin::new(&mut self.get_mut().io).poll_shutdown(cx)
+        self.project().io.poll_shutdown(cx)
     }
 }
 
 impl<IO, Role> AsyncWrite for Stream<IO, Role, Guarded>
 where
-    IO: AsyncWrite + Unpin,
-    Stream<IO, Role, Guarded>: Unpin,
+    IO: AsyncWrite,
     Role: RoleHelper,
 {
     /// Async version of `Stream::write`.
     /// Continue to write if frame head is not completely written.
+    /// Return an error of [`ErrorKind::WriteZero`] once `WriteZero` occurs.
     #[rustfmt::skip]
     fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<Result<usize>> {
-        let this = self.get_mut();
+        let StreamProj { mut io, state, .. } = self.project();
+        ready!(state.poll_flush_ping(io.as_mut(), cx))?;
         loop {
-            match write_some(this, |io, buf| Pin::new(io).poll_write_vectored(cx, buf), buf) {
-                Poll::Ready(Ok(0)) if this.is_write_partial_head() || !this.is_write_zero()=> continue,
-                Poll::Ready(Ok(n)) => return Poll::Ready(Ok(n)),
+            match write_some(state, |buf| io.as_mut().poll_write_vectored(cx, buf), buf) {
+                Poll::Ready(Ok(0)) if state.is_write_zero() => return Poll::Ready(Err(ErrorKind::WriteZero.into())),
+                Poll::Ready(Ok(0)) => continue,
+                Poll::Ready(Ok(n)) => {
+                    state.touch();
+                    return Poll::Ready(Ok(n));
+                }
                 Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
                 Poll::Pending => return Poll::Pending,
             }
         }
     }
 
-    /// This is a no-op since we do not buffer any data.
+    /// Async version of `Stream::write_frames`.
+    /// Each buffer is written as a frame.
+    /// Continue to write if frame head is not completely written.
+    /// Return an error of [`ErrorKind::WriteZero`] once `WriteZero` occurs.
+    #[rustfmt::skip]
+    fn poll_write_vectored(self: Pin<&mut Self>, cx: &mut Context<'_>, bufs: &[IoSlice<'_>]) -> Poll<Result<usize>> {
+        let StreamProj { mut io, state, .. } = self.project();
+        ready!(state.poll_flush_ping(io.as_mut(), cx))?;
+        if bufs.iter().all(|buf| buf.is_empty()) {
+            return Poll::Ready(Ok(0));
+        }
+        loop {
+            match write_frames_some(state, |iovec| io.as_mut().poll_write_vectored(cx, iovec), bufs) {
+                Poll::Ready(Ok(0)) if state.is_write_zero() => return Poll::Ready(Err(ErrorKind::WriteZero.into())),
+                Poll::Ready(Ok(0)) => continue,
+                Poll::Ready(Ok(n)) => {
+                    state.touch();
+                    return Poll::Ready(Ok(n));
+                }
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+
+    #[inline]
+    fn is_write_vectored(&self) -> bool { true }
+
+    /// Async version of `Stream::flush`.
+    #[rustfmt::skip]
     fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
-        Pin::new(&mut self.get_mut().io).poll_flush(cx)
+        let StreamProj { mut io, state, .. } = self.project();
+        ready!(state.poll_flush_ping(io.as_mut(), cx))?;
+        ready!(flush_head(state, |buf| io.as_mut().poll_write_vectored(cx, buf)))?;
+        io.poll_flush(cx)
     }
 
     /// Shutdown the underlying IO source.
     fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
-        Pin::new(&mut self.get_mut().io).poll_shutdown(cx)
+        self.project().io.poll_shutdown(cx)
+    }
+}
+
+impl<IO, Role: RoleHelper, Guard> Stream<IO, Role, Guard>
+where
+    Self: AsyncWrite,
+{
+    /// Async version of [`close`](Self::close).
+    /// The close frame is staged on the first poll, and written by
+    /// [`poll_flush`](AsyncWrite::poll_flush).
+    pub fn poll_close(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        code: u16,
+        reason: &[u8],
+    ) -> Poll<Result<()>> {
+        self.as_mut().project().state.stage_close(code, reason)?;
+        self.poll_flush(cx)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::test::{make_head, make_frame};
+    use crate::frame::{FrameReader, Mask, OpCode};
+    use crate::role::*;
+    use std::marker::PhantomPinned;
+    use tokio::io::{AsyncRead, ReadBuf};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt, DuplexStream};
+
+    #[tokio::test]
+    async fn async_write_vectored() {
+        let payloads: [&[u8]; 4] = [b"hello", b"", b" ", b"world"];
+        let bufs = payloads.map(IoSlice::new);
+
+        let mut stream = Stream::new(Vec::new(), Server);
+        assert!(stream.is_write_vectored());
+        let n = stream.write_vectored(&bufs).await.unwrap();
+        assert_eq!(n, 11);
+
+        let mut stream = stream.guard();
+        assert!(stream.is_write_vectored());
+        assert_eq!(stream.write_vectored(&bufs[1..2]).await.unwrap(), 0);
+        let n = stream.write_vectored(&bufs).await.unwrap();
+        assert_eq!(n, 11);
+
+        let frames: Vec<_> = FrameReader::new(stream.as_ref())
+            .map(|x| x.unwrap())
+            .inspect(|(head, _)| assert_eq!(head.opcode, OpCode::Binary))
+            .map(|(_, payload)| payload)
+            .collect();
+        let expect: [&[u8]; 6] = [b"hello", b" ", b"world", b"hello", b" ", b"world"];
+        assert_eq!(frames, expect);
+    }
+
+    #[tokio::test]
+    async fn async_flush_pong() {
+        let (local, mut remote) = tokio::io::duplex(0x100);
+        let mut stream = Stream::builder(local, Server).auto_pong(true).build();
+
+        let ping = [make_head(OpCode::Ping, Mask::None, 4), b"ping".to_vec()].concat();
+        remote.write_all(&ping).await.unwrap();
+        let mut buf = [0_u8; 0x100];
+        assert_eq!(stream.read(&mut buf).await.unwrap(), 0);
+        assert_eq!(stream.pending_pings(), 1);
+
+        // answered without a data write
+        stream.flush().await.unwrap();
+        assert_eq!(stream.pending_pings(), 0);
+        let pong = [make_head(OpCode::Pong, Mask::None, 4), b"ping".to_vec()].concat();
+        let mut buf = vec![0_u8; pong.len()];
+        remote.read_exact(&mut buf).await.unwrap();
+        assert_eq!(buf, pong);
+    }
+
+    #[tokio::test]
+    async fn async_close() {
+        let (local, mut remote) = tokio::io::duplex(0x100);
+        let mut stream = Stream::new(local, Server);
+        stream.write_all(b"hello").await.unwrap();
+        std::future::poll_fn(|cx| Pin::new(&mut stream).poll_close(cx, 1000, b"bye"))
+            .await
+            .unwrap();
+        assert!(stream.is_write_close());
+        let e = stream.write_all(b"world").await.unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::Other);
+
+        let frame = [make_head(OpCode::Binary, Mask::None, 5), b"hello".to_vec()].concat();
+        let close = [
+            make_head(OpCode::Close, Mask::None, 5),
+            b"\x03\xe8bye".to_vec(),
+        ]
+        .concat();
+        let mut buf = vec![0_u8; frame.len() + close.len()];
+        remote.read_exact(&mut buf).await.unwrap();
+        assert_eq!(buf, [frame, close].concat());
+    }
+
+    pin_project_lite::pin_project! {
+        /// A `!Unpin` IO, like some TLS streams.
+        struct NotUnpin {
+            #[pin]
+            inner: DuplexStream,
+            #[pin]
+            _pin: PhantomPinned,
+        }
+    }
+
+    impl AsyncRead for NotUnpin {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<Result<()>> {
+            self.project().inner.poll_read(cx, buf)
+        }
+    }
+
+    impl AsyncWrite for NotUnpin {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<Result<usize>> {
+            self.project().inner.poll_write(cx, buf)
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+            self.project().inner.poll_flush(cx)
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+            self.project().inner.poll_shutdown(cx)
+        }
+    }
+
+    #[tokio::test]
+    async fn not_unpin_io() {
+        async fn read_write<R: RoleHelper>() {
+            let (local, mut remote) = tokio::io::duplex(0x100);
+            let io = NotUnpin {
+                inner: local,
+                _pin: PhantomPinned,
+            };
+            let stream = Stream::new(io, R::new()).guard();
+            tokio::pin!(stream);
+
+            let (frame, data) = make_frame::<R>(OpCode::Binary, 100);
+            stream.write_all(&data).await.unwrap();
+            stream.flush().await.unwrap();
+
+            let mut buf = vec![0; frame.len()];
+            remote.read_exact(&mut buf).await.unwrap();
+            assert_eq!(buf, frame);
+
+            remote.write_all(&frame).await.unwrap();
+            let mut buf = vec![0; data.len()];
+            stream.read_exact(&mut buf).await.unwrap();
+            assert_eq!(buf, data);
+        }
+
+        read_write::<Client>().await;
+        read_write::<Server>().await;
     }
 }
@@ -0,0 +1,282 @@
+use std::io::{Read, BufRead, Write, Result};
+
+use super::{Stream, Direct, Guarded};
+use crate::bleed::Store;
+
+/// A [`Stream`] with a small read buffer, `14 <= N <= 255`.
+///
+/// Payload is read into the internal buffer first, which always holds
+/// a complete frame head, so a read into a buffer of any size is
+/// as efficient as the one into a buffer not shorter than 14 bytes.
+/// A read into a buffer not shorter than `N` bypasses the internal buffer.
+///
+/// In [`Guarded`] mode, it also implements [`BufRead`],
+/// which unlocks adapters like [`lines`](BufRead::lines).
+///
+/// Other operations are delegated to the inner stream.
+/// Async operations require an `Unpin` IO source,
+/// a `!Unpin` one could be wrapped with [`Box::pin`].
+///
+/// Example:
+///
+/// ```
+/// use std::io::Read;
+/// use lightws::role::Client;
+/// use lightws::stream::{Stream, BufferedStream};
+///
+/// let frame = [0x82, 0x05, b'h', b'e', b'l', b'l', b'o'];
+/// let stream = Stream::new(frame.as_slice(), Client).guard();
+/// let mut stream = BufferedStream::<_, _, _, 64>::new(stream);
+///
+/// let mut buf = [0_u8; 2];
+/// assert_eq!(stream.read(&mut buf).unwrap(), 2);
+/// assert_eq!(&buf, b"he");
+/// assert_eq!(stream.buffer(), b"llo");
+/// ```
+#[derive(Debug)]
+pub struct BufferedStream<IO, Role, Guard = Direct, const N: usize = 64> {
+    stream: Stream<IO, Role, Guard>,
+    buf: Store<N>,
+}
+
+impl<IO, Role, Guard, const N: usize> BufferedStream<IO, Role, Guard, N> {
+    /// Buffer must be able to hold a complete frame head.
+    const CAPACITY_CHECK: () = assert!(N >= 14);
+
+    /// Constructor, take the inner stream.
+    #[inline]
+    pub const fn new(stream: Stream<IO, Role, Guard>) -> Self {
+        #[allow(clippy::let_unit_value)]
+        let _ = Self::CAPACITY_CHECK;
+        Self {
+            stream,
+            buf: Store::new(),
+        }
+    }
+
+    /// Get the buffered payload.
+    #[inline]
+    pub const fn buffer(&self) -> &[u8] { self.buf.read() }
+
+    /// Get a reference to the inner stream.
+    #[inline]
+    pub const fn get_ref(&self) -> &Stream<IO, Role, Guard> { &self.stream }
+
+    /// Get a mutable reference to the inner stream.
+    ///
+    /// Caution: read from the inner stream directly skips the buffered payload.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut Stream<IO, Role, Guard> { &mut self.stream }
+
+    /// Get the inner stream, the buffered payload is lost.
+    #[inline]
+    pub fn into_inner(self) -> Stream<IO, Role, Guard> { self.stream }
+
+    /// Copy buffered payload to the provided buffer.
+    #[inline]
+    fn read_buffered(&mut self, buf: &mut [u8]) -> usize {
+        let n = std::cmp::min(buf.len(), self.buf.rd_left());
+        buf[..n].copy_from_slice(&self.buf.read()[..n]);
+        self.buf.advance_rd_pos(n);
+        n
+    }
+}
+
+impl<IO, Role, Guard, const N: usize> Read for BufferedStream<IO, Role, Guard, N>
+where
+    Stream<IO, Role, Guard>: Read,
+{
+    /// Read some data like [`Stream::read`], from the buffered payload if any.
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        if self.buf.rd_left() == 0 {
+            if buf.len() >= N {
+                return self.stream.read(buf);
+            }
+            self.buf.reset();
+            let n = self.stream.read(self.buf.write())?;
+            self.buf.advance_wr_pos(n);
+        }
+        Ok(self.read_buffered(buf))
+    }
+}
+
+impl<IO, Role, const N: usize> BufRead for BufferedStream<IO, Role, Guarded, N>
+where
+    Stream<IO, Role, Guarded>: Read,
+{
+    /// Return the buffered payload, read more if there is none.
+    ///
+    /// An empty slice indicates the end of the stream.
+    fn fill_buf(&mut self) -> Result<&[u8]> {
+        if self.buf.rd_left() == 0 {
+            self.buf.reset();
+            let n = self.stream.read(self.buf.write())?;
+            self.buf.advance_wr_pos(n);
+        }
+        Ok(self.buf.read())
+    }
+
+    #[inline]
+    fn consume(&mut self, amt: usize) {
+        self.buf
+            .advance_rd_pos(std::cmp::min(amt, self.buf.rd_left()));
+    }
+}
+
+impl<IO, Role, Guard, const N: usize> Write for BufferedStream<IO, Role, Guard, N>
+where
+    Stream<IO, Role, Guard>: Write,
+{
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> Result<usize> { self.stream.write(buf) }
+
+    #[inline]
+    fn flush(&mut self) -> Result<()> { self.stream.flush() }
+}
+
+cfg_if::cfg_if! {
+    if #[cfg(feature = "async")] {
+        use std::pin::Pin;
+        use std::task::{Context, Poll, ready};
+        use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+        impl<IO, Role, Guard, const N: usize> AsyncRead for BufferedStream<IO, Role, Guard, N>
+        where
+            Stream<IO, Role, Guard>: AsyncRead + Unpin,
+        {
+            /// Async version of [`BufferedStream::read`].
+            fn poll_read(
+                self: Pin<&mut Self>,
+                cx: &mut Context<'_>,
+                buf: &mut ReadBuf<'_>,
+            ) -> Poll<Result<()>> {
+                let this = self.get_mut();
+                let (mut stream, store) = (Pin::new(&mut this.stream), &mut this.buf);
+                if store.rd_left() == 0 {
+                    if buf.remaining() >= N {
+                        return stream.poll_read(cx, buf);
+                    }
+                    store.reset();
+                    let mut inner = ReadBuf::new(store.write());
+                    ready!(stream.as_mut().poll_read(cx, &mut inner))?;
+                    let n = inner.filled().len();
+                    store.advance_wr_pos(n);
+                }
+                let n = std::cmp::min(buf.remaining(), store.rd_left());
+                buf.put_slice(&store.read()[..n]);
+                store.advance_rd_pos(n);
+                Poll::Ready(Ok(()))
+            }
+        }
+
+        impl<IO, Role, Guard, const N: usize> AsyncWrite for BufferedStream<IO, Role, Guard, N>
+        where
+            Stream<IO, Role, Guard>: AsyncWrite + Unpin,
+        {
+            #[inline]
+            fn poll_write(
+                self: Pin<&mut Self>,
+                cx: &mut Context<'_>,
+                buf: &[u8],
+            ) -> Poll<Result<usize>> {
+                Pin::new(&mut self.get_mut().stream).poll_write(cx, buf)
+            }
+
+            #[inline]
+            fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+                Pin::new(&mut self.get_mut().stream).poll_flush(cx)
+            }
+
+            #[inline]
+            fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+                Pin::new(&mut self.get_mut().stream).poll_shutdown(cx)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::test::*;
+    use crate::frame::{Mask, OpCode};
+    use crate::role::*;
+
+    #[test]
+    fn read_buffered_stream() {
+        fn read<R: RoleHelper>(rlimit: usize, size: usize) {
+            let mut input = Vec::new();
+            let mut expect = Vec::new();
+            for len in [0, 1, 13, 14, 100, 1000] {
+                let (frame, data) = make_frame::<R>(OpCode::Binary, len);
+                input.extend(frame);
+                expect.extend(data);
+            }
+
+            let io = LimitReadWriter {
+                buf: input,
+                rlimit,
+                wlimit: 0,
+                cursor: 0,
+            };
+            let stream = Stream::new(io, Server).guard();
+            let mut stream = BufferedStream::<_, _, _, 32>::new(stream);
+
+            let mut buf = vec![0; size];
+            let mut data = Vec::new();
+            loop {
+                let n = stream.read(&mut buf).unwrap();
+                if n == 0 && stream.get_ref().is_read_end() {
+                    break;
+                }
+                data.extend_from_slice(&buf[..n]);
+            }
+            assert_eq!(data, expect);
+        }
+
+        for rlimit in [1, 3, 16, 100, 4096] {
+            for size in [1, 2, 7, 14, 31, 32, 100] {
+                read::<Client>(rlimit, size);
+            }
+        }
+    }
+
+    #[test]
+    fn read_lines_from_buffered_stream() {
+        let mut input = Vec::new();
+        for data in [&b"hello\nwor"[..], b"", b"ld\n", b"last"] {
+            input.extend(make_head(OpCode::Binary, Mask::None, data.len()));
+            input.extend_from_slice(data);
+        }
+
+        let io = LimitReadWriter {
+            buf: input,
+            rlimit: 3,
+            wlimit: 0,
+            cursor: 0,
+        };
+        let stream = Stream::new(io, Client).guard();
+        let stream = BufferedStream::<_, _, _, 16>::new(stream);
+
+        let lines: Vec<String> = stream.lines().map(|x| x.unwrap()).collect();
+        assert_eq!(lines, ["hello", "world", "last"]);
+    }
+
+    #[tokio::test]
+    async fn async_read_buffered_stream() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let (local, mut remote) = tokio::io::duplex(0x100);
+        let stream = Stream::new(local, Server).guard();
+        let mut stream = BufferedStream::<_, _, _, 16>::new(stream);
+
+        let (frame, data) = make_frame::<Client>(OpCode::Binary, 100);
+        remote.write_all(&frame).await.unwrap();
+
+        let mut buf = vec![0; data.len()];
+        for chunk in buf.chunks_mut(3) {
+            stream.read_exact(chunk).await.unwrap();
+        }
+        assert_eq!(buf, data);
+    }
+}
@@ -0,0 +1,184 @@
+use super::Stream;
+
+#[cfg(feature = "async")]
+use std::time::Duration;
+
+use crate::frame::OpCode;
+
+/// Build a [`Stream`] with options.
+///
+/// Example:
+///
+/// ```
+/// use lightws::role::Server;
+/// use lightws::stream::Stream;
+/// use lightws::frame::OpCode;
+///
+/// let stream = Stream::builder(Vec::<u8>::new(), Server)
+///     .max_message_size(0x10000)
+///     .auto_pong(true)
+///     .text(true)
+///     .build();
+///
+/// assert_eq!(stream.max_message_size(), 0x10000);
+/// assert!(stream.is_auto_pong());
+/// assert_eq!(stream.write_opcode(), OpCode::Text);
+/// ```
+#[derive(Debug, Clone)]
+pub struct StreamBuilder<IO, Role> {
+    io: IO,
+    role: Role,
+    max_message_size: u64,
+    strict_length: bool,
+    strict_mask: bool,
+    strict_control: bool,
+    auto_pong: bool,
+    accept_text: bool,
+    write_opcode: OpCode,
+    #[cfg(feature = "async")]
+    ping_interval: Option<Duration>,
+}
+
+impl<IO, Role> StreamBuilder<IO, Role> {
+    /// Constructor, with the same defaults as [`Stream::new`].
+    #[inline]
+    pub const fn new(io: IO, role: Role) -> Self {
+        Self {
+            io,
+            role,
+            max_message_size: u64::MAX,
+            strict_length: false,
+            strict_mask: false,
+            strict_control: false,
+            auto_pong: false,
+            accept_text: false,
+            write_opcode: OpCode::Binary,
+            #[cfg(feature = "async")]
+            ping_interval: None,
+        }
+    }
+
+    /// Set the max payload length of an incoming data frame,
+    /// see [`Stream::set_max_message_size`].
+    #[inline]
+    pub fn max_message_size(mut self, size: u64) -> Self {
+        self.max_message_size = size;
+        self
+    }
+
+    /// Reject a frame length not in its shortest form,
+    /// see [`Stream::set_strict_length`].
+    #[inline]
+    pub fn strict_length(mut self, strict: bool) -> Self {
+        self.strict_length = strict;
+        self
+    }
+
+    /// Reject a frame without the expected mask,
+    /// see [`Stream::set_strict_mask`].
+    #[inline]
+    pub fn strict_mask(mut self, strict: bool) -> Self {
+        self.strict_mask = strict;
+        self
+    }
+
+    /// Reject an illegal control frame,
+    /// see [`Stream::set_strict_control`].
+    #[inline]
+    pub fn strict_control(mut self, strict: bool) -> Self {
+        self.strict_control = strict;
+        self
+    }
+
+    /// Enable or disable all the strict options,
+    /// see [`Stream::set_strict`].
+    #[inline]
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict_length = strict;
+        self.strict_mask = strict;
+        self.strict_control = strict;
+        self.accept_text = strict;
+        self
+    }
+
+    /// Answer pings automatically, see [`Stream::set_auto_pong`].
+    #[inline]
+    pub fn auto_pong(mut self, enable: bool) -> Self {
+        self.auto_pong = enable;
+        self
+    }
+
+    /// Accept `Text` messages, see [`Stream::set_accept_text`].
+    #[inline]
+    pub fn accept_text(mut self, enable: bool) -> Self {
+        self.accept_text = enable;
+        self
+    }
+
+    /// Write new messages as `Text` instead of `Binary`,
+    /// see [`Stream::write_opcode`].
+    #[inline]
+    pub fn text(mut self, enable: bool) -> Self {
+        self.write_opcode = if enable { OpCode::Text } else { OpCode::Binary };
+        self
+    }
+
+    /// Create the stream.
+    pub fn build(self) -> Stream<IO, Role> {
+        let (io, role, options) = self.split();
+        options.configure(Stream::new(io, role))
+    }
+
+    /// Take `io` and `role` out, leave the options.
+    fn split(self) -> (IO, Role, StreamBuilder<(), ()>) {
+        let options = StreamBuilder {
+            io: (),
+            role: (),
+            max_message_size: self.max_message_size,
+            strict_length: self.strict_length,
+            strict_mask: self.strict_mask,
+            strict_control: self.strict_control,
+            auto_pong: self.auto_pong,
+            accept_text: self.accept_text,
+            write_opcode: self.write_opcode,
+            #[cfg(feature = "async")]
+            ping_interval: self.ping_interval,
+        };
+        (self.io, self.role, options)
+    }
+
+    /// Apply the options to an existing stream, e.g. one returned by a handshake.
+    pub(crate) fn configure<S, R>(&self, mut stream: Stream<S, R>) -> Stream<S, R> {
+        stream.set_max_message_size(self.max_message_size);
+        stream.set_strict_length(self.strict_length);
+        stream.set_strict_mask(self.strict_mask);
+        stream.set_strict_control(self.strict_control);
+        stream.set_auto_pong(self.auto_pong);
+        stream.set_accept_text(self.accept_text);
+        stream.set_write_opcode(self.write_opcode);
+        #[cfg(feature = "async")]
+        if let Some(period) = self.ping_interval {
+            stream.set_ping_interval(period);
+        }
+        stream
+    }
+}
+
+#[cfg(feature = "async")]
+impl<IO, Role> StreamBuilder<IO, Role> {
+    /// Send a ping once the stream is idle for the given period,
+    /// see [`Stream::set_ping_interval`].
+    #[inline]
+    pub fn ping_interval(mut self, period: Duration) -> Self {
+        self.ping_interval = Some(period);
+        self
+    }
+}
+
+impl<IO, Role> Stream<IO, Role> {
+    /// Create a [`StreamBuilder`].
+    #[inline]
+    pub const fn builder(io: IO, role: Role) -> StreamBuilder<IO, Role> {
+        StreamBuilder::new(io, role)
+    }
+}
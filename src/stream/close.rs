@@ -0,0 +1,457 @@
+use std::io::{Write, Result};
+use std::pin::Pin;
+use std::task::Poll;
+
+use super::{Stream, State};
+use super::state::{CtrlStore, MAX_CONTROL_LEN};
+use super::detail::{flush_head, flush_close};
+
+use crate::frame::{FrameHead, Fin, OpCode, Mask, PayloadLen};
+use crate::frame::apply_mask4;
+use crate::role::RoleHelper;
+use crate::error::CtrlError;
+
+impl<IO, Role: RoleHelper, Guard> Stream<IO, Role, Guard>
+where
+    Self: Write,
+{
+    /// Send a close frame, which carries a status code and a reason of at most 123 bytes,
+    /// then flush the underlying IO source.
+    /// A longer reason will fail with [`CtrlError::CloseReasonTooLong`].
+    ///
+    /// The frame is staged and written by [`flush`](Write::flush) after pending pongs,
+    /// never in the middle of a data frame. If a data frame is partially written,
+    /// it is sent by a later flush once that frame is finished,
+    /// see [`is_write_close`](Self::is_write_close).
+    ///
+    /// Only one close frame is sent, a staged one is kept, e.g. the echo
+    /// in [strict mode](Self::set_strict_control). Once a close frame is
+    /// (partially) written, a new data frame will fail with [`CtrlError::WriteAfterClose`].
+    ///
+    /// Nothing is sent when the stream is dropped, unless
+    /// [`close_on_drop`](Self::close_on_drop) is set.
+    pub fn close(&mut self, code: u16, reason: &[u8]) -> Result<()> {
+        self.state.stage_close(code, reason)?;
+        self.flush()
+    }
+}
+
+impl<IO, Role: RoleHelper, Guard> Stream<IO, Role, Guard> {
+    /// Send a close frame when the stream is dropped, which carries a status code
+    /// and a reason of at most 123 bytes. It is disabled by default.
+    /// A longer reason will fail with [`CtrlError::CloseReasonTooLong`].
+    ///
+    /// This is best-effort, and only for a sync `IO` which implements [`Write`]:
+    /// drop makes a single write without retrying or flushing, and any error,
+    /// e.g. `WouldBlock`, is ignored. A blocking `IO` is not made non-blocking,
+    /// so this write may block like any other if its send buffer is full.
+    ///
+    /// A close frame staged by [`close`](Self::close) is preferred, nothing is
+    /// sent once a close frame has been written, or while a data frame or a pong
+    /// is partially written. Nothing is sent by [`into_inner`](Self::into_inner).
+    /// An async stream should be closed explicitly.
+    pub fn close_on_drop(&mut self, code: u16, reason: &[u8]) -> Result<()> {
+        self.state.drop_close = Some(self.state.build_close(code, reason)?);
+        Ok(())
+    }
+}
+
+impl<Role: RoleHelper> State<Role> {
+    /// Build and stage a close frame, unless one is staged, see [`Stream::close`].
+    pub(super) fn stage_close(
+        &mut self,
+        code: u16,
+        reason: &[u8],
+    ) -> std::result::Result<(), CtrlError> {
+        let close_store = self.build_close(code, reason)?;
+        if self.close_store.is_none() {
+            self.close_store = Some(close_store);
+        }
+        Ok(())
+    }
+
+    /// Build a close frame, masked if required by the role.
+    fn build_close(
+        &self,
+        code: u16,
+        reason: &[u8],
+    ) -> std::result::Result<Box<CtrlStore>, CtrlError> {
+        if 2 + reason.len() > MAX_CONTROL_LEN {
+            return Err(CtrlError::CloseReasonTooLong);
+        }
+
+        let mask = self.role.mask_key();
+        let length = PayloadLen::from_num(2 + reason.len() as u64);
+        let head = FrameHead::new(Fin::Y, OpCode::Close, mask, length);
+
+        let mut close_store = Box::new(CtrlStore::new());
+        let head_len = head.encode(close_store.write()).unwrap();
+        let payload = &mut close_store.write()[head_len..head_len + 2 + reason.len()];
+        payload[..2].copy_from_slice(&code.to_be_bytes());
+        payload[2..].copy_from_slice(reason);
+        if let Mask::Key(key) = mask {
+            apply_mask4(key, payload);
+        }
+        close_store.advance_wr_pos(head_len + 2 + reason.len());
+        Ok(close_store)
+    }
+
+    /// Try to send a staged close frame once a received close frame is reported,
+    /// e.g. the echo in strict mode, only if IO is writable.
+    /// An error is left to the next flush.
+    #[inline]
+    pub(super) fn send_close<IO>(&mut self, io: &mut IO) {
+        if self.is_read_close() && self.is_close_pending() {
+            io.send_close(self);
+        }
+    }
+}
+
+/// Write a staged close frame from the read path, only if IO is writable.
+trait SendClose {
+    fn send_close<Role: RoleHelper>(&mut self, state: &mut State<Role>);
+}
+
+impl<IO> SendClose for IO {
+    default fn send_close<Role: RoleHelper>(&mut self, _: &mut State<Role>) {}
+}
+
+impl<IO: Write> SendClose for IO {
+    fn send_close<Role: RoleHelper>(&mut self, state: &mut State<Role>) {
+        if let Poll::Ready(Ok(())) = flush_head(state, |iovec| self.write_vectored(iovec).into()) {
+            let _ = self.flush();
+        }
+    }
+}
+
+/// Write the close frame set by [`Stream::close_on_drop`] when the stream is dropped,
+/// only if IO is writable.
+pub(super) trait DropClose<Role> {
+    fn drop_close(self: Pin<&mut Self>, state: &mut State<Role>);
+}
+
+impl<IO, Role> DropClose<Role> for IO {
+    default fn drop_close(self: Pin<&mut Self>, _: &mut State<Role>) {}
+}
+
+impl<IO: Write + Unpin, Role: RoleHelper> DropClose<Role> for IO {
+    fn drop_close(self: Pin<&mut Self>, state: &mut State<Role>) {
+        let Some(close_store) = state.drop_close.take() else {
+            return;
+        };
+        if state.close_store.is_none() {
+            state.close_store = Some(close_store);
+        }
+
+        // write once, never wait
+        let io = self.get_mut();
+        let mut once = true;
+        let _ = flush_close(state, &mut |iovec| {
+            if std::mem::take(&mut once) {
+                io.write_vectored(iovec).into()
+            } else {
+                Poll::Pending
+            }
+        });
+    }
+}
+
+cfg_if::cfg_if! {
+    if #[cfg(feature = "async")] {
+        use std::task::Context;
+        use tokio::io::AsyncWrite;
+
+        impl<Role: RoleHelper> State<Role> {
+            /// Async version of [`send_close`](Self::send_close).
+            /// A pending write is resumed by the next read or flush.
+            #[inline]
+            pub(super) fn poll_send_close<IO>(&mut self, io: Pin<&mut IO>, cx: &mut Context<'_>) {
+                if self.is_read_close() && self.is_close_pending() {
+                    io.poll_send_close(cx, self);
+                }
+            }
+        }
+
+        /// Write a staged close frame from the read path, only if IO is writable.
+        trait PollSendClose {
+            fn poll_send_close<Role: RoleHelper>(
+                self: Pin<&mut Self>,
+                cx: &mut Context<'_>,
+                state: &mut State<Role>,
+            );
+        }
+
+        impl<IO> PollSendClose for IO {
+            default fn poll_send_close<Role: RoleHelper>(
+                self: Pin<&mut Self>,
+                _: &mut Context<'_>,
+                _: &mut State<Role>,
+            ) {
+            }
+        }
+
+        impl<IO: AsyncWrite> PollSendClose for IO {
+            fn poll_send_close<Role: RoleHelper>(
+                mut self: Pin<&mut Self>,
+                cx: &mut Context<'_>,
+                state: &mut State<Role>,
+            ) {
+                let ret = flush_head(state, |iovec| self.as_mut().poll_write_vectored(cx, iovec));
+                if let Poll::Ready(Ok(())) = ret {
+                    let _ = self.poll_flush(cx);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::test::*;
+    use crate::role::*;
+    use crate::frame::FrameReader;
+
+    fn is_ctrl_error(e: std::io::Error, expect: CtrlError) -> bool {
+        let e = e.into_inner().unwrap();
+        matches!(
+            e.downcast_ref::<crate::error::Error>(),
+            Some(crate::error::Error::Ctrl(e)) if *e == expect
+        )
+    }
+
+    #[test]
+    fn close_stream() {
+        // nothing is sent on drop
+        let mut buf: Vec<u8> = Vec::new();
+        drop(Stream::new(&mut buf, Server));
+        assert!(buf.is_empty());
+
+        fn close<R: RoleHelper>(role: R) -> Vec<u8> {
+            let mut stream = Stream::new(Vec::new(), role);
+            let e = stream.close(1000, &[b'x'; 124]).unwrap_err();
+            assert!(is_ctrl_error(e, CtrlError::CloseReasonTooLong));
+            assert!(!stream.is_write_close());
+
+            stream.close(1000, b"bye").unwrap();
+            assert!(stream.is_write_close());
+            // sent only once
+            stream.close(1001, b"ignored").unwrap();
+
+            let mut stream = stream.guard();
+            let e = stream.write_all(b"hello").unwrap_err();
+            assert!(is_ctrl_error(e, CtrlError::WriteAfterClose));
+            stream.into_inner().0
+        }
+
+        let buf = close(Server);
+        let mut reader = FrameReader::new(&buf);
+        let (head, payload) = reader.next().unwrap().unwrap();
+        assert_eq!(head.opcode, OpCode::Close);
+        assert_eq!(head.mask, Mask::None);
+        assert_eq!(payload, b"\x03\xe8bye");
+        assert!(reader.remaining().is_empty());
+
+        let key = [1, 2, 3, 4];
+        let mut role = StandardClient::new();
+        role.set_mask_key(key);
+        let buf = close(role);
+        let mut reader = FrameReader::new(&buf);
+        let (head, payload) = reader.next().unwrap().unwrap();
+        assert_eq!(head.mask, Mask::Key(key));
+        let mut payload = payload.to_vec();
+        apply_mask4(key, &mut payload);
+        assert_eq!(payload, b"\x03\xe8bye");
+        assert!(reader.remaining().is_empty());
+    }
+
+    #[test]
+    fn close_on_drop() {
+        let close = |code: u16| {
+            [
+                make_head(OpCode::Close, Mask::None, 2),
+                code.to_be_bytes().to_vec(),
+            ]
+            .concat()
+        };
+
+        // sent after data frames
+        let mut buf: Vec<u8> = Vec::new();
+        let mut stream = Stream::new(&mut buf, Server).guard();
+        let e = stream.close_on_drop(1000, &[b'x'; 124]).unwrap_err();
+        assert!(is_ctrl_error(e, CtrlError::CloseReasonTooLong));
+        stream.close_on_drop(1001, b"").unwrap();
+        stream.write_all(b"hello").unwrap();
+        drop(stream);
+        let frame = [make_head(OpCode::Binary, Mask::None, 5), b"hello".to_vec()].concat();
+        assert_eq!(buf, [frame, close(1001)].concat());
+
+        // a close frame is sent only once
+        let mut buf: Vec<u8> = Vec::new();
+        let mut stream = Stream::new(&mut buf, Server);
+        stream.close_on_drop(1001, b"").unwrap();
+        stream.close(1000, b"").unwrap();
+        drop(stream);
+        assert_eq!(buf, close(1000));
+
+        // not sent by into_inner
+        let mut buf: Vec<u8> = Vec::new();
+        let mut stream = Stream::new(&mut buf, Server);
+        stream.close_on_drop(1000, b"").unwrap();
+        let _ = stream.into_inner();
+        assert!(buf.is_empty());
+
+        // a single write, a partial frame is left as is
+        let mut io = LimitReadWriter {
+            buf: Vec::new(),
+            rlimit: 0,
+            wlimit: 3,
+            cursor: 0,
+        };
+        let mut stream = Stream::new(&mut io, Server);
+        stream.close_on_drop(1000, b"").unwrap();
+        drop(stream);
+        assert_eq!(io.buf, close(1000)[..3]);
+
+        // not sent in the middle of a data frame
+        let mut io = LimitReadWriter {
+            buf: Vec::new(),
+            rlimit: 0,
+            wlimit: 3,
+            cursor: 0,
+        };
+        let mut stream = Stream::new(&mut io, Server);
+        stream.close_on_drop(1000, b"").unwrap();
+        let _ = stream.write(&[0; 4]).unwrap();
+        assert_ne!(stream.write_remaining(), 0);
+        drop(stream);
+        let frame = [make_head(OpCode::Binary, Mask::None, 4), vec![0; 4]].concat();
+        assert!(io.buf.len() < frame.len());
+        assert!(frame.starts_with(&io.buf));
+    }
+
+    #[test]
+    fn close_after_frame() {
+        let io = LimitReadWriter {
+            buf: Vec::new(),
+            rlimit: 0,
+            wlimit: 3,
+            cursor: 0,
+        };
+        let (frame, data) = make_frame::<Server>(OpCode::Binary, 4);
+        let mut stream = Stream::new(io, Server);
+
+        // the payload is partially written
+        let mut offset = stream.write(&data).unwrap();
+        assert_ne!(stream.write_remaining(), 0);
+        stream.close(1000, b"").unwrap();
+        assert!(!stream.is_write_close());
+
+        // finish the frame, then the close frame
+        while offset < data.len() {
+            offset += stream.write(&data[offset..]).unwrap();
+        }
+        while !stream.is_write_close() {
+            stream.flush().unwrap();
+        }
+
+        let close = [make_head(OpCode::Close, Mask::None, 2), vec![0x03, 0xe8]].concat();
+        assert_eq!(stream.as_ref().buf, [frame, close].concat());
+    }
+
+    #[test]
+    fn echo_close() {
+        use std::io::Read;
+
+        let key = [1, 2, 3, 4];
+        let mut close = make_head(OpCode::Close, Mask::Key(key), 2);
+        let mut code = 1001_u16.to_be_bytes();
+        apply_mask4(key, &mut code);
+        close.extend(code);
+        let echo = [make_head(OpCode::Close, Mask::None, 2), vec![0x03, 0xe9]].concat();
+
+        for wlimit in [1, 2, 100] {
+            let io = LimitReadWriter {
+                buf: close.clone(),
+                rlimit: 100,
+                wlimit,
+                cursor: 0,
+            };
+            let mut stream = Stream::new(io, Server);
+            stream.set_strict(true);
+            let mut buf = [0_u8; 32];
+
+            // sent by the read which reports the close frame
+            assert_eq!(stream.read(&mut buf).unwrap(), 0);
+            assert!(stream.is_read_close());
+            assert!(stream.is_write_close());
+            assert_eq!(&stream.as_ref().buf[close.len()..], &echo);
+        }
+
+        // a read-only IO
+        let mut stream = Stream::new(close.as_slice(), Server);
+        stream.set_strict(true);
+        assert_eq!(stream.read(&mut [0_u8; 32]).unwrap(), 0);
+        assert!(stream.is_read_close());
+        assert!(!stream.is_write_close());
+    }
+
+    #[test]
+    fn validate_close() {
+        use std::io::Read;
+        use crate::error::{Error, FrameError};
+
+        fn read(payload: &[u8], rlimit: usize, size: usize) -> std::io::Result<Vec<u8>> {
+            let key = [1, 2, 3, 4];
+            let mut close = make_head(OpCode::Close, Mask::Key(key), payload.len());
+            let mut payload = payload.to_vec();
+            apply_mask4(key, &mut payload);
+            close.extend(payload);
+            let len = close.len();
+
+            let io = LimitReadWriter {
+                buf: close,
+                rlimit,
+                wlimit: 100,
+                cursor: 0,
+            };
+            let mut stream = Stream::new(io, Server).guard();
+            stream.set_strict(true);
+            let mut buf = vec![0_u8; size];
+
+            assert_eq!(stream.read(&mut buf)?, 0);
+            assert!(stream.is_read_close());
+            Ok(stream.as_ref().buf[len..].to_vec())
+        }
+
+        fn is_frame_error(e: std::io::Error, expect: FrameError) -> bool {
+            let e = e.into_inner().unwrap();
+            matches!(e.downcast_ref::<Error>(), Some(Error::Frame(e)) if *e == expect)
+        }
+
+        let echo = |code: u16| {
+            [
+                make_head(OpCode::Close, Mask::None, 2),
+                code.to_be_bytes().to_vec(),
+            ]
+            .concat()
+        };
+
+        for rlimit in [1, 3, 100] {
+            // the payload is not read along with the head
+            for size in [1, 4, 13, 64] {
+                assert_eq!(read(&[], rlimit, size).unwrap(), echo(1000));
+                let reason = [b"\x0b\xb8", "bye \u{1f600}".as_bytes()].concat();
+                assert_eq!(read(&reason, rlimit, size).unwrap(), echo(3000));
+
+                for code in [999_u16, 1005, 1006, 5000] {
+                    let e = read(&code.to_be_bytes(), rlimit, size).unwrap_err();
+                    assert!(is_frame_error(e, FrameError::IllegalData));
+                }
+
+                let e = read(b"\x03\xe8\xed\xa0\x80", rlimit, size).unwrap_err();
+                assert!(is_frame_error(e, FrameError::InvalidUtf8));
+            }
+        }
+    }
+}
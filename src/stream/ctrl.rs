@@ -1,8 +1,8 @@
 use super::Stream;
-use super::state::WriteState;
+use super::state::{ReadState, WriteState, HeartBeat, Leftover, AutoPong};
 
-use crate::frame::Mask;
-use crate::role::RoleHelper;
+use crate::frame::{Mask, OpCode};
+use crate::role::{RoleHelper, ServerRole};
 use crate::error::CtrlError;
 
 impl<IO, Role, Guard> Stream<IO, Role, Guard>
@@ -11,19 +11,291 @@ where
 {
     /// Get mask for upcoming writes.
     #[inline]
-    pub fn mask_key(&self) -> Mask { self.role.mask_key() }
+    pub fn mask_key(&self) -> Mask { self.state.role.mask_key() }
 
     /// Set mask for upcoming writes.
     /// An attempt to set mask during a write will fail with [`CtrlError::SetMaskInWrite`].
+    ///
+    /// A role which does not hold a mask key(e.g. [`Server`](crate::role::Server)
+    /// or [`Client`](crate::role::Client)) will fail with [`CtrlError::SetMaskNotSupported`].
     #[inline]
     pub fn set_mask_key(&mut self, key: [u8; 4]) -> Result<(), CtrlError> {
+        if !matches!(self.state.role.mask_key(), Mask::Key(_)) {
+            return Err(CtrlError::SetMaskNotSupported);
+        }
         // make sure this is a new fresh write
-        if let WriteState::WriteHead(head) = self.write_state {
+        if let WriteState::WriteHead(head) = self.state.write_state {
             if head.is_empty() {
-                self.role.set_mask_key(key);
+                self.state.role.set_mask_key(key);
                 return Ok(());
             }
         }
         Err(CtrlError::SetMaskInWrite)
     }
 }
+
+impl<IO, Role: ServerRole, Guard> Stream<IO, Role, Guard> {
+    /// Accept unmasked frames from clients, which disables
+    /// [`set_strict_mask`](Self::set_strict_mask) while keeping other strict options,
+    /// e.g. after [`set_strict`](Self::set_strict).
+    ///
+    /// Caution: this deviates from RFC-6455, where a server must close the connection
+    /// upon receiving an unmasked frame. Only use it on trusted internal links,
+    /// e.g. between relays which skip masking.
+    ///
+    /// [RFC-6455 Section 5.1](https://datatracker.ietf.org/doc/html/rfc6455#section-5.1)
+    #[inline]
+    pub fn allow_unmasked(&mut self) { self.state.strict_mask = false; }
+}
+
+impl<IO, Role, Guard> Stream<IO, Role, Guard> {
+    /// Get the max payload length of an incoming message.
+    #[inline]
+    pub const fn max_message_size(&self) -> u64 { self.state.max_message_size }
+
+    /// Set the max payload length of an incoming message, default is unlimited.
+    ///
+    /// Lengths of a fragmented message are summed up across its frames.
+    /// A frame which makes the message exceed the limit will be rejected with
+    /// [`FrameError::FrameTooLarge`](crate::error::FrameError::FrameTooLarge)
+    /// once its head is parsed, before any of its payload is read.
+    #[inline]
+    pub fn set_max_message_size(&mut self, size: u64) { self.state.max_message_size = size; }
+
+    /// Check if a frame length not in its shortest form is rejected.
+    #[inline]
+    pub const fn is_strict_length(&self) -> bool { self.state.strict_length }
+
+    /// Reject a frame length not in its shortest form with
+    /// [`FrameError::IllegalLength`](crate::error::FrameError::IllegalLength),
+    /// see [`FrameHead::decode_strict`](crate::frame::FrameHead::decode_strict).
+    ///
+    /// This is disabled by default, to be lenient with peers.
+    #[inline]
+    pub fn set_strict_length(&mut self, strict: bool) { self.state.strict_length = strict; }
+
+    /// Check if a frame without the expected mask is rejected.
+    #[inline]
+    pub const fn is_strict_mask(&self) -> bool { self.state.strict_mask }
+
+    /// Reject a frame without the expected mask.
+    ///
+    /// A role which does not mask its own frames, e.g. [`Server`](crate::role::Server),
+    /// rejects an unmasked frame with
+    /// [`FrameError::UnmaskedClientFrame`](crate::error::FrameError::UnmaskedClientFrame).
+    /// A role which masks its own frames, e.g. [`Client`](crate::role::Client),
+    /// rejects a masked frame with
+    /// [`FrameError::MaskedServerFrame`](crate::error::FrameError::MaskedServerFrame).
+    ///
+    /// This is disabled by default, to be lenient with peers.
+    ///
+    /// [RFC-6455 Section 5.1](https://datatracker.ietf.org/doc/html/rfc6455#section-5.1)
+    #[inline]
+    pub fn set_strict_mask(&mut self, strict: bool) { self.state.strict_mask = strict; }
+
+    /// Check if illegal control frames are rejected.
+    #[inline]
+    pub const fn is_strict_control(&self) -> bool { self.state.strict_control }
+
+    /// Reject a fragmented control frame, or a close frame
+    /// with an illegal length or status code, with
+    /// [`FrameError::IllegalData`](crate::error::FrameError::IllegalData).
+    /// A close reason which is not UTF-8 is rejected with
+    /// [`FrameError::InvalidUtf8`](crate::error::FrameError::InvalidUtf8).
+    /// The payload of a close frame is saved until it is complete.
+    ///
+    /// The status code of an accepted close frame is echoed
+    /// like [`close`](Self::close), unless a close frame is already staged.
+    /// The echo is written by the read which reports the close frame,
+    /// i.e. returns `0`, if `IO` is writable, otherwise by the next flush.
+    ///
+    /// This is disabled by default, to be lenient with peers.
+    ///
+    /// [RFC-6455 Section 5.5](https://datatracker.ietf.org/doc/html/rfc6455#section-5.5)
+    #[inline]
+    pub fn set_strict_control(&mut self, strict: bool) { self.state.strict_control = strict; }
+
+    /// Check if `Text` messages are accepted.
+    #[inline]
+    pub const fn is_accept_text(&self) -> bool { self.state.accept_text }
+
+    /// Accept `Text` messages, which are read the same as `Binary` ones,
+    /// while their payload is validated as UTF-8 across fragments.
+    /// An invalid sequence is rejected with
+    /// [`FrameError::InvalidUtf8`](crate::error::FrameError::InvalidUtf8)
+    /// as soon as it is read, then the stream stops reading,
+    /// as if `EOF` is reached in the middle of a frame.
+    ///
+    /// This is disabled by default, where a `Text` frame is rejected with
+    /// [`FrameError::UnsupportedOpcode`](crate::error::FrameError::UnsupportedOpcode).
+    #[inline]
+    pub fn set_accept_text(&mut self, enable: bool) { self.state.accept_text = enable; }
+
+    /// Check if all the strict options are enabled.
+    #[inline]
+    pub const fn is_strict(&self) -> bool {
+        self.state.strict_length
+            && self.state.strict_mask
+            && self.state.strict_control
+            && self.state.accept_text
+    }
+
+    /// Enable or disable all the strict options, see
+    /// [`set_strict_length`](Self::set_strict_length),
+    /// [`set_strict_mask`](Self::set_strict_mask),
+    /// [`set_strict_control`](Self::set_strict_control) and
+    /// [`set_accept_text`](Self::set_accept_text),
+    /// where `Text` messages are accepted and validated as UTF-8.
+    ///
+    /// Reserved bits are always rejected, regardless of this option.
+    #[inline]
+    pub fn set_strict(&mut self, strict: bool) {
+        self.state.strict_length = strict;
+        self.state.strict_mask = strict;
+        self.state.strict_control = strict;
+        self.state.accept_text = strict;
+    }
+
+    /// Remove the oldest pending ping so that it is not answered,
+    /// return `false` if there is none.
+    #[inline]
+    pub fn pop_pending_ping(&mut self) -> bool {
+        match &mut self.state.auto_pong {
+            Some(auto_pong) => auto_pong.pop(),
+            None => false,
+        }
+    }
+
+    /// Check if pending pings are answered automatically.
+    #[inline]
+    pub const fn is_auto_pong(&self) -> bool { self.state.auto_pong.is_some() }
+
+    /// Answer pending pings automatically, disabled by default.
+    ///
+    /// Once enabled, completed pings are queued, each pending ping is popped
+    /// and echoed with a pong before the next frame is written, or on flush.
+    /// A partially written pong is finished before another frame,
+    /// so it should not be disabled during that write.
+    ///
+    /// Before a new frame, a write makes at most one write to the IO for pongs,
+    /// then returns `Ok(0)` like a partially written frame head, so the frame
+    /// is written by a later call, see [`Guarded`](super::Guarded).
+    /// Flush writes all pending pongs.
+    ///
+    /// The queue is allocated only when enabled, and dropped when disabled.
+    #[inline]
+    pub fn set_auto_pong(&mut self, enable: bool) {
+        if enable != self.state.auto_pong.is_some() {
+            self.state.auto_pong = enable.then(|| Box::new(AutoPong::new()));
+        }
+    }
+
+    /// Get the opcode of a new message, `Binary` or `Text`.
+    #[inline]
+    pub const fn write_opcode(&self) -> OpCode { self.state.write_opcode }
+
+    /// Set the opcode of a new message, see [`StreamBuilder::text`](super::StreamBuilder::text).
+    #[inline]
+    pub(crate) fn set_write_opcode(&mut self, opcode: OpCode) { self.state.write_opcode = opcode; }
+
+    /// Return the read state machine to its initial state,
+    /// where pings and unread data are also cleared.
+    ///
+    /// This is only meaningful when the underlying IO has been
+    /// replaced or rewound, e.g. to reuse the stream after a `Close`.
+    #[inline]
+    pub fn reset_read_state(&mut self) {
+        self.state.read_state = ReadState::new();
+        self.state.heartbeat = HeartBeat::new();
+        if let Some(auto_pong) = &mut self.state.auto_pong {
+            auto_pong.clear();
+        }
+        self.state.leftover = Leftover::new();
+        self.state.read_fragmented = false;
+        self.state.read_opcode = None;
+        self.state.read_message_len = 0;
+        self.state.read_utf8 = None;
+        self.state.read_close = None;
+    }
+
+    /// Return the write state machine to its initial state,
+    /// where a partially written frame or pong, and a staged close frame, are dropped.
+    ///
+    /// This is only meaningful when the underlying IO has been
+    /// replaced or rewound, e.g. to reuse the stream after `WriteZero`.
+    #[inline]
+    pub fn reset_write_state(&mut self) {
+        self.state.write_state = WriteState::new();
+        self.state.write_fragmented = false;
+        self.state.close_store = None;
+        if let Some(auto_pong) = &mut self.state.auto_pong {
+            auto_pong.pong_store.reset();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::role::*;
+
+    #[test]
+    fn set_mask_key() {
+        let key = [1, 2, 3, 4];
+
+        let mut stream = Stream::new(Vec::<u8>::new(), StandardClient::new());
+        stream.set_mask_key(key).unwrap();
+        assert_eq!(stream.mask_key(), Mask::Key(key));
+
+        let mut stream = Stream::new(Vec::<u8>::new(), Client::new());
+        assert!(matches!(
+            stream.set_mask_key(key),
+            Err(CtrlError::SetMaskNotSupported)
+        ));
+        assert_eq!(stream.mask_key(), Mask::Skip);
+
+        let mut stream = Stream::new(Vec::<u8>::new(), Server::new());
+        assert!(matches!(
+            stream.set_mask_key(key),
+            Err(CtrlError::SetMaskNotSupported)
+        ));
+        assert_eq!(stream.mask_key(), Mask::None);
+    }
+
+    #[test]
+    fn reset_state() {
+        use std::io::{Read, Write};
+        use super::super::test::*;
+
+        let close = make_head(OpCode::Close, Mask::None, 0);
+        let (frame, data) = make_frame::<Server>(OpCode::Binary, 32);
+        let mut buf = vec![0; 64];
+
+        let mut stream = Stream::new(close.as_slice(), Server).guard();
+        assert_eq!(stream.read(&mut buf).unwrap(), 0);
+        assert!(stream.is_read_close());
+
+        // rewind with another frame
+        *stream.as_mut() = frame.as_slice();
+        assert_eq!(stream.read(&mut buf).unwrap(), 0);
+        stream.reset_read_state();
+        assert!(!stream.is_read_end());
+        let n = stream.read(&mut buf).unwrap();
+        assert_eq!(&buf[..n], &data);
+
+        let mut small = [0; 4];
+        let mut buf = vec![0; 64];
+        let mut stream = Stream::new(small.as_mut_slice(), Server);
+        assert_eq!(stream.write(&data).unwrap(), 2);
+        assert_eq!(stream.write(&data[2..]).unwrap(), 0);
+        assert!(stream.is_write_zero());
+
+        // replace with a larger buffer
+        *stream.as_mut() = buf.as_mut_slice();
+        stream.reset_write_state();
+        assert!(!stream.is_write_zero());
+        assert_eq!(stream.write(&data).unwrap(), data.len());
+        drop(stream);
+        assert_eq!(&buf[..frame.len()], &frame);
+    }
+}
@@ -2,7 +2,8 @@ mod read;
 mod write;
 
 pub(super) use read::read_some;
-pub(super) use write::write_some;
+pub(super) use write::{write_some, write_frame_some, write_frames_some, flush_head, flush_close};
+pub use write::MAX_WRITE_FRAMES;
 
 #[inline]
 fn min_len(buf_len: usize, length: u64) -> usize {
@@ -2,78 +2,223 @@ use std::io::Result;
 use std::task::{Poll, ready};
 
 use super::min_len;
-use super::super::{Stream, RoleHelper};
-use super::super::state::{ReadState, HeadStore};
+use super::super::{State, RoleHelper};
+use super::super::state::{ReadState, HeadStore, ClosePayload, MAX_CONTROL_LEN};
+use super::super::utf8::Utf8Validator;
 
-use crate::frame::{FrameHead, Mask, OpCode};
-use crate::frame::mask::apply_mask4;
+use crate::frame::{FrameHead, Fin, Mask, OpCode};
+use crate::frame::mask::{apply_mask4, apply_mask_with_offset};
 use crate::error::FrameError;
 
-pub fn read_some<F, IO, Role, Guard>(
-    stream: &mut Stream<IO, Role, Guard>,
+/// Read from leftover data first, then IO.
+macro_rules! read_io {
+    ($state: expr, $read: expr, $buf: expr) => {{
+        let n = match $state.leftover.read($buf) {
+            0 => ready!($read($buf))?,
+            n => n,
+        };
+        count!($state, bytes_read, n);
+        n
+    }};
+}
+
+/// Max length of a frame head.
+const MAX_HEAD_LEN: usize = 14;
+
+/// Get the length of a frame head from its second byte.
+#[inline]
+const fn head_len(b2: u8) -> usize {
+    let ext_len = match b2 & 0x7f {
+        126 => 2,
+        127 => 8,
+        _ => 0,
+    };
+    let mask_len = if b2 & 0x80 != 0 { 4 } else { 0 };
+    2 + ext_len + mask_len
+}
+
+/// Check if a stored head could be processed without reading more,
+/// which is either complete or already illegal.
+#[inline]
+fn is_head_ready(stored: &[u8]) -> bool {
+    stored.len() >= 2
+        && (stored.len() >= head_len(stored[1])
+            || !matches!(FrameHead::decode(stored), Err(FrameError::NotEnoughData)))
+}
+
+/// Validate payload of a text message, if any, and finish the message
+/// once its last frame is complete. An invalid sequence stops reading.
+macro_rules! validate_utf8 {
+    ($state: expr, $data: expr, $complete: expr) => {{
+        if let Some(utf8) = &mut $state.read_utf8 {
+            let mut ret = utf8.feed($data);
+            if ret.is_ok() && $complete && !$state.read_fragmented {
+                ret = utf8.finish();
+                $state.read_utf8 = None;
+            }
+            if let Err(e) = ret {
+                $state.read_state = ReadState::Eof { truncated: true };
+                return Poll::Ready(Err(e.into()));
+            }
+        }
+    }};
+}
+
+/// Check if a close status code may be received,
+/// [RFC-6455 Section 7.4](https://datatracker.ietf.org/doc/html/rfc6455#section-7.4).
+#[inline]
+const fn is_valid_close_code(code: u16) -> bool {
+    matches!(code, 1000..=1003 | 1007..=1014 | 3000..=4999)
+}
+
+/// Validate a complete close payload, which is empty,
+/// or a status code followed by a UTF-8 reason, return the status code to echo.
+#[inline]
+fn validate_close(payload: &[u8]) -> std::result::Result<u16, FrameError> {
+    let [b1, b2, reason @ ..] = payload else {
+        return Ok(1000);
+    };
+    let code = u16::from_be_bytes([*b1, *b2]);
+    if !is_valid_close_code(code) {
+        return Err(FrameError::IllegalData);
+    }
+    if std::str::from_utf8(reason).is_err() {
+        return Err(FrameError::InvalidUtf8);
+    }
+    Ok(code)
+}
+
+pub fn read_some<F, Role>(
+    state: &mut State<Role>,
     mut read: F,
     buf: &mut [u8],
 ) -> Poll<Result<usize>>
 where
-    F: FnMut(&mut IO, &mut [u8]) -> Poll<Result<usize>>,
+    F: FnMut(&mut [u8]) -> Poll<Result<usize>>,
     Role: RoleHelper,
 {
-    debug_assert!(buf.len() >= 14);
+    if buf.is_empty() {
+        return Poll::Ready(Ok(0));
+    }
+
+    // hold a complete frame head if the provided buffer is too short
+    let mut head_buf = [0_u8; MAX_HEAD_LEN];
+    let mut buf = buf;
 
     loop {
-        match stream.read_state {
+        match state.read_state {
             // always returns 0
-            ReadState::Eof => return Poll::Ready(Ok(0)),
+            ReadState::Eof { .. } => return Poll::Ready(Ok(0)),
             ReadState::Close => return Poll::Ready(Ok(0)),
+            // buffer is too short to parse a frame head in place,
+            // read head bytes into the store, but never payload
+            ReadState::ReadHead(mut head_store) if buf.len() < MAX_HEAD_LEN => {
+                let stored = head_store.rd_left();
+
+                if !is_head_ready(head_store.read()) {
+                    let want = if stored < 2 {
+                        2
+                    } else {
+                        head_len(head_store.read()[1])
+                    };
+                    let (spare, _) = head_store.write().split_at_mut(want - stored);
+                    let read_n = read_io!(state, read, spare);
+
+                    // EOF ?
+                    if read_n == 0 {
+                        state.read_state = ReadState::Eof {
+                            truncated: stored != 0,
+                        };
+                        return Poll::Ready(Ok(0));
+                    }
+
+                    head_store.advance_wr_pos(read_n);
+                    state.read_state = ReadState::ReadHead(head_store);
+                    continue;
+                }
+
+                // process the complete head, there is no payload
+                head_buf[..stored].copy_from_slice(head_store.read());
+                buf = &mut head_buf;
+                state.read_state = ReadState::ProcessBuf {
+                    beg: 0,
+                    end: stored,
+                    processed: 0,
+                };
+            }
             // read a new incoming frame
             ReadState::ReadHead(head_store) => {
                 let head_store_len = head_store.rd_left();
 
-                // write stored data to user provided buffer
+                // write stored data to user provided buffer,
+                // which is not shorter than MAX_HEAD_LEN here
                 if !head_store.is_empty() {
                     let (left, _) = buf.split_at_mut(head_store_len);
                     left.copy_from_slice(head_store.read());
                 }
 
-                let read_n = ready!(read(&mut stream.io, &mut buf[head_store_len..]))?;
+                // a rejected head is stored, see `reject`
+                if is_head_ready(head_store.read()) {
+                    state.read_state = ReadState::ProcessBuf {
+                        beg: 0,
+                        end: head_store_len,
+                        processed: 0,
+                    };
+                    continue;
+                }
+
+                let read_n = read_io!(state, read, &mut buf[head_store_len..]);
 
                 // EOF ?
                 if read_n == 0 {
-                    stream.read_state = ReadState::Eof;
+                    state.read_state = ReadState::Eof {
+                        truncated: head_store_len != 0,
+                    };
                     return Poll::Ready(Ok(0));
                 }
 
-                stream.read_state = ReadState::ProcessBuf {
+                state.read_state = ReadState::ProcessBuf {
                     beg: 0,
                     end: read_n + head_store_len,
                     processed: 0,
                 }
             }
-            // continue to read data from the same frame
-            ReadState::ReadData { next, mask } => {
-                let read_n = ready!(read(&mut stream.io, buf))?;
+            // continue to read data from the same frame,
+            // bytes within the declared length are always payload,
+            // a new head is only parsed once `next` reaches 0
+            ReadState::ReadData {
+                next,
+                mask,
+                phase,
+                opcode,
+            } => {
+                let read_n = read_io!(state, read, buf);
                 // EOF ?
                 if read_n == 0 {
-                    stream.read_state = ReadState::Eof;
+                    state.read_state = ReadState::Eof { truncated: true };
                     return Poll::Ready(Ok(0));
                 }
                 let len = min_len(read_n, next);
                 // unmask if server receives data from client
                 // this operation can be skipped if mask key is 0
+                // continue with the key offset of the last read
                 if let Mask::Key(key) = mask {
-                    apply_mask4(key, &mut buf[..len])
+                    apply_mask_with_offset(key, &mut buf[..len], phase as usize)
                 };
+                validate_utf8!(state, &buf[..len], next <= read_n as u64);
                 // read complete ?
                 if next > read_n as u64 {
                     // need to read more
-                    stream.read_state = ReadState::ReadData {
+                    state.read_state = ReadState::ReadData {
                         next: next - read_n as u64,
                         mask,
+                        phase: ((phase as usize + read_n) & 3) as u8,
+                        opcode,
                     };
                     return Poll::Ready(Ok(read_n));
                 } else {
                     // continue to process
-                    stream.read_state = ReadState::ProcessBuf {
+                    state.read_state = ReadState::ProcessBuf {
                         beg: len,
                         end: read_n,
                         processed: len,
@@ -82,65 +227,191 @@ where
             }
             // continue to read data from a ctrl frame
             ReadState::ReadPing { next, mask } => {
-                let (buf, _) = stream
-                    .heartbeat
-                    .ping_store
-                    .write()
-                    .split_at_mut(next as usize);
-                let read_n = ready!(read(&mut stream.io, buf))?;
+                // never write beyond the store, though `next` is
+                // bounded before entering this state
+                let ping_store = &mut state.heartbeat.ping_store;
+                let offset = ping_store.wr_pos();
+                let to_read = min_len(ping_store.wr_left(), next as u64);
+                let (buf, _) = ping_store.write().split_at_mut(to_read);
+                let read_n = read_io!(state, read, buf);
                 // EOF ?
                 if read_n == 0 {
-                    stream.read_state = ReadState::Eof;
+                    state.read_state = ReadState::Eof { truncated: true };
                     return Poll::Ready(Ok(0));
                 }
                 // unmask if server receives data from client
                 // this operation can be skipped if mask key is 0
+                // stored bytes have been unmasked
                 if let Mask::Key(key) = mask {
-                    apply_mask4(key, buf);
+                    apply_mask_with_offset(key, &mut buf[..read_n], offset);
                 };
 
-                stream.heartbeat.ping_store.advance_wr_pos(read_n);
+                state.heartbeat.ping_store.advance_wr_pos(read_n);
 
                 // read complete ?
                 if next == read_n as u8 {
-                    stream.heartbeat.is_complete = true;
-                    stream.read_state = ReadState::new();
+                    state.complete_ping();
+                    state.read_state = ReadState::new();
                 } else {
-                    stream.read_state = ReadState::ReadPing {
+                    state.read_state = ReadState::ReadPing {
                         next: next - read_n as u8,
                         mask,
                     };
                 }
                 return Poll::Ready(Ok(0));
             }
+            // discard the payload of a pong
+            ReadState::ReadPong { next } => {
+                let to_read = min_len(buf.len(), next as u64);
+                let read_n = read_io!(state, read, &mut buf[..to_read]);
+                // EOF ?
+                if read_n == 0 {
+                    state.read_state = ReadState::Eof { truncated: true };
+                    return Poll::Ready(Ok(0));
+                }
+                state.read_state = if next == read_n as u8 {
+                    ReadState::new()
+                } else {
+                    ReadState::ReadPong {
+                        next: next - read_n as u8,
+                    }
+                };
+                return Poll::Ready(Ok(0));
+            }
+            // continue to read the payload of a close frame in strict mode,
+            // then validate it and stage the echo
+            ReadState::ReadClose { next, mask } => {
+                let Some(close_payload) = &mut state.read_close else {
+                    unreachable!()
+                };
+                if next != 0 {
+                    // never write beyond the store, `next` is bounded
+                    // before entering this state
+                    let offset = close_payload.wr_pos();
+                    let to_read = min_len(close_payload.wr_left(), next as u64);
+                    let (buf, _) = close_payload.write().split_at_mut(to_read);
+                    let read_n = read_io!(state, read, buf);
+                    // EOF ?
+                    if read_n == 0 {
+                        state.read_state = ReadState::Eof { truncated: true };
+                        return Poll::Ready(Ok(0));
+                    }
+                    if let Mask::Key(key) = mask {
+                        apply_mask_with_offset(key, &mut buf[..read_n], offset);
+                    };
+                    close_payload.advance_wr_pos(read_n);
+                    // need to read more
+                    if next != read_n as u8 {
+                        state.read_state = ReadState::ReadClose {
+                            next: next - read_n as u8,
+                            mask,
+                        };
+                        return Poll::Ready(Ok(0));
+                    }
+                }
+
+                let ret = validate_close(close_payload.read());
+                state.read_close = None;
+                match ret {
+                    Ok(code) => {
+                        let _ = state.stage_close(code, &[]);
+                        state.read_state = ReadState::Close;
+                        return Poll::Ready(Ok(0));
+                    }
+                    Err(e) => {
+                        state.read_state = ReadState::Eof { truncated: true };
+                        return Poll::Ready(Err(e.into()));
+                    }
+                }
+            }
             // handle the read data in user provided buffer
             ReadState::ProcessBuf {
                 mut beg,
                 end,
                 mut processed,
             } => {
-                // parse head, fin is ignored
+                // data in front of an illegal head is returned first,
+                // then the head is stored and rejected by the next read,
+                // the head is kept after a rejection, so it is rejected again
+                let head_beg = beg;
+                macro_rules! reject {
+                    ($e: expr) => {{
+                        let head_end = std::cmp::min(end, head_beg + MAX_HEAD_LEN);
+                        let Some(head_store) =
+                            HeadStore::try_new_with_data(&buf[head_beg..head_end])
+                        else {
+                            state.read_state = ReadState::Eof { truncated: true };
+                            return Poll::Ready(Err($e.into()));
+                        };
+                        state.read_state = ReadState::ReadHead(head_store);
+                        if processed == 0 {
+                            return Poll::Ready(Err($e.into()));
+                        }
+                        return Poll::Ready(Ok(processed));
+                    }};
+                }
+
+                // parse head
+                let decode = if state.strict_length {
+                    FrameHead::decode_strict
+                } else {
+                    FrameHead::decode
+                };
                 let (
                     FrameHead {
+                        fin,
                         opcode,
                         mask,
                         length,
-                        ..
                     },
                     parse_n,
-                ) = match FrameHead::decode(&buf[beg..end]) {
+                ) = match decode(&buf[beg..end]) {
                     Ok(x) => x,
                     Err(ref e) if *e == FrameError::NotEnoughData => {
                         if beg == end {
-                            stream.read_state = ReadState::new();
+                            state.read_state = ReadState::new();
                         } else {
-                            stream.read_state =
-                                ReadState::ReadHead(HeadStore::new_with_data(&buf[beg..end]));
+                            // a partial head is shorter than the store
+                            let Some(head_store) = HeadStore::try_new_with_data(&buf[beg..end])
+                            else {
+                                state.read_state = ReadState::Eof { truncated: true };
+                                return Poll::Ready(Err(FrameError::IllegalData.into()));
+                            };
+                            state.read_state = ReadState::ReadHead(head_store);
                         }
                         return Poll::Ready(Ok(processed));
                     }
-                    Err(e) => return Poll::Ready(Err(e.into())),
+                    Err(e) => reject!(e),
                 };
+
+                // observe an accepted frame
+                macro_rules! accept {
+                    () => {{
+                        count!(state, frames_read, 1);
+                        #[cfg(feature = "frame_hook")]
+                        if let Some(hook) = &mut state.frame_hook {
+                            hook(&FrameHead::new(fin, opcode, mask, length));
+                        }
+                    }};
+                }
+
+                // a server must not accept an unmasked frame,
+                // and a client must not accept a masked frame
+                if state.strict_mask {
+                    match (state.role.mask_key(), mask) {
+                        (Mask::None, Mask::None) => reject!(FrameError::UnmaskedClientFrame),
+                        (Mask::Key(_) | Mask::Skip, Mask::Key(_) | Mask::Skip) => {
+                            reject!(FrameError::MaskedServerFrame)
+                        }
+                        _ => {}
+                    }
+                }
+
+                // a control frame must not be fragmented
+                if state.strict_control && opcode.is_control() && fin == Fin::N {
+                    reject!(FrameError::IllegalData);
+                }
+
                 // point to payload
                 beg += parse_n;
 
@@ -150,13 +421,65 @@ where
                 let data_len = min_len(buf_len, frame_len);
 
                 match opcode {
-                    // text is not allowed
-                    // we never send a ping, so we ignore the pong
-                    OpCode::Text | OpCode::Pong => {
-                        return Poll::Ready(Err(FrameError::UnsupportedOpcode.into()));
+                    // text is not allowed unless accepted
+                    OpCode::Text if !state.accept_text => {
+                        reject!(FrameError::UnsupportedOpcode(opcode.to_flag()));
                     }
-                    // ignore fin flag
-                    OpCode::Binary | OpCode::Continue => {
+                    // an unsolicited pong is allowed, its payload is discarded
+                    OpCode::Pong => {
+                        if frame_len > MAX_CONTROL_LEN as u64 {
+                            reject!(FrameError::IllegalData);
+                        }
+                        accept!();
+                        // an empty pong answers the automatic ping
+                        #[cfg(feature = "async")]
+                        if frame_len == 0 {
+                            state.take_pong();
+                        }
+                        // processed does not increase
+                        beg += data_len;
+                        // need to read more payload
+                        if frame_len > buf_len as u64 {
+                            state.read_state = ReadState::ReadPong {
+                                next: (frame_len as usize - data_len) as u8,
+                            };
+                            return Poll::Ready(Ok(processed));
+                        }
+                        // continue to process
+                        state.read_state = ReadState::ProcessBuf {
+                            beg,
+                            end,
+                            processed,
+                        };
+                    }
+                    OpCode::Text | OpCode::Binary | OpCode::Continue => {
+                        // a continuation frame must follow a non-fin data frame,
+                        // and a new message must not start before the last one ends
+                        if (opcode == OpCode::Continue) != state.read_fragmented {
+                            reject!(FrameError::IllegalData);
+                        }
+
+                        // sum up lengths of a fragmented message,
+                        // reject before reading any payload
+                        let message_len = match opcode {
+                            OpCode::Continue => state.read_message_len.saturating_add(frame_len),
+                            _ => frame_len,
+                        };
+                        if message_len > state.max_message_size {
+                            reject!(FrameError::FrameTooLarge);
+                        }
+                        // update states once the frame is accepted,
+                        // a deferred rejection is checked again
+                        state.read_fragmented = fin == Fin::N;
+                        state.read_opcode = Some(opcode);
+                        state.read_message_len = if fin == Fin::Y { 0 } else { message_len };
+                        // a text message is validated until its last frame
+                        match opcode {
+                            OpCode::Text => state.read_utf8 = Some(Utf8Validator::new()),
+                            OpCode::Binary => state.read_utf8 = None,
+                            _ => {}
+                        }
+                        accept!();
                         if data_len != 0 {
                             // unmask payload data from client
                             if let Mask::Key(key) = mask {
@@ -171,65 +494,103 @@ where
                                 );
                             };
                         }
+                        validate_utf8!(
+                            state,
+                            &buf[processed..processed + data_len],
+                            frame_len <= buf_len as u64
+                        );
                         beg += data_len;
                         processed += data_len;
                         // need to read more payload
                         if frame_len > buf_len as u64 {
-                            stream.read_state = ReadState::ReadData {
+                            state.read_state = ReadState::ReadData {
                                 next: frame_len - data_len as u64,
                                 mask,
+                                phase: (data_len & 3) as u8,
+                                opcode,
                             };
                             return Poll::Ready(Ok(processed));
                         }
                         // continue to process
-                        stream.read_state = ReadState::ProcessBuf {
+                        state.read_state = ReadState::ProcessBuf {
                             beg,
                             end,
                             processed,
                         };
                     }
                     OpCode::Ping => {
-                        // a ping frame must not have extened data
-                        if frame_len > 125 {
-                            return Poll::Ready(Err(FrameError::IllegalData.into()));
+                        // a ping frame must not have extened data,
+                        // reject before storing any payload
+                        if frame_len > MAX_CONTROL_LEN as u64 {
+                            reject!(FrameError::IllegalData);
                         }
+                        accept!();
+                        count!(state, pings_received, 1);
                         if data_len != 0 {
                             // unmask payload data from client
                             if let Mask::Key(key) = mask {
                                 apply_mask4(key, &mut buf[beg..beg + data_len]);
                             }
-                            // save ping data
-                            stream
-                                .heartbeat
-                                .ping_store
-                                .replace_with_data(&buf[beg..beg + data_len]);
+                            // save ping data,
+                            // a ping longer than the store is rejected above
+                            unsafe {
+                                state
+                                    .heartbeat
+                                    .ping_store
+                                    .replace_with_data(&buf[beg..beg + data_len]);
+                            }
                         } else {
                             // no payload
-                            stream.heartbeat.ping_store.reset();
+                            state.heartbeat.ping_store.reset();
                         }
 
-                        // processed does not increase;
+                        // processed does not increase
                         beg += data_len;
 
                         // need to read more payload
                         if frame_len > buf_len as u64 {
-                            stream.heartbeat.is_complete = false;
-                            stream.read_state = ReadState::ReadPing {
-                                next: frame_len as u8 - data_len as u8,
+                            state.heartbeat.is_complete = false;
+                            state.read_state = ReadState::ReadPing {
+                                next: (frame_len as usize - data_len) as u8,
                                 mask,
                             };
                             return Poll::Ready(Ok(processed));
                         }
                         // continue to process
-                        stream.heartbeat.is_complete = true;
-                        stream.read_state = ReadState::ProcessBuf {
+                        state.complete_ping();
+                        state.read_state = ReadState::ProcessBuf {
                             beg,
                             end,
                             processed,
                         };
                     }
                     OpCode::Close => {
-                        stream.read_state = ReadState::Close;
+                        if state.strict_control {
+                            // empty, or a status code followed by a reason
+                            if frame_len == 1 || frame_len > MAX_CONTROL_LEN as u64 {
+                                reject!(FrameError::IllegalData);
+                            }
+                            accept!();
+                            // save the payload, which is validated once complete,
+                            // a close frame longer than the store is rejected above
+                            if let Mask::Key(key) = mask {
+                                apply_mask4(key, &mut buf[beg..beg + data_len]);
+                            }
+                            let close_payload =
+                                unsafe { ClosePayload::new_with_data(&buf[beg..beg + data_len]) };
+                            state.read_close = Some(Box::new(close_payload));
+                            state.read_state = ReadState::ReadClose {
+                                next: (frame_len as usize - data_len) as u8,
+                                mask,
+                            };
+                            // data in front of the close frame is returned first
+                            if processed != 0 {
+                                return Poll::Ready(Ok(processed));
+                            }
+                            continue;
+                        }
+                        accept!();
+                        state.read_state = ReadState::Close;
                         return Poll::Ready(Ok(processed));
                     }
                 }
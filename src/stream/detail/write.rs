@@ -1,25 +1,41 @@
-use std::io::Result;
+use std::io::{Result, ErrorKind};
 use std::io::IoSlice;
 use std::task::{Poll, ready};
 use std::marker::PhantomData;
+use std::ops::Deref;
 
 use super::min_len;
-use super::super::{Stream, RoleHelper};
+use super::super::{State, RoleHelper};
 use super::super::state::{WriteState, HeadStore};
 
 use crate::frame::FrameHead;
-use crate::frame::{Fin, OpCode, PayloadLen};
+use crate::frame::{Fin, OpCode, Mask, PayloadLen};
+use crate::frame::apply_mask4;
+use crate::error::CtrlError;
 
-pub fn write_some<F, IO, Role, Guard>(
-    stream: &mut Stream<IO, Role, Guard>,
+pub fn write_some<F, Role>(state: &mut State<Role>, write: F, buf: &[u8]) -> Poll<Result<usize>>
+where
+    F: FnMut(&[IoSlice]) -> Poll<Result<usize>>,
+    Role: RoleHelper,
+{
+    let opcode = state.write_opcode;
+    write_frame_some(state, write, buf, Fin::Y, opcode)
+}
+
+/// Same as [`write_some`], but a new frame is created with the provided
+/// fin flag and data opcode.
+pub fn write_frame_some<F, Role>(
+    state: &mut State<Role>,
     mut write: F,
     buf: &[u8],
+    fin: Fin,
+    opcode: OpCode,
 ) -> Poll<Result<usize>>
 where
-    F: FnMut(&mut IO, &[IoSlice]) -> Poll<Result<usize>>,
+    F: FnMut(&[IoSlice]) -> Poll<Result<usize>>,
     Role: RoleHelper,
 {
-    match stream.write_state {
+    match state.write_state {
         // always returns 0
         WriteState::WriteZero => Poll::Ready(Ok(0)),
         // create a new frame
@@ -27,26 +43,55 @@ where
             // data frame length depends on provided buffer length
             let frame_len = buf.len();
 
-            if head_store.is_empty() {
+            let new_frame = head_store.is_empty();
+            if new_frame {
+                // an unfinished message only continues with `Continue`
+                if state.write_fragmented && opcode != OpCode::Continue {
+                    return Poll::Ready(Err(CtrlError::MessageInProgress.into()));
+                }
+                // no data frame follows a close frame
+                if state.is_close_started() {
+                    return Poll::Ready(Err(CtrlError::WriteAfterClose.into()));
+                }
+
+                // answer a ping before a new frame, with at most one write,
+                // the frame is written by the next call
+                if ready!(write_pong(state, &mut write))? || state.is_write_zero() {
+                    return Poll::Ready(Ok(0));
+                }
+
                 // build frame head
                 // mask payload(this is unsafe) if unsafe_auto_mask_write is activated
-                WriteFrameHead::<Role>::write_data_frame(&mut head_store, &mut stream.role, buf);
+                WriteFrameHead::<Role>::write_data_frame(
+                    &mut head_store,
+                    &mut state.role,
+                    buf,
+                    fin,
+                    opcode,
+                );
             }
             // frame head(maybe partial) + payload
             let iovec = [IoSlice::new(head_store.read()), IoSlice::new(buf)];
-            let write_n = ready!(write(&mut stream.io, &iovec))?;
+            let write_n = ready!(write(&iovec))?;
             let head_len = head_store.rd_left();
 
             // write zero ?
             if write_n == 0 {
-                stream.write_state = WriteState::WriteZero;
+                state.write_state = WriteState::WriteZero;
                 return Poll::Ready(Ok(0));
             }
 
+            // the frame is sent, at least partially
+            count!(state, bytes_written, write_n);
+            if new_frame {
+                count!(state, frames_written, 1);
+                state.write_fragmented = fin == Fin::N;
+            }
+
             // frame head is not written completely
             if write_n < head_len {
                 head_store.advance_rd_pos(write_n);
-                stream.write_state = WriteState::WriteHead(head_store);
+                state.write_state = WriteState::WriteHead(head_store);
                 return Poll::Ready(Ok(0));
             }
 
@@ -55,9 +100,9 @@ where
 
             // all data written ?
             if write_n == frame_len {
-                stream.write_state = WriteState::new();
+                state.write_state = WriteState::new();
             } else {
-                stream.write_state = WriteState::WriteData((frame_len - write_n) as u64);
+                state.write_state = WriteState::WriteData((frame_len - write_n) as u64);
             }
 
             Poll::Ready(Ok(write_n))
@@ -65,38 +110,312 @@ where
         // continue to write to the same frame
         WriteState::WriteData(next) => {
             let len = min_len(buf.len(), next);
-            let write_n = ready!(write(&mut stream.io, &[IoSlice::new(&buf[..len])]))?;
+            let write_n = ready!(write(&[IoSlice::new(&buf[..len])]))?;
             // write zero ?
             if write_n == 0 {
-                stream.write_state = WriteState::WriteZero;
+                state.write_state = WriteState::WriteZero;
                 return Poll::Ready(Ok(0));
             }
+            count!(state, bytes_written, write_n);
             // all data written ?
             if next == write_n as u64 {
-                stream.write_state = WriteState::new()
+                state.write_state = WriteState::new()
             } else {
-                stream.write_state = WriteState::WriteData(next - write_n as u64)
+                state.write_state = WriteState::WriteData(next - write_n as u64)
             }
             Poll::Ready(Ok(write_n))
         }
     }
 }
 
+/// Write the rest of a partially written frame head,
+/// which is the only data held by the state.
+pub fn flush_head<F, Role>(state: &mut State<Role>, mut write: F) -> Poll<Result<()>>
+where
+    F: FnMut(&[IoSlice]) -> Poll<Result<usize>>,
+    Role: RoleHelper,
+{
+    while let WriteState::WriteHead(mut head_store) = state.write_state {
+        // nothing staged
+        if head_store.rd_left() == 0 {
+            break;
+        }
+        let write_n = ready!(write(&[IoSlice::new(head_store.read())]))?;
+        // write zero ?
+        if write_n == 0 {
+            state.write_state = WriteState::WriteZero;
+            return Poll::Ready(Err(ErrorKind::WriteZero.into()));
+        }
+        count!(state, bytes_written, write_n);
+        // payload follows the head in the next write
+        head_store.advance_rd_pos(write_n);
+        state.write_state = WriteState::WriteHead(head_store);
+        // an empty frame is finished with its head
+        if head_store.rd_left() == 0 && head_store.as_ref()[1] & 0x7f == 0 {
+            state.write_state = WriteState::new();
+        }
+    }
+
+    // answer pings if no frame is being written, then close
+    ready!(flush_pong(state, &mut write))?;
+    if state.is_write_zero() {
+        return Poll::Ready(Err(ErrorKind::WriteZero.into()));
+    }
+    ready!(flush_close(state, &mut write))?;
+    if state.is_write_zero() {
+        return Poll::Ready(Err(ErrorKind::WriteZero.into()));
+    }
+    Poll::Ready(Ok(()))
+}
+
+/// Write a staged close frame, only when neither a data frame nor a pong
+/// is being written.
+///
+/// Return `Ok(())` and set `WriteZero` if the IO writes zero.
+pub fn flush_close<F, Role>(state: &mut State<Role>, write: &mut F) -> Poll<Result<()>>
+where
+    F: FnMut(&[IoSlice]) -> Poll<Result<usize>>,
+    Role: RoleHelper,
+{
+    let Some(close_store) = &mut state.close_store else {
+        return Poll::Ready(Ok(()));
+    };
+    if !matches!(state.write_state, WriteState::WriteHead(head) if head.is_empty()) {
+        return Poll::Ready(Ok(()));
+    }
+    if matches!(&state.auto_pong, Some(auto_pong) if auto_pong.pong_store.rd_left() != 0) {
+        return Poll::Ready(Ok(()));
+    }
+
+    while close_store.rd_left() != 0 {
+        let write_n = ready!(write(&[IoSlice::new(close_store.read())]))?;
+        // write zero ?
+        if write_n == 0 {
+            state.write_state = WriteState::WriteZero;
+            return Poll::Ready(Ok(()));
+        }
+        count!(state, bytes_written, write_n);
+        if close_store.rd_pos() == 0 {
+            count!(state, frames_written, 1);
+        }
+        close_store.advance_rd_pos(write_n);
+    }
+    Poll::Ready(Ok(()))
+}
+
+/// Echo each pending ping with a pong if auto pong is enabled,
+/// only when no data frame is being written.
+///
+/// Return `Ok(())` and set `WriteZero` if the IO writes zero.
+fn flush_pong<F, Role>(state: &mut State<Role>, write: &mut F) -> Poll<Result<()>>
+where
+    F: FnMut(&[IoSlice]) -> Poll<Result<usize>>,
+    Role: RoleHelper,
+{
+    while ready!(write_pong(state, write))? {}
+    Poll::Ready(Ok(()))
+}
+
+/// Write the current or the oldest pending pong with a single write,
+/// only when no data frame is being written.
+///
+/// Return `Ok(true)` if some bytes are written, or `Ok(false)` if nothing
+/// is pending. Return `Ok(false)` and set `WriteZero` if the IO writes zero.
+fn write_pong<F, Role>(state: &mut State<Role>, write: &mut F) -> Poll<Result<bool>>
+where
+    F: FnMut(&[IoSlice]) -> Poll<Result<usize>>,
+    Role: RoleHelper,
+{
+    // nothing follows a close frame
+    if state.is_close_started() {
+        return Poll::Ready(Ok(false));
+    }
+    let Some(auto_pong) = &mut state.auto_pong else {
+        return Poll::Ready(Ok(false));
+    };
+    if !matches!(state.write_state, WriteState::WriteHead(head) if head.is_empty()) {
+        return Poll::Ready(Ok(false));
+    }
+
+    // stage the oldest pending ping
+    if auto_pong.pong_store.rd_left() == 0 {
+        if auto_pong.pending_len == 0 {
+            return Poll::Ready(Ok(false));
+        }
+        let data = auto_pong.pending[0].read();
+        let mask = state.role.mask_key();
+        let head = FrameHead::new(
+            Fin::Y,
+            OpCode::Pong,
+            mask,
+            PayloadLen::from_num(data.len() as u64),
+        );
+
+        auto_pong.pong_store.reset();
+        let head_len = head.encode(auto_pong.pong_store.write()).unwrap();
+        auto_pong.pong_store.write()[head_len..head_len + data.len()].copy_from_slice(data);
+        if let Mask::Key(key) = mask {
+            apply_mask4(
+                key,
+                &mut auto_pong.pong_store.write()[head_len..head_len + data.len()],
+            );
+        }
+        auto_pong.pong_store.advance_wr_pos(head_len + data.len());
+        auto_pong.pop();
+    }
+
+    let write_n = ready!(write(&[IoSlice::new(auto_pong.pong_store.read())]))?;
+    // write zero ?
+    if write_n == 0 {
+        state.write_state = WriteState::WriteZero;
+        return Poll::Ready(Ok(false));
+    }
+    count!(state, bytes_written, write_n);
+    if auto_pong.pong_store.rd_pos() == 0 {
+        count!(state, frames_written, 1);
+    }
+    auto_pong.pong_store.advance_rd_pos(write_n);
+    Poll::Ready(Ok(true))
+}
+
+/// Max number of frames written by a vectored write.
+pub const MAX_WRITE_FRAMES: usize = 8;
+
+pub fn write_frames_some<F, Role, B>(
+    state: &mut State<Role>,
+    mut write: F,
+    bufs: &[B],
+) -> Poll<Result<usize>>
+where
+    F: FnMut(&[IoSlice]) -> Poll<Result<usize>>,
+    Role: RoleHelper,
+    B: Deref<Target = [u8]>,
+{
+    let mut bufs = bufs.iter().map(|buf| &**buf).filter(|buf| !buf.is_empty());
+
+    // answer a ping before new frames, see write_frame_some
+    if ready!(write_pong(state, &mut write))? || state.is_write_zero() {
+        return Poll::Ready(Ok(0));
+    }
+
+    // continue to write an unfinished frame
+    match state.write_state {
+        // an unfinished message only continues with `Continue`
+        WriteState::WriteHead(head_store) if head_store.is_empty() && state.write_fragmented => {
+            return Poll::Ready(Err(CtrlError::MessageInProgress.into()));
+        }
+        // no data frame follows a close frame
+        WriteState::WriteHead(head_store) if head_store.is_empty() && state.is_close_started() => {
+            return Poll::Ready(Err(CtrlError::WriteAfterClose.into()));
+        }
+        WriteState::WriteHead(head_store) if head_store.is_empty() => {}
+        _ => {
+            return match bufs.next() {
+                Some(buf) => write_some(state, write, buf),
+                None => Poll::Ready(Ok(0)),
+            }
+        }
+    }
+
+    // payload is masked in place once its head is built,
+    // build only one frame so that no unwritten payload is masked
+    let max_frames = if WriteFrameHead::<Role>::MASK_IN_PLACE {
+        1
+    } else {
+        MAX_WRITE_FRAMES
+    };
+
+    let mut frames: [(HeadStore, &[u8]); MAX_WRITE_FRAMES] =
+        [(HeadStore::new(), &[]); MAX_WRITE_FRAMES];
+    let mut count = 0;
+    for buf in bufs.take(max_frames) {
+        let (head_store, payload) = &mut frames[count];
+        WriteFrameHead::<Role>::write_data_frame(
+            head_store,
+            &mut state.role,
+            buf,
+            Fin::Y,
+            state.write_opcode,
+        );
+        *payload = buf;
+        count += 1;
+    }
+
+    if count == 0 {
+        return Poll::Ready(Ok(0));
+    }
+
+    // frame head + payload, one after another
+    let mut iovec = [IoSlice::new(&[]); MAX_WRITE_FRAMES * 2];
+    for (i, (head_store, payload)) in frames[..count].iter().enumerate() {
+        iovec[i * 2] = IoSlice::new(head_store.read());
+        iovec[i * 2 + 1] = IoSlice::new(payload);
+    }
+    let mut write_n = ready!(write(&iovec[..count * 2]))?;
+
+    // write zero ?
+    if write_n == 0 {
+        state.write_state = WriteState::WriteZero;
+        return Poll::Ready(Ok(0));
+    }
+
+    count!(state, bytes_written, write_n);
+
+    let mut total = 0;
+    for (mut head_store, payload) in frames.into_iter().take(count) {
+        let head_len = head_store.rd_left();
+
+        // frame head is not written completely
+        if write_n < head_len {
+            if write_n != 0 {
+                count!(state, frames_written, 1);
+                head_store.advance_rd_pos(write_n);
+                state.write_state = WriteState::WriteHead(head_store);
+            }
+            break;
+        }
+        write_n -= head_len;
+        count!(state, frames_written, 1);
+
+        // payload is not written completely
+        if write_n < payload.len() {
+            state.write_state = WriteState::WriteData((payload.len() - write_n) as u64);
+            total += write_n;
+            break;
+        }
+        write_n -= payload.len();
+        total += payload.len();
+    }
+
+    Poll::Ready(Ok(total))
+}
+
 struct WriteFrameHead<Role: RoleHelper> {
     _marker: PhantomData<Role>,
 }
 
 trait WriteFrameHeadTrait<R> {
-    fn write_data_frame(_: &mut HeadStore, _: &mut R, _: &[u8]) {}
+    /// Whether payload is masked in place.
+    const MASK_IN_PLACE: bool = false;
+
+    fn write_data_frame(_: &mut HeadStore, _: &mut R, _: &[u8], _: Fin, _: OpCode) {}
 }
 
 // use default impl
 impl<Role: RoleHelper> WriteFrameHeadTrait<Role> for WriteFrameHead<Role> {
+    default const MASK_IN_PLACE: bool = false;
+
     #[inline]
-    default fn write_data_frame(store: &mut HeadStore, role: &mut Role, buf: &[u8]) {
+    default fn write_data_frame(
+        store: &mut HeadStore,
+        role: &mut Role,
+        buf: &[u8],
+        fin: Fin,
+        opcode: OpCode,
+    ) {
         let head = FrameHead::new(
-            Fin::Y,
-            OpCode::Binary,
+            fin,
+            opcode,
             role.mask_key(),
             PayloadLen::from_num(buf.len() as u64),
         );
@@ -110,16 +429,24 @@ cfg_if::cfg_if! {
     if #[cfg(feature = "unsafe_auto_mask_write")] {
         use crate::role::AutoMaskClientRole;
         use crate::bleed::const_cast;
-        use crate::frame::{Mask, new_mask_key, apply_mask4};
+        use crate::frame::new_mask_key;
     }
 }
 
 // specialize
 #[cfg(feature = "unsafe_auto_mask_write")]
 impl<Role: AutoMaskClientRole> WriteFrameHeadTrait<Role> for WriteFrameHead<Role> {
+    const MASK_IN_PLACE: bool = true;
+
     #[inline]
-    fn write_data_frame(store: &mut HeadStore, role: &mut Role, buf: &[u8]) {
-        let key = if Role::UPDATE_MASK_KEY {
+    fn write_data_frame(
+        store: &mut HeadStore,
+        role: &mut Role,
+        buf: &[u8],
+        fin: Fin,
+        opcode: OpCode,
+    ) {
+        let key = if role.update_mask_key() {
             let key = new_mask_key();
             role.set_mask_key(key);
             key
@@ -135,8 +462,8 @@ impl<Role: AutoMaskClientRole> WriteFrameHeadTrait<Role> for WriteFrameHead<Role
 
         // below is the same of default impl
         let head = FrameHead::new(
-            Fin::Y,
-            OpCode::Binary,
+            fin,
+            opcode,
             Mask::Key(key),
             PayloadLen::from_num(buf.len() as u64),
         );
@@ -150,18 +477,17 @@ impl<Role: AutoMaskClientRole> WriteFrameHeadTrait<Role> for WriteFrameHead<Role
 mod test {
     use super::*;
     use crate::bleed::Store;
-    use crate::frame::mask::*;
     use crate::role::*;
 
     fn auto_mask<R: RoleHelper>(role: &mut R, buf: &[u8]) {
         let mut store = Store::new();
-        WriteFrameHead::<R>::write_data_frame(&mut store, role, buf)
+        WriteFrameHead::<R>::write_data_frame(&mut store, role, buf, Fin::Y, OpCode::Binary)
     }
 
     #[test]
     fn auto_mask_active() {
         for i in 0..4096 {
-            let mut buf: Vec<u8> = std::iter::repeat(rand::random::<u8>()).take(i).collect();
+            let mut buf: Vec<u8> = std::iter::repeat_n(rand::random::<u8>(), i).collect();
             let buf2 = buf.clone();
             assert_eq!(buf.len(), i);
 
@@ -179,7 +505,7 @@ mod test {
     #[test]
     fn auto_mask_active2() {
         for i in 0..4096 {
-            let mut buf: Vec<u8> = std::iter::repeat(rand::random::<u8>()).take(i).collect();
+            let mut buf: Vec<u8> = std::iter::repeat_n(rand::random::<u8>(), i).collect();
             let buf2 = buf.clone();
             assert_eq!(buf.len(), i);
 
@@ -196,10 +522,39 @@ mod test {
         }
     }
 
+    #[test]
+    fn auto_mask_rotate() {
+        fn rotate<const N: u32>() {
+            let mut role = RotateMaskClient::<N>::new();
+            let mut keys = Vec::new();
+
+            for _ in 0..4 * N {
+                let mut buf = vec![0_u8; 16];
+                auto_mask(&mut role, &buf);
+
+                // payload is masked with the current key
+                let key = role.mask_key().to_key();
+                apply_mask4(key, &mut buf);
+                assert!(buf.iter().all(|b| *b == 0));
+                keys.push(key);
+            }
+
+            for chunk in keys.chunks(N as usize) {
+                assert!(chunk.iter().all(|key| *key == chunk[0]));
+            }
+            // different keys with extremely high probability
+            assert!(keys.windows(N as usize + 1).all(|w| w[0] != w[N as usize]));
+        }
+
+        rotate::<1>();
+        rotate::<2>();
+        rotate::<7>();
+    }
+
     #[test]
     fn auto_mask_inactive() {
         for i in 0..4096 {
-            let buf: Vec<u8> = std::iter::repeat(rand::random::<u8>()).take(i).collect();
+            let buf: Vec<u8> = std::iter::repeat_n(rand::random::<u8>(), i).collect();
             let buf2 = buf.clone();
             assert_eq!(buf.len(), i);
 
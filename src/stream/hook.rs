@@ -0,0 +1,76 @@
+use super::Stream;
+use crate::frame::FrameHead;
+
+/// Callback invoked for each incoming frame head.
+pub(super) type FrameHook = Box<dyn FnMut(&FrameHead) + Send>;
+
+impl<IO, Role, Guard> Stream<IO, Role, Guard> {
+    /// Register a callback invoked for each incoming frame head,
+    /// once the head is decoded and accepted, before its payload is read.
+    ///
+    /// A rejected head (which leads to an error) is not observed.
+    /// This replaces the previous callback.
+    pub fn on_frame<F>(&mut self, hook: F)
+    where
+        F: FnMut(&FrameHead) + Send + 'static,
+    {
+        self.state.frame_hook = Some(Box::new(hook));
+    }
+
+    /// Remove the callback.
+    #[inline]
+    pub fn clear_frame_hook(&mut self) { self.state.frame_hook = None; }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::test::*;
+    use crate::frame::{Fin, Mask, OpCode, PayloadLen};
+    use crate::role::*;
+    use std::io::Read;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn frame_hook() {
+        let key = [1, 2, 3, 4];
+        let mut input = Vec::new();
+        input.extend(make_head_with_fin(
+            Fin::N,
+            OpCode::Binary,
+            Mask::Key(key),
+            0,
+        ));
+        input.extend(make_head(OpCode::Ping, Mask::Key(key), 0));
+        input.extend(make_masked_frame(OpCode::Continue, key, 200).0);
+        input.extend(make_head(OpCode::Close, Mask::Key(key), 0));
+
+        let heads = Arc::new(Mutex::new(Vec::new()));
+        let heads2 = heads.clone();
+        let mut stream = Stream::new(input.as_slice(), Server).guard();
+        stream.on_frame(move |head| heads2.lock().unwrap().push(*head));
+
+        let mut buf = Vec::new();
+        stream.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf.len(), 200);
+
+        let head = |fin, opcode, len| {
+            FrameHead::new(fin, opcode, Mask::Key(key), PayloadLen::from_num(len))
+        };
+        assert_eq!(
+            *heads.lock().unwrap(),
+            [
+                head(Fin::N, OpCode::Binary, 0),
+                head(Fin::Y, OpCode::Ping, 0),
+                head(Fin::Y, OpCode::Continue, 200),
+                head(Fin::Y, OpCode::Close, 0),
+            ]
+        );
+
+        // a rejected head is not observed
+        let input = make_head(OpCode::Text, Mask::Key(key), 0);
+        let mut stream = Stream::new(input.as_slice(), Server).guard();
+        stream.on_frame(|_| unreachable!());
+        assert!(stream.read_to_end(&mut buf).is_err());
+    }
+}
@@ -0,0 +1,261 @@
+use std::io::{Result, ErrorKind};
+use std::pin::Pin;
+use std::task::{Poll, Context};
+use std::time::Duration;
+
+use tokio::io::AsyncWrite;
+use tokio::time::{Interval, Instant, MissedTickBehavior, interval_at};
+
+use super::{Stream, State};
+use super::state::HeadStore;
+
+use crate::frame::{FrameHead, Fin, OpCode, PayloadLen};
+use crate::role::RoleHelper;
+
+/// Idle timer, and an empty ping staged for writing.
+#[derive(Debug)]
+pub(super) struct KeepAlive {
+    period: Duration,
+    // created on the first poll, inside a runtime
+    interval: Option<Interval>,
+    last_active: Instant,
+    wait_pong: bool,
+    ping: HeadStore,
+}
+
+/// Restart the timer, a staged ping is not copied.
+impl Clone for KeepAlive {
+    fn clone(&self) -> Self { Self::new(self.period) }
+}
+
+impl KeepAlive {
+    #[inline]
+    fn new(period: Duration) -> Self {
+        Self {
+            period,
+            interval: None,
+            last_active: Instant::now(),
+            wait_pong: false,
+            ping: HeadStore::new(),
+        }
+    }
+}
+
+/// Write the staged ping, only if IO is writable.
+trait PollSendPing {
+    /// Return `None` if IO is not writable.
+    fn poll_send_ping(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Option<Poll<Result<usize>>>;
+}
+
+impl<IO> PollSendPing for IO {
+    default fn poll_send_ping(
+        self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+        _: &[u8],
+    ) -> Option<Poll<Result<usize>>> {
+        None
+    }
+}
+
+impl<IO: AsyncWrite> PollSendPing for IO {
+    fn poll_send_ping(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Option<Poll<Result<usize>>> {
+        Some(self.poll_write(cx, buf))
+    }
+}
+
+impl<IO, Role, Guard> Stream<IO, Role, Guard> {
+    /// Send a ping automatically once the stream is idle
+    /// (nothing is read or written) for the given period, which is checked
+    /// in the async poll paths, see [`tokio::time::Interval`].
+    ///
+    /// If no pong returns before the next tick, the read fails with
+    /// [`ErrorKind::TimedOut`].
+    ///
+    /// The ping is empty, and an empty pong answers it while it is pending.
+    /// It is only sent if `IO` implements [`AsyncWrite`] and no data frame
+    /// is being written.
+    ///
+    /// This is disabled by default.
+    pub fn set_ping_interval(&mut self, period: Duration) {
+        self.state.keepalive = Some(Box::new(KeepAlive::new(period)));
+    }
+
+    /// Disable the automatic ping.
+    #[inline]
+    pub fn clear_ping_interval(&mut self) { self.state.keepalive = None; }
+
+    /// Check if an automatic ping is waiting for its pong.
+    #[inline]
+    pub fn is_wait_pong(&self) -> bool {
+        matches!(&self.state.keepalive, Some(keepalive) if keepalive.wait_pong)
+    }
+}
+
+impl<Role> State<Role> {
+    /// Mark the stream as active.
+    #[inline]
+    pub(super) fn touch(&mut self) {
+        if let Some(keepalive) = &mut self.keepalive {
+            keepalive.last_active = Instant::now();
+        }
+    }
+
+    /// Take an empty pong if an automatic ping is pending.
+    #[inline]
+    pub(super) fn take_pong(&mut self) -> bool {
+        match &mut self.keepalive {
+            Some(keepalive) if keepalive.wait_pong => {
+                keepalive.wait_pong = false;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Write the rest of a staged ping, it must be finished
+    /// before a new frame is written.
+    pub(super) fn poll_flush_ping<IO>(
+        &mut self,
+        mut io: Pin<&mut IO>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<()>> {
+        let Some(keepalive) = &mut self.keepalive else {
+            return Poll::Ready(Ok(()));
+        };
+
+        while keepalive.ping.rd_left() != 0 {
+            let write_n = match io.as_mut().poll_send_ping(cx, keepalive.ping.read()) {
+                Some(Poll::Ready(x)) => x?,
+                Some(Poll::Pending) => return Poll::Pending,
+                // not writable, drop the ping
+                None => {
+                    keepalive.ping.reset();
+                    keepalive.wait_pong = false;
+                    break;
+                }
+            };
+            if write_n == 0 {
+                return Poll::Ready(Err(ErrorKind::WriteZero.into()));
+            }
+            count!(self, bytes_written, write_n);
+            if keepalive.ping.rd_pos() == 0 {
+                count!(self, frames_written, 1);
+            }
+            keepalive.ping.advance_rd_pos(write_n);
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<Role: RoleHelper> State<Role> {
+    /// Drive the idle timer, stage a ping or fail with a timeout,
+    /// never pending.
+    pub(super) fn poll_keepalive<IO>(
+        &mut self,
+        io: Pin<&mut IO>,
+        cx: &mut Context<'_>,
+    ) -> Result<()> {
+        let mask = self.role.mask_key();
+        // do not break an incomplete frame
+        let is_boundary = self.is_write_boundary();
+        let Some(keepalive) = &mut self.keepalive else {
+            return Ok(());
+        };
+
+        let period = keepalive.period;
+        let interval = keepalive.interval.get_or_insert_with(|| {
+            let mut interval = interval_at(Instant::now() + period, period);
+            interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            interval
+        });
+
+        let mut send_ping = false;
+        while interval.poll_tick(cx).is_ready() {
+            if keepalive.wait_pong {
+                return Err(ErrorKind::TimedOut.into());
+            }
+
+            let is_idle = keepalive.last_active.elapsed() >= period;
+            if is_idle && is_boundary && keepalive.ping.rd_left() == 0 {
+                let head = FrameHead::new(Fin::Y, OpCode::Ping, mask, PayloadLen::from_num(0));
+                keepalive.ping.reset();
+                let n = head.encode(keepalive.ping.write()).unwrap();
+                keepalive.ping.advance_wr_pos(n);
+                keepalive.wait_pong = true;
+                send_ping = true;
+            }
+        }
+
+        if send_ping {
+            // a pending ping is resumed by the next poll
+            if let Poll::Ready(Err(e)) = self.poll_flush_ping(io, cx) {
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::test::*;
+    use crate::frame::Mask;
+    use crate::role::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test(start_paused = true)]
+    async fn keepalive_ping() {
+        async fn ping<R: RoleHelper + Unpin>() {
+            let (local, mut remote) = tokio::io::duplex(0x100);
+            let mut stream = Stream::new(local, R::new());
+            stream.set_ping_interval(Duration::from_secs(10));
+
+            let mut buf = [0_u8; 16];
+            let expect = make_head(OpCode::Ping, R::new().mask_key(), 0);
+
+            // idle, send a ping, then answer it
+            let read = tokio::time::timeout(Duration::from_secs(15), stream.read(&mut buf));
+            assert!(read.await.is_err());
+            assert!(stream.is_wait_pong());
+            let n = remote.read(&mut buf).await.unwrap();
+            assert_eq!(&buf[..n], &expect);
+
+            remote
+                .write_all(&make_head(OpCode::Pong, Mask::None, 0))
+                .await
+                .unwrap();
+            let read = tokio::time::timeout(Duration::from_secs(1), stream.read(&mut buf));
+            let _ = read.await;
+            assert!(!stream.is_wait_pong());
+
+            // active, no ping
+            let (frame, data) = make_frame::<R>(OpCode::Binary, 1);
+            for _ in 0..3 {
+                tokio::time::sleep(Duration::from_secs(8)).await;
+                stream.write_all(&data).await.unwrap();
+                let read = tokio::time::timeout(Duration::from_secs(1), stream.read(&mut buf));
+                assert!(read.await.is_err());
+                assert!(!stream.is_wait_pong());
+            }
+            let mut written = vec![0_u8; frame.len() * 3];
+            remote.read_exact(&mut written).await.unwrap();
+            assert_eq!(written, frame.repeat(3));
+
+            // idle, send a ping, no pong
+            let e = stream.read(&mut buf).await.unwrap_err();
+            assert_eq!(e.kind(), ErrorKind::TimedOut);
+        }
+
+        ping::<Client>().await;
+        ping::<Server>().await;
+    }
+}
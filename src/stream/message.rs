@@ -0,0 +1,265 @@
+use std::io::{Write, Result, ErrorKind};
+use std::task::Poll;
+
+use super::{Stream, RoleHelper, Direct};
+use super::state::WriteState;
+use super::detail::{write_frame_some, flush_head};
+
+use crate::frame::{Fin, OpCode};
+use crate::error::CtrlError;
+
+/// Write a message as fragments, created by [`Stream::start_message`].
+///
+/// The first fragment is sent with the opcode of the message,
+/// and the following ones are sent as `Continue`.
+/// The stream is borrowed until the message is finished,
+/// so that no other frame could be interleaved.
+///
+/// Each [`write`](Write::write) creates a fragment (or continues an unfinished one),
+/// and [`finish`](Self::finish) ends the message with an empty fragment.
+/// A message dropped without being finished is left open, where a new frame
+/// other than `Continue` fails with [`CtrlError::MessageInProgress`], until it is
+/// ended by [`write_fragment`](Stream::write_fragment) with `fin` set.
+#[derive(Debug)]
+pub struct MessageWriter<'a, IO, Role, Guard = Direct> {
+    stream: &'a mut Stream<IO, Role, Guard>,
+    opcode: OpCode,
+}
+
+impl<IO: Write, Role: RoleHelper, Guard> Stream<IO, Role, Guard> {
+    /// Start to write a new message.
+    ///
+    /// Only `Text` or `Binary` is allowed, otherwise it fails with
+    /// [`CtrlError::NotDataOpcode`].
+    /// If another message or frame is not finished,
+    /// it fails with [`CtrlError::MessageInProgress`].
+    pub fn start_message(&mut self, opcode: OpCode) -> Result<MessageWriter<'_, IO, Role, Guard>> {
+        if !matches!(opcode, OpCode::Text | OpCode::Binary) {
+            return Err(CtrlError::NotDataOpcode.into());
+        }
+
+        let is_new_frame =
+            matches!(&self.state.write_state, WriteState::WriteHead(head) if head.is_empty());
+        if self.state.write_fragmented || !is_new_frame {
+            return Err(CtrlError::MessageInProgress.into());
+        }
+
+        Ok(MessageWriter {
+            stream: self,
+            opcode,
+        })
+    }
+}
+
+impl<'a, IO: Write, Role: RoleHelper, Guard> MessageWriter<'a, IO, Role, Guard> {
+    /// Opcode of the message.
+    #[inline]
+    pub const fn opcode(&self) -> OpCode { self.opcode }
+
+    #[inline]
+    fn frame_opcode(&self) -> OpCode {
+        if self.stream.state.write_fragmented {
+            OpCode::Continue
+        } else {
+            self.opcode
+        }
+    }
+
+    /// Send the last fragment, then flush the underlying IO source.
+    ///
+    /// Data passed to [`write`](Write::write) must be completely written,
+    /// otherwise it fails with [`CtrlError::IncompleteFrame`].
+    /// If nothing is written, the message is sent as a single empty frame.
+    pub fn finish(self) -> Result<()> {
+        let opcode = self.frame_opcode();
+        let stream = self.stream;
+        match flush_head(&mut stream.state, |iovec| {
+            stream.io.write_vectored(iovec).into()
+        }) {
+            Poll::Ready(x) => x?,
+            Poll::Pending => unreachable!(),
+        };
+
+        if !matches!(&stream.state.write_state, WriteState::WriteHead(head) if head.is_empty()) {
+            return Err(CtrlError::IncompleteFrame.into());
+        }
+
+        match write_frame_some(
+            &mut stream.state,
+            |iovec| stream.io.write_vectored(iovec).into(),
+            &[],
+            Fin::Y,
+            opcode,
+        ) {
+            Poll::Ready(x) => x?,
+            Poll::Pending => unreachable!(),
+        };
+        if stream.is_write_zero() {
+            return Err(ErrorKind::WriteZero.into());
+        }
+
+        // the head may be partially written
+        match flush_head(&mut stream.state, |iovec| {
+            stream.io.write_vectored(iovec).into()
+        }) {
+            Poll::Ready(x) => x?,
+            Poll::Pending => unreachable!(),
+        };
+        stream.io.flush()
+    }
+}
+
+impl<'a, IO: Write, Role: RoleHelper, Guard> Write for MessageWriter<'a, IO, Role, Guard> {
+    /// Write some data as a fragment,
+    /// continue to write if frame head is not completely written.
+    ///
+    /// An empty buffer is ignored.
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        loop {
+            let opcode = self.frame_opcode();
+            match write_frame_some(
+                &mut self.stream.state,
+                |iovec| self.stream.io.write_vectored(iovec).into(),
+                buf,
+                Fin::N,
+                opcode,
+            ) {
+                Poll::Ready(Ok(0)) if self.stream.is_write_zero() => {
+                    return Err(ErrorKind::WriteZero.into())
+                }
+                Poll::Ready(Ok(0)) => continue,
+                Poll::Ready(x) => return x,
+                Poll::Pending => unreachable!(),
+            }
+        }
+    }
+
+    /// Write the rest of a partially written frame head (if any),
+    /// then flush the underlying IO source.
+    fn flush(&mut self) -> Result<()> {
+        match flush_head(&mut self.stream.state, |iovec| {
+            self.stream.io.write_vectored(iovec).into()
+        }) {
+            Poll::Ready(x) => x?,
+            Poll::Pending => unreachable!(),
+        };
+        self.stream.io.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::test::*;
+    use crate::frame::Mask;
+    use crate::role::*;
+
+    fn is_ctrl_error(e: std::io::Error, expect: CtrlError) -> bool {
+        let e = e.into_inner().unwrap();
+        matches!(
+            e.downcast_ref::<crate::error::Error>(),
+            Some(crate::error::Error::Ctrl(e)) if *e == expect
+        )
+    }
+
+    #[test]
+    fn write_message_to_stream() {
+        fn write<R: RoleHelper>(opcode: OpCode, limit: usize) {
+            let fragments: [&[u8]; 3] = [b"hello", b"", b" world"];
+
+            let io = LimitReadWriter {
+                buf: Vec::new(),
+                rlimit: 0,
+                wlimit: limit,
+                cursor: 0,
+            };
+            let mut stream = Stream::new(io, R::new());
+
+            // send a message, then an empty one
+            let mut message = stream.start_message(opcode).unwrap();
+            for data in fragments {
+                message.write_all(data).unwrap();
+            }
+            message.finish().unwrap();
+            assert!(!stream.is_write_fragmented());
+            stream.start_message(opcode).unwrap().finish().unwrap();
+
+            let mask = R::new().mask_key();
+            let mut frames = Vec::new();
+            frames.extend(make_head_with_fin(Fin::N, opcode, mask, 5));
+            frames.extend_from_slice(b"hello");
+            frames.extend(make_head_with_fin(Fin::N, OpCode::Continue, mask, 6));
+            frames.extend_from_slice(b" world");
+            frames.extend(make_head_with_fin(Fin::Y, OpCode::Continue, mask, 0));
+            frames.extend(make_head_with_fin(Fin::Y, opcode, mask, 0));
+            assert_eq!(stream.as_ref().buf, frames);
+        }
+
+        for opcode in [OpCode::Text, OpCode::Binary] {
+            for limit in [1, 2, 3, 100] {
+                write::<Client>(opcode, limit);
+                write::<Server>(opcode, limit);
+            }
+        }
+    }
+
+    #[test]
+    fn write_message_error() {
+        let mut stream = Stream::new(Vec::new(), Server);
+
+        for opcode in [OpCode::Continue, OpCode::Close, OpCode::Ping, OpCode::Pong] {
+            let e = stream.start_message(opcode).map(drop).unwrap_err();
+            assert!(is_ctrl_error(e, CtrlError::NotDataOpcode));
+        }
+
+        // left open
+        stream
+            .start_message(OpCode::Binary)
+            .unwrap()
+            .write_all(b"hello")
+            .unwrap();
+        let e = stream.start_message(OpCode::Binary).map(drop).unwrap_err();
+        assert!(is_ctrl_error(e, CtrlError::MessageInProgress));
+
+        // no new message is interleaved
+        let e = stream.write(b"world").unwrap_err();
+        assert!(is_ctrl_error(e, CtrlError::MessageInProgress));
+        let e = stream.write_frames(&[b"world"]).unwrap_err();
+        assert!(is_ctrl_error(e, CtrlError::MessageInProgress));
+        let e = stream
+            .write_with_opcode(OpCode::Text, b"world")
+            .unwrap_err();
+        assert!(is_ctrl_error(e, CtrlError::MessageInProgress));
+
+        // end it, then write a new one
+        assert_eq!(stream.write_fragment(b"!", true).unwrap(), 1);
+        assert_eq!(stream.write(b"world").unwrap(), 5);
+        let mut frames = Vec::new();
+        frames.extend(make_head_with_fin(Fin::N, OpCode::Binary, Mask::None, 5));
+        frames.extend_from_slice(b"hello");
+        frames.extend(make_head_with_fin(Fin::Y, OpCode::Continue, Mask::None, 1));
+        frames.extend_from_slice(b"!");
+        frames.extend(make_head_with_fin(Fin::Y, OpCode::Binary, Mask::None, 5));
+        frames.extend_from_slice(b"world");
+        assert_eq!(stream.as_ref(), &frames);
+
+        // a frame is partially written
+        let io = LimitReadWriter {
+            buf: Vec::new(),
+            rlimit: 0,
+            wlimit: 4,
+            cursor: 0,
+        };
+        let mut stream = Stream::new(io, Server);
+        let mut message = stream.start_message(OpCode::Binary).unwrap();
+        assert!(message.write(b"hello").unwrap() < 5);
+        let e = message.finish().unwrap_err();
+        assert!(is_ctrl_error(e, CtrlError::IncompleteFrame));
+        let e = stream.start_message(OpCode::Binary).map(drop).unwrap_err();
+        assert!(is_ctrl_error(e, CtrlError::MessageInProgress));
+    }
+}
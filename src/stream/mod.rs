@@ -23,7 +23,14 @@
 //! which wraps `Read` or `Write` in a loop, where `Ok(0)` is handled internally.
 //!
 //! Stream itself does not buffer any payload data during
-//! a `Read` or `Write`, so there is no extra heap allocation.
+//! a `Read` or `Write`, payload is read into or written from the caller's buffer.
+//!
+//! Other states are kept inline, e.g. `Stream<TcpStream, Server>` takes 336 bytes
+//! on a 64-bit target with default features. A few optional states are boxed,
+//! and only allocated when used: data read past the handshake, the negotiated
+//! subprotocol and extensions, the auto pong queue, a close frame to send,
+//! the payload of a close frame in strict mode, and the keepalive timer.
+//! The `frame_hook` feature also boxes the hook.
 //!
 //! # Masking payload
 //!
@@ -65,6 +72,25 @@
 //! }
 //! ```
 //!
+//! # Strict mode
+//!
+//! By default a stream is lenient with peers. `Stream::set_strict` rejects
+//! non-minimal payload lengths, frames masked in the wrong direction,
+//! illegal control frames and invalid UTF-8 in text messages or close reasons,
+//! and echoes the status code of a close frame once it is read. Reserved bits are always rejected.
+//! Nothing is sent on drop, use `Stream::close` to close a connection.
+//!
+//! # Metrics
+//!
+//! With `metrics` feature enabled, a stream counts frames and bytes
+//! read from or written to the underlying IO source,
+//! which could be retrieved via `Stream::stats`.
+//!
+//! # Frame hook
+//!
+//! With `frame_hook` feature enabled, a callback could be registered via
+//! `Stream::on_frame`, which observes each incoming frame head.
+//!
 //! # Automatic masking
 //!
 //! It is annoying to mask the payload each time before a write,
@@ -75,30 +101,71 @@
 //!
 //! This feature only has effects on [`AutoMaskClientRole`](crate::role::AutoMaskClientRole),
 //! where its inner mask key may be updated (depends on
-//! [`AutoMaskClientRole::UPDATE_MASK_KEY`](crate::role::AutoMaskClientRole::UPDATE_MASK_KEY))
+//! [`AutoMaskClientRole::update_mask_key`](crate::role::AutoMaskClientRole::update_mask_key))
 //! and used to mask the payload before each write.
 //! Other [`ClientRole`](crate::role::ClientRole) and [`ServerRole`](crate::role::ServerRole)
 //! are not affected. Related code lies in `src/stream/detail/write#L118`.
 //!
 
+/// Update a counter of [`StreamStats`], only with the `metrics` feature.
+macro_rules! count {
+    ($state: expr, $field: ident, $n: expr) => {
+        #[cfg(feature = "metrics")]
+        {
+            $state.stats.$field += $n as u64;
+        }
+    };
+}
+
 mod read;
 mod write;
 
 mod ctrl;
+mod close;
+mod builder;
+mod message;
+mod buffered;
 mod state;
 mod detail;
 mod special;
+mod utf8;
 
 cfg_if::cfg_if! {
     if #[cfg(feature = "async")] {
         mod async_read;
         mod async_write;
+        mod keepalive;
     }
 }
 
+cfg_if::cfg_if! {
+    if #[cfg(feature = "metrics")] {
+        mod stats;
+        pub use stats::StreamStats;
+    }
+}
+
+cfg_if::cfg_if! {
+    if #[cfg(feature = "frame_hook")] {
+        mod hook;
+    }
+}
+
+pub use read::ReadEvent;
+pub use builder::StreamBuilder;
+pub use message::MessageWriter;
+pub use buffered::BufferedStream;
+pub use detail::MAX_WRITE_FRAMES;
+
+use std::ptr;
+use std::mem::ManuallyDrop;
 use std::marker::PhantomData;
-use state::{ReadState, WriteState, HeartBeat};
+use close::DropClose;
+use state::{ReadState, WriteState, HeartBeat, Leftover, AutoPong, CtrlStore, ClosePayload};
+use utf8::Utf8Validator;
+use crate::frame::OpCode;
 use crate::role::RoleHelper;
+use crate::handshake::Negotiated;
 
 /// Direct read or write.
 pub struct Direct {}
@@ -106,22 +173,63 @@ pub struct Direct {}
 /// Wrapped read or write.
 pub struct Guarded {}
 
-/// Websocket stream.
-///
-/// Depending on `IO`, [`Stream`] implements [`std::io::Read`] and [`std::io::Write`]
-/// or [`tokio::io::AsyncRead`] and [`tokio::io::AsyncWrite`].
-///
-/// `Role` decides whether to mask payload data.
-/// It is reserved to provide extra infomation to apply optimizations.
-///
-/// See also: `Stream::read`, `Stream::write`.
-pub struct Stream<IO, Role, Guard = Direct> {
-    io: IO,
+pin_project_lite::pin_project! {
+    /// Websocket stream.
+    ///
+    /// Depending on `IO`, [`Stream`] implements [`std::io::Read`] and [`std::io::Write`]
+    /// or [`tokio::io::AsyncRead`] and [`tokio::io::AsyncWrite`].
+    ///
+    /// `Role` decides whether to mask payload data.
+    /// It is reserved to provide extra infomation to apply optimizations.
+    ///
+    /// See also: `Stream::read`, `Stream::write`.
+    ///
+    /// Only `io` is structurally pinned, other states are never pinned.
+    #[project = StreamProj]
+    pub struct Stream<IO, Role, Guard = Direct> {
+        #[pin]
+        io: IO,
+        state: State<Role>,
+        __marker: PhantomData<Guard>,
+    }
+
+    impl<IO, Role, Guard> PinnedDrop for Stream<IO, Role, Guard> {
+        fn drop(this: Pin<&mut Self>) {
+            let this = this.project();
+            this.io.drop_close(this.state);
+        }
+    }
+}
+
+/// Everything except the IO source, which is moved as a whole.
+pub(crate) struct State<Role> {
     role: Role,
     read_state: ReadState,
     write_state: WriteState,
     heartbeat: HeartBeat,
-    __marker: PhantomData<Guard>,
+    leftover: Leftover,
+    read_fragmented: bool,
+    read_opcode: Option<OpCode>,
+    read_message_len: u64,
+    read_utf8: Option<Utf8Validator>,
+    read_close: Option<Box<ClosePayload>>,
+    write_fragmented: bool,
+    write_opcode: OpCode,
+    max_message_size: u64,
+    strict_length: bool,
+    strict_mask: bool,
+    strict_control: bool,
+    accept_text: bool,
+    negotiated: Negotiated,
+    close_store: Option<Box<CtrlStore>>,
+    drop_close: Option<Box<CtrlStore>>,
+    auto_pong: Option<Box<AutoPong>>,
+    #[cfg(feature = "async")]
+    keepalive: Option<Box<keepalive::KeepAlive>>,
+    #[cfg(feature = "metrics")]
+    stats: StreamStats,
+    #[cfg(feature = "frame_hook")]
+    frame_hook: Option<hook::FrameHook>,
 }
 
 impl<IO, Role, Guard> AsRef<IO> for Stream<IO, Role, Guard> {
@@ -136,43 +244,178 @@ impl<IO, Role, Guard> AsMut<IO> for Stream<IO, Role, Guard> {
 
 impl<IO, Role, Guard> std::fmt::Debug for Stream<IO, Role, Guard> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("Stream")
-            .field("read_state", &self.read_state)
-            .field("write_state", &self.write_state)
-            .field("heartbeat", &self.heartbeat)
-            .finish()
+        use std::any::type_name;
+        let mut s = f.debug_struct("Stream");
+        s.field("role", &format_args!("{}", type_name::<Role>()))
+            .field("guard", &format_args!("{}", type_name::<Guard>()))
+            .field("read_state", &self.state.read_state)
+            .field("write_state", &self.state.write_state)
+            .field("heartbeat", &self.state.heartbeat)
+            .field("leftover", &self.state.leftover)
+            .field("read_fragmented", &self.state.read_fragmented)
+            .field("read_opcode", &self.state.read_opcode)
+            .field("read_message_len", &self.state.read_message_len)
+            .field("read_utf8", &self.state.read_utf8)
+            .field("read_close", &self.state.read_close)
+            .field("write_fragmented", &self.state.write_fragmented)
+            .field("write_opcode", &self.state.write_opcode)
+            .field("max_message_size", &self.state.max_message_size)
+            .field("strict_length", &self.state.strict_length)
+            .field("strict_mask", &self.state.strict_mask)
+            .field("strict_control", &self.state.strict_control)
+            .field("accept_text", &self.state.accept_text)
+            .field("negotiated", &self.state.negotiated)
+            .field("close_store", &self.state.close_store)
+            .field("drop_close", &self.state.drop_close)
+            .field("auto_pong", &self.state.auto_pong);
+        #[cfg(feature = "async")]
+        s.field("keepalive", &self.state.keepalive);
+        #[cfg(feature = "metrics")]
+        s.field("stats", &self.state.stats);
+        #[cfg(feature = "frame_hook")]
+        s.field("frame_hook", &self.state.frame_hook.is_some());
+        s.finish()
     }
 }
 
 impl<IO, Role> Stream<IO, Role> {
     /// Create websocket stream from IO source directly,
     /// without a handshake.
+    ///
+    /// The stream is as one-directional as `IO`, e.g. a stream over `&[u8]`
+    /// only implements `Read`, and one over `Vec<u8>` only implements `Write`.
     #[inline]
     pub const fn new(io: IO, role: Role) -> Self {
         Stream {
             io,
-            role,
-            read_state: ReadState::new(),
-            write_state: WriteState::new(),
-            heartbeat: HeartBeat::new(),
+            state: State {
+                role,
+                read_state: ReadState::new(),
+                write_state: WriteState::new(),
+                heartbeat: HeartBeat::new(),
+                leftover: Leftover::new(),
+                read_fragmented: false,
+                read_opcode: None,
+                read_message_len: 0,
+                read_utf8: None,
+                read_close: None,
+                write_fragmented: false,
+                write_opcode: OpCode::Binary,
+                max_message_size: u64::MAX,
+                strict_length: false,
+                strict_mask: false,
+                strict_control: false,
+                accept_text: false,
+                negotiated: Negotiated::new(),
+                close_store: None,
+                drop_close: None,
+                auto_pong: None,
+                #[cfg(feature = "async")]
+                keepalive: None,
+                #[cfg(feature = "metrics")]
+                stats: StreamStats::new(),
+                #[cfg(feature = "frame_hook")]
+                frame_hook: None,
+            },
             __marker: PhantomData,
         }
     }
 
+    /// Create websocket stream from IO source, along with data which has been
+    /// read from IO, e.g. data read past a handshake done by the caller.
+    ///
+    /// These data are read before any data from IO.
+    #[inline]
+    pub fn new_with_leftover(io: IO, role: Role, leftover: Box<[u8]>) -> Self {
+        let mut stream = Self::new(io, role);
+        stream.state.leftover = Leftover::new_with_data(leftover);
+        stream
+    }
+
     /// Convert to a guarded stream.
     #[inline]
     pub fn guard(self) -> Stream<IO, Role, Guarded> {
+        let (io, state) = self.into_parts();
         Stream {
-            io: self.io,
-            role: self.role,
-            read_state: self.read_state,
-            write_state: self.write_state,
-            heartbeat: self.heartbeat,
+            io,
+            state,
             __marker: PhantomData,
         }
     }
 }
 
+impl<IO, Role, Guard> Stream<IO, Role, Guard> {
+    /// Move out `io` and `state` without running drop.
+    #[inline]
+    fn into_parts(self) -> (IO, State<Role>) {
+        // each field is moved out exactly once
+        let this = ManuallyDrop::new(self);
+        unsafe { (ptr::read(&this.io), ptr::read(&this.state)) }
+    }
+}
+
+impl<IO, Role: RoleHelper, Guard> Stream<IO, Role, Guard> {
+    /// Save negotiated results, the role also takes them.
+    #[inline]
+    pub(crate) fn set_negotiated(&mut self, negotiated: Negotiated) {
+        self.state.role.set_negotiated(&negotiated);
+        self.state.negotiated = negotiated;
+    }
+}
+
+impl<IO, Role, Guard> Stream<IO, Role, Guard> {
+    /// Get subprotocol, extensions and compression parameters
+    /// negotiated during the handshake.
+    #[inline]
+    pub const fn negotiated(&self) -> &Negotiated { &self.state.negotiated }
+
+    /// Subprotocol selected by the server, a shortcut of [`Negotiated::protocol`].
+    #[inline]
+    pub fn protocol(&self) -> Option<&[u8]> { self.state.negotiated.protocol() }
+
+    /// Extensions selected by the server, the raw header value,
+    /// a shortcut of [`Negotiated::extensions`].
+    #[inline]
+    pub fn extensions(&self) -> Option<&[u8]> { self.state.negotiated.extensions() }
+
+    /// Get the role, which may carry negotiated state,
+    /// see [`DeflateRole`](crate::role::DeflateRole).
+    #[inline]
+    pub const fn role(&self) -> &Role { &self.state.role }
+
+    /// Get the underlying IO source, along with data which has been
+    /// read from IO but not consumed, i.e. an incomplete frame head,
+    /// or data read past the handshake.
+    ///
+    /// Other states are lost, including payload of a partially read ping,
+    /// and a staged close frame, see [`close`](Self::close).
+    /// The close frame set by [`close_on_drop`](Self::close_on_drop) is not sent.
+    pub fn into_inner(self) -> (IO, Option<Vec<u8>>) {
+        let (io, state) = self.into_parts();
+        let State {
+            read_state,
+            leftover,
+            ..
+        } = state;
+
+        let mut data = match read_state {
+            ReadState::ReadHead(head_store) => head_store.read().to_vec(),
+            _ => Vec::new(),
+        };
+        data.append(&mut leftover.into_vec());
+
+        let data = if data.is_empty() { None } else { Some(data) };
+        (io, data)
+    }
+
+    /// Get the number of bytes read past the handshake, e.g. a frame sent
+    /// right after the request, which are not consumed yet.
+    ///
+    /// These bytes are read before any data from IO.
+    #[inline]
+    pub fn leftover_len(&self) -> usize { self.state.leftover.len() }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -216,9 +459,101 @@ mod test {
         fn flush(&mut self) -> Result<()> { Ok(()) }
     }
 
+    /// Return `WouldBlock`(or another error) before each read or write.
+    pub struct WouldBlockReadWriter<T> {
+        pub inner: T,
+        pub blocked: bool,
+        pub kind: std::io::ErrorKind,
+    }
+
+    impl<T> WouldBlockReadWriter<T> {
+        pub fn new(inner: T) -> Self { Self::with_kind(inner, std::io::ErrorKind::WouldBlock) }
+
+        pub fn with_kind(inner: T, kind: std::io::ErrorKind) -> Self {
+            Self {
+                inner,
+                blocked: false,
+                kind,
+            }
+        }
+
+        fn block(&mut self) -> Result<()> {
+            self.blocked = !self.blocked;
+            if self.blocked {
+                Err(self.kind.into())
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    impl<T: Read> Read for WouldBlockReadWriter<T> {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            self.block()?;
+            self.inner.read(buf)
+        }
+    }
+
+    impl<T: Write> Write for WouldBlockReadWriter<T> {
+        fn write(&mut self, buf: &[u8]) -> Result<usize> {
+            self.block()?;
+            self.inner.write(buf)
+        }
+
+        fn flush(&mut self) -> Result<()> { self.inner.flush() }
+    }
+
+    #[test]
+    fn debug_type_names() {
+        use std::any::type_name;
+
+        let stream = Stream::new([0_u8; 0].as_slice(), Server::new());
+        let debug = format!("{:?}", stream);
+        let expect = format!(
+            "Stream {{ role: {}, guard: {}, read_state: ",
+            type_name::<Server>(),
+            type_name::<Direct>()
+        );
+        assert!(debug.starts_with(&expect));
+
+        let stream = Stream::new([0_u8; 0].as_slice(), Client::new()).guard();
+        let debug = format!("{:?}", stream);
+        assert!(debug.contains("Client, guard: "));
+        assert!(debug.contains("Guarded, read_state: "));
+    }
+
+    #[test]
+    fn into_inner() {
+        let stream = Stream::new([1_u8].as_slice(), Client::new());
+        let (io, data) = stream.into_inner();
+        assert_eq!(io, [1]);
+        assert_eq!(data, None);
+
+        // incomplete frame head
+        let mut stream = Stream::new([0x82, 0x7e, 0x01].as_slice(), Client::new());
+        let mut buf = vec![0; 32];
+        assert_eq!(stream.read(&mut buf).unwrap(), 0);
+        let (io, data) = stream.into_inner();
+        assert!(io.is_empty());
+        assert_eq!(data, Some(vec![0x82, 0x7e, 0x01]));
+
+        // unread data from handshake
+        let stream = Stream::new_with_leftover(
+            [0_u8; 0].as_slice(),
+            Client::new(),
+            Box::new([0x82, 0x01, b'a']),
+        );
+        let (_, data) = stream.into_inner();
+        assert_eq!(data, Some(vec![0x82, 0x01, b'a']));
+    }
+
     pub fn make_head(opcode: OpCode, mask: Mask, len: usize) -> Vec<u8> {
+        make_head_with_fin(Fin::Y, opcode, mask, len)
+    }
+
+    pub fn make_head_with_fin(fin: Fin, opcode: OpCode, mask: Mask, len: usize) -> Vec<u8> {
         let mut tmp = vec![0; 14];
-        let head = FrameHead::new(Fin::Y, opcode, mask, PayloadLen::from_num(len as u64));
+        let head = FrameHead::new(fin, opcode, mask, PayloadLen::from_num(len as u64));
 
         let head_len = head.encode(&mut tmp).unwrap();
         let mut head = Vec::new();
@@ -228,7 +563,7 @@ mod test {
     }
 
     pub fn make_data(len: usize) -> Vec<u8> {
-        std::iter::repeat(rand::random::<u8>()).take(len).collect()
+        std::iter::repeat_n(rand::random::<u8>(), len).collect()
     }
 
     pub fn make_frame<R: RoleHelper>(opcode: OpCode, len: usize) -> (Vec<u8>, Vec<u8>) {
@@ -249,6 +584,19 @@ mod test {
         (frame, data)
     }
 
+    // payload is masked, data is unmasked
+    pub fn make_masked_frame(opcode: OpCode, key: [u8; 4], len: usize) -> (Vec<u8>, Vec<u8>) {
+        let data: Vec<u8> = (0..len).map(|x| x as u8).collect();
+
+        let mut frame = make_head(opcode, Mask::Key(key), len);
+        let head_len = frame.len();
+
+        frame.extend_from_slice(&data);
+        apply_mask4(key, &mut frame[head_len..]);
+
+        (frame, data)
+    }
+
     #[test]
     fn read_write_stream() {
         fn read_write<R: RoleHelper>(rlimit: usize, wlimit: usize, len: usize) {
@@ -263,7 +611,7 @@ mod test {
             // this is not correct in practice, but our program can still handle it.
             let mut stream = Stream::<_, R>::new(io, R::new());
 
-            let data: Vec<u8> = std::iter::repeat(rand::random::<u8>()).take(len).collect();
+            let data: Vec<u8> = std::iter::repeat_n(rand::random::<u8>(), len).collect();
             let mut data2: Vec<u8> = Vec::new();
 
             let mut buf = vec![0; 0x2000];
@@ -290,7 +638,7 @@ mod test {
                     //     break;
                     // }
 
-                    tmp.write(&buf[..n]).unwrap();
+                    tmp.write_all(&buf[..n]).unwrap();
                 }
 
                 assert_eq!(tmp.len(), n);
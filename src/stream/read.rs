@@ -1,28 +1,28 @@
-use std::io::{Read, Result};
+use std::io::{Read, Result, ErrorKind};
 use std::task::Poll;
 
 use super::{Stream, RoleHelper, Guarded};
+use super::state::ReadState;
 use super::detail::read_some;
 
+use crate::frame::OpCode;
+
 impl<IO: Read, Role: RoleHelper> Read for Stream<IO, Role> {
     /// Read some data from the underlying IO source,
     /// returns `Ok(0)` until a complete frame head is present.
-    /// Caller should ensure the available buffer size is larger
-    /// than **14** before a read.
+    /// A buffer shorter than **14** bytes is allowed, but less efficient,
+    /// since frame head is then read separately from payload.
     ///
     /// Read a control frame(like Ping) returns `Ok(0)`,
-    /// which could be detected via [`Stream::is_pinged`].
+    /// which could be detected via [`Stream::is_pinged`], or use [`Stream::read_event`].
     ///
     /// Any read after receiving a `Close` frame or reaching `EOF`
     /// will return `Ok(0)`,
     /// which could be checked via [`Stream::is_read_end`],
     /// [`Stream::is_read_close`], [`Stream::is_read_eof`].
-    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
-        match read_some(self, |io, buf| io.read(buf).into(), buf) {
-            Poll::Ready(x) => x,
-            Poll::Pending => unreachable!(),
-        }
-    }
+    /// An `EOF` in the middle of a frame could be detected
+    /// via [`Stream::is_read_truncated`].
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> { self.read_io(buf) }
 
     /// **This is NOT supported!**
     fn read_to_end(&mut self, _: &mut Vec<u8>) -> Result<usize> {
@@ -43,76 +43,195 @@ impl<IO: Read, Role: RoleHelper> Read for Stream<IO, Role> {
 impl<IO: Read, Role: RoleHelper> Read for Stream<IO, Role, Guarded> {
     /// Wrap read in a loop.
     /// Continue to read if frame head is not complete.
+    ///
+    /// An `Interrupted` error is retried. Other IO errors(like `WouldBlock`)
+    /// are returned as is, and the next read resumes from where it stopped.
+    ///
+    /// Unlike the direct mode, once `EOF` is reached in the middle of a frame,
+    /// this and any later read will return an error of [`ErrorKind::UnexpectedEof`].
+    ///
+    /// A control frame(like Ping) does not end the read, so on a stream which
+    /// only carries control frames (e.g. keepalive pings) it returns only when
+    /// data arrives or the stream ends. To regain control in between, set a
+    /// read timeout or use a non-blocking IO: received pings are recorded,
+    /// and they could be answered with [`flush`](std::io::Write::flush) if
+    /// auto pong is enabled.
     fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
         loop {
-            match read_some(self, |io, buf| io.read(buf).into(), buf) {
-                Poll::Ready(Ok(0)) if self.is_read_partial_head() || !self.is_read_end() => {
-                    continue
-                }
-                Poll::Ready(x) => return x,
-                Poll::Pending => unreachable!(),
+            match self.read_io(buf) {
+                Ok(0) if self.is_read_truncated() => return Err(ErrorKind::UnexpectedEof.into()),
+                Ok(0) if self.is_read_partial_head() || !self.is_read_end() => continue,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                x => return x,
             }
         }
     }
 
     /// Override default implement, extend reserved buffer size,
     /// so that there is enough space to accommodate frame head.
+    ///
+    /// A frame which makes its message exceed [`Stream::max_message_size`]
+    /// is rejected with [`FrameError::FrameTooLarge`](crate::error::FrameError::FrameTooLarge)
+    /// before the buffer grows to hold its payload.
+    ///
+    /// Like [`read`](Read::read), control frames are consumed without returning,
+    /// an IO error(like `WouldBlock` or `TimedOut`) is returned as is,
+    /// and data which has been read is kept in the buffer.
     fn read_to_end(&mut self, buf: &mut Vec<u8>) -> Result<usize> {
-        use std::io::BorrowedBuf;
-        use std::io::ErrorKind;
+        use std::mem::MaybeUninit;
 
         let start_len = buf.len();
-        let start_cap = buf.capacity();
 
         let mut initialized = 0; // Extra initialized bytes from previous loop iteration
         loop {
-            if buf.len() < buf.capacity() + 14 {
+            if buf.capacity() - buf.len() < 14 {
                 buf.reserve(32); // buf is full, need more space
+                initialized = 0;
             }
 
-            let mut read_buf: BorrowedBuf<'_> = buf.spare_capacity_mut().into();
+            let spare = buf.spare_capacity_mut();
+            let spare_len = spare.len();
 
-            // SAFETY: These bytes were initialized but not filled in the previous loop
-            unsafe {
-                read_buf.set_init(initialized);
-            }
+            // initialize the rest of spare capacity
+            spare[initialized..].fill(MaybeUninit::new(0));
 
-            let mut cursor = read_buf.unfilled();
-            match self.read_buf(cursor.reborrow()) {
-                Ok(()) => {}
-                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            // SAFETY: all spare bytes are initialized above
+            let read_buf = unsafe { &mut *(spare as *mut [MaybeUninit<u8>] as *mut [u8]) };
+
+            let read_n = match self.read(read_buf) {
+                Ok(n) => n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {
+                    initialized = spare_len;
+                    continue;
+                }
                 Err(e) => return Err(e),
-            }
+            };
 
-            if cursor.written() == 0 {
+            if read_n == 0 {
                 return Ok(buf.len() - start_len);
             }
 
             // store how much was initialized but not filled
-            initialized = cursor.init_ref().len();
+            initialized = spare_len - read_n;
 
-            // SAFETY: BorrowedBuf's invariants mean this much memory is init
+            // SAFETY: these bytes are initialized and filled
             unsafe {
-                let new_len = read_buf.filled().len() + buf.len();
+                let new_len = buf.len() + read_n;
                 buf.set_len(new_len);
             }
+        }
+    }
+}
 
-            if buf.len() == buf.capacity() && buf.capacity() == start_cap {
-                // The buffer might be an exact fit. Let's read into a probe buffer
-                // and see if it returns `Ok(0)`. If so, we've avoided an
-                // unnecessary doubling of the capacity. But if not, append the
-                // probe buffer to the primary buffer and let its capacity grow.
-                let mut probe = [0u8; 32];
+/// Result of [`Stream::read_event`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadEvent {
+    /// Some payload data is read, a `Ping` may be completed as well.
+    Data(usize),
+    /// A `Ping` frame is completely read, see [`Stream::ping_data`].
+    Ping,
+    /// A `Close` frame is received.
+    Close,
+    /// `EOF` is reached.
+    Eof,
+    /// A frame head or a `Ping` is partially read, or a frame is empty.
+    Incomplete,
+}
 
-                loop {
-                    match self.read(&mut probe) {
-                        Ok(0) => return Ok(buf.len() - start_len),
-                        Ok(n) => {
-                            buf.extend_from_slice(&probe[..n]);
-                            break;
+impl<IO: Read, Role: RoleHelper, Guard> Stream<IO, Role, Guard> {
+    /// Read some data, then try to send a staged close frame
+    /// once a received close frame is reported,
+    /// see [`set_strict_control`](Self::set_strict_control).
+    #[inline]
+    fn read_io(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let ret = match read_some(&mut self.state, |buf| self.io.read(buf).into(), buf) {
+            Poll::Ready(x) => x,
+            Poll::Pending => unreachable!(),
+        };
+        // data in front of the close frame is returned first
+        if let Ok(0) = ret {
+            self.state.send_close(&mut self.io);
+        }
+        ret
+    }
+
+    /// Read some data like [`read`](Read::read) in [`Direct`](super::Direct) mode,
+    /// and tell what happened instead of returning `Ok(0)`.
+    ///
+    /// A `Pong` frame is discarded like [`read`](Read::read) does.
+    pub fn read_event(&mut self, buf: &mut [u8]) -> Result<ReadEvent> {
+        let pings = self.state.heartbeat.completed;
+
+        let n = self.read_io(buf)?;
+
+        let event = if n != 0 {
+            ReadEvent::Data(n)
+        } else if self.state.heartbeat.completed != pings {
+            ReadEvent::Ping
+        } else if self.is_read_close() {
+            ReadEvent::Close
+        } else if self.is_read_eof() {
+            ReadEvent::Eof
+        } else {
+            ReadEvent::Incomplete
+        };
+        Ok(event)
+    }
+
+    /// Read exactly one frame(not a whole fragmented message),
+    /// append its payload to `buf`, return its opcode and `FIN` bit.
+    ///
+    /// The opcode is the one of this frame, which is `Binary`, `Text`(if accepted)
+    /// or `Continue`, control frames like `Ping` are handled as [`read`](Read::read) does. Receiving a `Close` frame returns
+    /// `(OpCode::Close, true)` without payload,
+    /// reaching `EOF` returns an error of [`ErrorKind::UnexpectedEof`].
+    ///
+    /// An IO error(like `WouldBlock`) is returned as is, payload which has been
+    /// appended is kept, and the next call resumes from where it stopped.
+    pub fn read_message_frame(&mut self, buf: &mut Vec<u8>) -> Result<(OpCode, bool)> {
+        /// Read at most this many bytes per syscall.
+        const CHUNK_LEN: u64 = 0x2000;
+
+        loop {
+            match self.state.read_state {
+                ReadState::Close => return Ok((OpCode::Close, true)),
+                ReadState::Eof { .. } => return Err(ErrorKind::UnexpectedEof.into()),
+                // read payload, never past the end of this frame
+                ReadState::ReadData { next, opcode, .. } => {
+                    let len = buf.len();
+                    buf.resize(len + std::cmp::min(next, CHUNK_LEN) as usize, 0);
+
+                    let read_n = match self.read_io(&mut buf[len..]) {
+                        Ok(n) => n,
+                        Err(e) => {
+                            buf.truncate(len);
+                            return Err(e);
+                        }
+                    };
+                    buf.truncate(len + read_n);
+
+                    if matches!(self.state.read_state, ReadState::ReadHead(_)) {
+                        return Ok((opcode, !self.state.read_fragmented));
+                    }
+                }
+                // read a frame head or ping payload with a short buffer,
+                // so that no payload is read
+                _ => {
+                    self.state.read_opcode = None;
+
+                    let mut head_buf = [0_u8; 1];
+                    self.read_io(&mut head_buf)?;
+
+                    // an empty data frame, not a control frame
+                    if let Some(opcode) = self.state.read_opcode.take() {
+                        if matches!(self.state.read_state, ReadState::ReadHead(ref head) if head.is_empty())
+                        {
+                            return Ok((opcode, !self.state.read_fragmented));
                         }
-                        Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
-                        Err(e) => return Err(e),
                     }
                 }
             }
@@ -124,9 +243,13 @@ impl<IO: Read, Role: RoleHelper> Read for Stream<IO, Role, Guarded> {
 mod test {
     use std::io::Read;
     use super::*;
-    use super::super::test::{LimitReadWriter, make_frame};
+    use super::super::test::{
+        LimitReadWriter, WouldBlockReadWriter, make_frame, make_frame_with_mask, make_masked_frame,
+        make_head, make_head_with_fin, make_data,
+    };
     use crate::frame::*;
     use crate::role::*;
+    use crate::error::{Error, FrameError};
 
     #[test]
     fn read_from_stream() {
@@ -153,6 +276,85 @@ mod test {
         }
     }
 
+    #[test]
+    fn read_from_stream_with_short_buffer() {
+        fn read<R1: RoleHelper, R2: RoleHelper>(n: usize, size: usize) {
+            let mut frame = Vec::new();
+            let mut data = Vec::new();
+            for (opcode, len) in [
+                (OpCode::Binary, n),
+                (OpCode::Ping, 4),
+                (OpCode::Binary, n + 1),
+            ] {
+                let (mut f, mut d) = make_frame::<R1>(opcode, len);
+                frame.append(&mut f);
+                if opcode == OpCode::Binary {
+                    data.append(&mut d);
+                }
+            }
+
+            let mut stream = Stream::new(frame.as_slice(), R2::new()).guard();
+
+            let mut buf = vec![0; size];
+            let mut data2 = Vec::new();
+            loop {
+                let read_n = stream.read(&mut buf).unwrap();
+                if read_n == 0 {
+                    break;
+                }
+                data2.extend_from_slice(&buf[..read_n]);
+            }
+
+            assert!(stream.is_read_eof());
+            assert!(stream.is_pinged());
+            assert_eq!(data2, data);
+            assert_eq!(stream.read(&mut []).unwrap(), 0);
+        }
+
+        for n in [0, 1, 125, 126, 65536] {
+            for size in 1..=14 {
+                read::<Client, Server>(n, size);
+                read::<Server, Client>(n, size);
+            }
+        }
+    }
+
+    #[test]
+    fn read_stored_head_with_short_buffer() {
+        fn read<R: RoleHelper>(stored: usize, size: usize) {
+            // 14 bytes head, with an 8-byte extended length and a mask key
+            let (frame, data) = make_masked_frame(OpCode::Binary, new_mask_key(), 65536);
+
+            let io = LimitReadWriter {
+                buf: frame,
+                rlimit: stored,
+                wlimit: 0,
+                cursor: 0,
+            };
+
+            let mut stream = Stream::new(io, R::new());
+
+            // part of the head is stored
+            let mut buf = vec![0; 32];
+            assert_eq!(stream.read(&mut buf).unwrap(), 0);
+            assert!(stream.is_read_partial_head());
+
+            let mut buf = vec![0; size];
+            let mut data2 = Vec::new();
+            while data2.len() < data.len() {
+                let n = stream.read(&mut buf).unwrap();
+                data2.extend_from_slice(&buf[..n]);
+            }
+            assert_eq!(data2, data);
+        }
+
+        for stored in 1..14 {
+            for size in [1, 2, 13] {
+                read::<Server>(stored, size);
+            }
+        }
+    }
+
     #[test]
     fn read_from_limit_stream() {
         fn read<R1: RoleHelper, R2: RoleHelper>(n: usize, limit: usize) {
@@ -189,6 +391,204 @@ mod test {
         }
     }
 
+    #[test]
+    fn read_masked_head_byte_by_byte() {
+        fn read(n: usize, size: usize) {
+            let key = new_mask_key();
+            let (frame, data) = make_masked_frame(OpCode::Binary, key, n);
+            let head_len = frame.len() - n;
+
+            let io = LimitReadWriter {
+                buf: frame.clone(),
+                rlimit: 1,
+                wlimit: 0,
+                cursor: 0,
+            };
+            let mut stream = Stream::new(io, Server::new());
+
+            let mut buf = vec![0; size];
+            let mut data2 = Vec::new();
+            loop {
+                let read_n = stream.read(&mut buf).unwrap();
+                data2.extend_from_slice(&buf[..read_n]);
+
+                // stored head bytes, including a partial mask key, are kept as is
+                let cursor = stream.as_ref().cursor;
+                if let ReadState::ReadHead(head_store) = stream.state.read_state {
+                    if cursor < head_len {
+                        assert_eq!(head_store.read(), &frame[..cursor]);
+                    }
+                }
+                if stream.is_read_eof() {
+                    break;
+                }
+            }
+
+            assert!(!stream.is_read_truncated());
+            assert_eq!(data2, data);
+        }
+
+        // 7-bit, 16-bit and 64-bit payload length
+        for n in [0, 1, 125, 126, 65535, 65536] {
+            for size in [1, 2, 13, 14, 15, 64, 0x20000] {
+                read(n, size);
+            }
+        }
+    }
+
+    #[test]
+    fn read_masked_from_limit_stream() {
+        fn read(opcode: OpCode, n: usize, limit: usize) {
+            let (frame, data) = make_masked_frame(opcode, [1, 2, 3, 4], n);
+
+            let io = LimitReadWriter {
+                buf: frame,
+                rlimit: limit,
+                wlimit: 0,
+                cursor: 0,
+            };
+
+            let mut buf = Vec::new();
+            let mut stream = Stream::new(io, Server::new()).guard();
+            stream.read_to_end(&mut buf).unwrap();
+
+            match opcode {
+                OpCode::Ping => assert_eq!(stream.ping_data(), data),
+                _ => assert_eq!(buf, data),
+            }
+        }
+
+        for limit in 1..=16 {
+            for n in [1, 2, 3, 4, 5, 7, 64, 125] {
+                read(OpCode::Binary, n, limit);
+                read(OpCode::Ping, n, limit);
+            }
+            read(OpCode::Binary, 1000, limit);
+        }
+    }
+
+    #[test]
+    fn read_event_from_stream() {
+        fn read<R1: RoleHelper, R2: RoleHelper>(limit: usize) {
+            let (data_frame, data) = make_frame::<R1>(OpCode::Binary, 32);
+            let (ping_frame, ping) = make_frame::<R1>(OpCode::Ping, 16);
+            let (close_frame, _) = make_frame::<R1>(OpCode::Close, 0);
+
+            let io = LimitReadWriter {
+                buf: [ping_frame, data_frame, close_frame].concat(),
+                rlimit: limit,
+                wlimit: 0,
+                cursor: 0,
+            };
+            let mut stream = Stream::new(io, R2::new());
+
+            let mut buf = vec![0; 64];
+            let mut data2 = Vec::new();
+            let mut events = Vec::new();
+            loop {
+                let event = stream.read_event(&mut buf).unwrap();
+                match event {
+                    ReadEvent::Data(n) => data2.extend_from_slice(&buf[..n]),
+                    ReadEvent::Ping => events.push(event),
+                    ReadEvent::Incomplete => {}
+                    ReadEvent::Close => break,
+                    ReadEvent::Eof => unreachable!(),
+                }
+            }
+
+            assert_eq!(data2, data);
+            assert_eq!(stream.ping_data(), ping.as_slice());
+            // a ping followed by data in the same read is reported as data
+            assert!(events.len() <= 1);
+            assert_eq!(stream.read_event(&mut buf).unwrap(), ReadEvent::Close);
+        }
+
+        for limit in [1, 2, 3, 7, 16, 100] {
+            read::<Client, Server>(limit);
+            read::<Server, Client>(limit);
+        }
+
+        let mut stream = Stream::new([0_u8; 0].as_slice(), Server::new());
+        let mut buf = vec![0; 16];
+        assert_eq!(stream.read_event(&mut buf).unwrap(), ReadEvent::Eof);
+    }
+
+    #[test]
+    fn read_from_interrupted_stream() {
+        for limit in [1, 3, 14] {
+            for n in [0, 1, 126, 1000] {
+                let (frame, data) = make_masked_frame(OpCode::Binary, new_mask_key(), n);
+                let (ping, _) = make_masked_frame(OpCode::Ping, new_mask_key(), 5);
+
+                let io = WouldBlockReadWriter::with_kind(
+                    LimitReadWriter {
+                        buf: [ping, frame].concat(),
+                        rlimit: limit,
+                        wlimit: 0,
+                        cursor: 0,
+                    },
+                    std::io::ErrorKind::Interrupted,
+                );
+                let mut stream = Stream::new(io, Server::new()).guard();
+
+                // never interrupted
+                let mut buf = vec![0; 7];
+                let mut data2 = Vec::new();
+                loop {
+                    let n = stream.read(&mut buf).unwrap();
+                    if n == 0 {
+                        break;
+                    }
+                    data2.extend_from_slice(&buf[..n]);
+                }
+                assert!(stream.is_read_eof() && !stream.is_read_truncated());
+                assert_eq!(data2, data);
+            }
+        }
+    }
+
+    #[test]
+    fn read_from_would_block_stream() {
+        fn read(opcode: OpCode, n: usize, limit: usize, size: usize) {
+            let (frame, data) = make_masked_frame(opcode, new_mask_key(), n);
+
+            let io = WouldBlockReadWriter::new(LimitReadWriter {
+                buf: frame,
+                rlimit: limit,
+                wlimit: 0,
+                cursor: 0,
+            });
+            let mut stream = Stream::new(io, Server::new()).guard();
+
+            let mut buf = vec![0; size];
+            let mut data2 = Vec::new();
+            loop {
+                match stream.read(&mut buf) {
+                    Ok(0) => break,
+                    Ok(n) => data2.extend_from_slice(&buf[..n]),
+                    // resume with the stored state
+                    Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => continue,
+                    Err(e) => panic!("{}", e),
+                }
+            }
+            assert!(stream.is_read_eof());
+
+            match opcode {
+                OpCode::Ping => assert_eq!(stream.ping_data(), data.as_slice()),
+                _ => assert_eq!(data2, data),
+            }
+        }
+
+        for limit in [1, 2, 3, 5, 14] {
+            for size in [1, 13, 14, 100] {
+                for n in [0, 1, 125, 126, 1000] {
+                    read(OpCode::Binary, n, limit, size);
+                }
+                read(OpCode::Ping, 125, limit, size);
+            }
+        }
+    }
+
     #[test]
     fn read_eof_from_stream() {
         fn read<R: RoleHelper>() {
@@ -216,6 +616,52 @@ mod test {
         read::<Server>();
     }
 
+    #[test]
+    fn read_truncated_from_stream() {
+        fn read<R: RoleHelper>(frame: &[u8], end: usize, limit: usize) {
+            let truncated = end != 0 && end != frame.len();
+            let io = |end| LimitReadWriter {
+                buf: frame[..end].to_vec(),
+                rlimit: limit,
+                wlimit: 0,
+                cursor: 0,
+            };
+
+            let mut stream = Stream::new(io(end), R::new());
+            let mut buf = vec![0; 32];
+            while stream.read(&mut buf).unwrap() != 0 || !stream.is_read_eof() {}
+            assert_eq!(stream.is_read_truncated(), truncated);
+
+            let mut buf = Vec::new();
+            let mut stream = Stream::new(io(end), R::new()).guard();
+            match stream.read_to_end(&mut buf) {
+                Ok(_) => assert!(!truncated),
+                Err(e) => {
+                    assert!(truncated);
+                    assert_eq!(e.kind(), ErrorKind::UnexpectedEof);
+                    // later reads fail as well
+                    let e = stream.read(&mut [0; 32]).unwrap_err();
+                    assert_eq!(e.kind(), ErrorKind::UnexpectedEof);
+                }
+            }
+        }
+
+        fn run<R1: RoleHelper, R2: RoleHelper>(limit: usize) {
+            let (data, _) = make_frame::<R1>(OpCode::Binary, 200);
+            let (ping, _) = make_frame::<R1>(OpCode::Ping, 100);
+            for frame in [data, ping] {
+                for end in [0, 1, 2, 3, 4, 5, 8, 9, 50, frame.len() - 1, frame.len()] {
+                    read::<R2>(&frame, end, limit);
+                }
+            }
+        }
+
+        for limit in [1, 3, 7, 16, 1000] {
+            run::<Client, Server>(limit);
+            run::<Server, Client>(limit);
+        }
+    }
+
     #[test]
     fn read_close_from_stream() {
         fn read<R1: RoleHelper, R2: RoleHelper>(limit: usize) {
@@ -248,6 +694,587 @@ mod test {
         }
     }
 
+    #[test]
+    fn read_huge_frame_from_stream() {
+        fn read<R1: RoleHelper, R2: RoleHelper>(limit: usize) {
+            let mut frame = make_head(OpCode::Binary, R1::new().mask_key(), 1 << 60);
+            frame.extend_from_slice(&[0u8; 1024]);
+
+            let io = LimitReadWriter {
+                buf: frame,
+                rlimit: limit,
+                wlimit: 0,
+                cursor: 0,
+            };
+
+            let mut buf = Vec::new();
+            let mut stream = Stream::new(io, R2::new()).guard();
+            stream.set_max_message_size(4096);
+
+            let e = stream.read_to_end(&mut buf).unwrap_err();
+            let e = e.into_inner().unwrap();
+            let e: &Error = e.downcast_ref().unwrap();
+            assert!(matches!(e, Error::Frame(FrameError::FrameTooLarge)));
+
+            // no payload is read
+            assert!(buf.is_empty());
+            assert!(buf.capacity() < 4096);
+        }
+
+        for limit in 1..=32 {
+            read::<Client, Server>(limit);
+            read::<Server, Client>(limit);
+        }
+    }
+
+    #[test]
+    fn read_huge_fragmented_message_from_stream() {
+        fn read<R1: RoleHelper, R2: RoleHelper>(
+            limit: usize,
+            fragments: usize,
+        ) -> (Vec<u8>, Result<usize>) {
+            // each fragment is under the limit
+            let mut frames = Vec::new();
+            for i in 0..fragments {
+                let opcode = if i == 0 {
+                    OpCode::Binary
+                } else {
+                    OpCode::Continue
+                };
+                let fin = if i == fragments - 1 { Fin::Y } else { Fin::N };
+                frames.extend(make_head_with_fin(fin, opcode, R1::new().mask_key(), 1024));
+                frames.extend_from_slice(&[0u8; 1024]);
+            }
+
+            let io = LimitReadWriter {
+                buf: frames,
+                rlimit: limit,
+                wlimit: 0,
+                cursor: 0,
+            };
+
+            let mut buf = Vec::new();
+            let mut stream = Stream::new(io, R2::new()).guard();
+            stream.set_max_message_size(4096);
+
+            let ret = stream.read_to_end(&mut buf);
+            (buf, ret)
+        }
+
+        for limit in [1, 7, 14, 100, 4096] {
+            // exactly the limit
+            let (buf, ret) = read::<Client, Server>(limit, 4);
+            assert_eq!(ret.unwrap(), 4096);
+            assert_eq!(buf.len(), 4096);
+
+            for fragments in [5, 64] {
+                let (buf, ret) = read::<Server, Client>(limit, fragments);
+                let e = ret.unwrap_err();
+                let e = e.into_inner().unwrap();
+                let e: &Error = e.downcast_ref().unwrap();
+                assert!(matches!(e, Error::Frame(FrameError::FrameTooLarge)));
+
+                // the exceeding fragment is not read
+                assert!(buf.len() <= 4096);
+            }
+        }
+    }
+
+    #[test]
+    fn read_oversized_ping_from_stream() {
+        fn read<R1: RoleHelper, R2: RoleHelper>(limit: usize, size: usize) {
+            // 200 bytes ping, with a 2-byte extended length
+            let (frame, _) = make_frame_with_mask(OpCode::Ping, R1::new().mask_key(), 200);
+
+            let io = LimitReadWriter {
+                buf: frame,
+                rlimit: limit,
+                wlimit: 0,
+                cursor: 0,
+            };
+
+            let mut stream = Stream::new(io, R2::new());
+            let mut buf = vec![0; size];
+
+            let e = loop {
+                match stream.read(&mut buf) {
+                    Ok(0) if stream.is_read_eof() => panic!("oversized ping is accepted"),
+                    Ok(n) => assert_eq!(n, 0),
+                    Err(e) => break e,
+                }
+            };
+            let e = e.into_inner().unwrap();
+            let e: &Error = e.downcast_ref().unwrap();
+            assert!(matches!(e, Error::Frame(FrameError::IllegalData)));
+
+            // no payload is stored
+            assert!(!stream.is_pinged());
+            assert_eq!(stream.pending_pings(), 0);
+        }
+
+        for limit in 1..=16 {
+            for size in [1, 4, 13, 14, 64, 256] {
+                read::<Client, Server>(limit, size);
+                read::<Server, Client>(limit, size);
+            }
+        }
+    }
+
+    #[test]
+    fn read_unsupported_from_stream() {
+        fn read<R1: RoleHelper, R2: RoleHelper>(opcode: OpCode, limit: usize) {
+            let (frame, _) = make_frame_with_mask(opcode, R1::new().mask_key(), 10);
+
+            let io = LimitReadWriter {
+                buf: frame,
+                rlimit: limit,
+                wlimit: 0,
+                cursor: 0,
+            };
+
+            let mut stream = Stream::new(io, R2::new());
+            let mut buf = vec![0; 32];
+
+            let e = loop {
+                match stream.read(&mut buf) {
+                    Ok(0) if stream.is_read_eof() => panic!("unsupported frame is accepted"),
+                    Ok(n) => assert_eq!(n, 0),
+                    Err(e) => break e,
+                }
+            };
+            let e = e.into_inner().unwrap();
+            let e: &Error = e.downcast_ref().unwrap();
+            assert!(
+                matches!(e, Error::Frame(FrameError::UnsupportedOpcode(b)) if *b == opcode as u8)
+            );
+        }
+
+        for limit in [1, 2, 100] {
+            read::<Client, Server>(OpCode::Text, limit);
+            read::<Server, Client>(OpCode::Text, limit);
+        }
+    }
+
+    #[test]
+    fn read_again_after_reject() {
+        fn read(frame: &[u8], prefix: bool, limit: usize, size: usize, expect: FrameError) {
+            let mut data = Vec::new();
+            if prefix {
+                data.extend(make_frame::<Client>(OpCode::Binary, 4).0);
+            }
+            data.extend_from_slice(frame);
+
+            let io = LimitReadWriter {
+                buf: data,
+                rlimit: limit,
+                wlimit: 0,
+                cursor: 0,
+            };
+
+            let mut stream = Stream::new(io, Server);
+            stream.set_max_message_size(10);
+            let mut buf = vec![0; size];
+
+            let is_expected = |e: std::io::Error| {
+                let e = e.into_inner().unwrap();
+                let e: &Error = e.downcast_ref().unwrap();
+                matches!(e, Error::Frame(e) if *e == expect)
+            };
+
+            let e = loop {
+                match stream.read(&mut buf) {
+                    Ok(0) if stream.is_read_eof() => panic!("illegal frame is accepted"),
+                    Ok(_) => {}
+                    Err(e) => break e,
+                }
+            };
+            assert!(is_expected(e));
+
+            // the rejection is sticky
+            for _ in 0..3 {
+                assert!(is_expected(stream.read(&mut buf).unwrap_err()));
+            }
+        }
+
+        let key = Client.mask_key();
+        let mut huge = vec![0x82, 0xff, 0, 0, 0, 0, 0, 1, 0, 0, 1, 2, 3, 4];
+        huge.extend_from_slice(&[0; 16]);
+        let text = make_frame_with_mask(OpCode::Text, key, 4).0;
+        let ping = make_frame_with_mask(OpCode::Ping, key, 200).0;
+        let cont = make_frame_with_mask(OpCode::Continue, key, 4).0;
+        let reserved = [0x83, 0x80, 1, 2, 3, 4];
+
+        for prefix in [false, true] {
+            for limit in [1, 3, 100] {
+                for size in [1, 4, 13, 14, 64] {
+                    read(&huge, prefix, limit, size, FrameError::FrameTooLarge);
+                    read(&text, prefix, limit, size, FrameError::UnsupportedOpcode(1));
+                    read(&ping, prefix, limit, size, FrameError::IllegalData);
+                    read(&cont, prefix, limit, size, FrameError::IllegalData);
+                    read(&reserved, prefix, limit, size, FrameError::IllegalOpCode);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn read_close_in_data_from_stream() {
+        fn read(frame: &[u8], limit: usize) -> (Vec<u8>, Result<Stream<LimitReadWriter, Client>>) {
+            let io = LimitReadWriter {
+                buf: frame.to_vec(),
+                rlimit: limit,
+                wlimit: 0,
+                cursor: 0,
+            };
+            let mut stream = Stream::new(io, Client::new());
+            let mut buf = vec![0; 32];
+            let mut data = Vec::new();
+            loop {
+                match stream.read(&mut buf) {
+                    Ok(0) if stream.is_read_end() => return (data, Ok(stream)),
+                    Ok(n) => data.extend_from_slice(&buf[..n]),
+                    Err(e) => return (data, Err(e)),
+                }
+            }
+        }
+
+        let close = make_head(OpCode::Close, Mask::None, 0);
+        for limit in [1, 2, 3, 100] {
+            // close within the declared length is payload,
+            // then the frame is truncated
+            let mut frame = make_head(OpCode::Binary, Mask::None, 10);
+            frame.extend([1, 2, 3, 4]);
+            frame.extend(&close);
+            let (data, stream) = read(&frame, limit);
+            let stream = stream.unwrap();
+            assert_eq!(data, [[1, 2, 3, 4].as_slice(), &close].concat());
+            assert!(stream.is_read_truncated());
+            assert!(!stream.is_read_close());
+
+            // extra payload is parsed as a frame head
+            let mut frame = make_head(OpCode::Binary, Mask::None, 2);
+            frame.extend([1, 2, 0x42, 0x00]);
+            frame.extend(&close);
+            let (data, stream) = read(&frame, limit);
+            let e = stream.map(|_| ()).unwrap_err().into_inner().unwrap();
+            let e: &Error = e.downcast_ref().unwrap();
+            assert!(matches!(e, Error::Frame(FrameError::IllegalFin)));
+            assert_eq!(data, [1, 2]);
+
+            // an unsupported frame right after the payload
+            let mut frame = make_head(OpCode::Binary, Mask::None, 2);
+            frame.extend([1, 2]);
+            frame.extend(make_head(OpCode::Text, Mask::None, 0));
+            let (data, stream) = read(&frame, limit);
+            let e = stream.map(|_| ()).unwrap_err().into_inner().unwrap();
+            let e: &Error = e.downcast_ref().unwrap();
+            assert!(matches!(
+                e,
+                Error::Frame(FrameError::UnsupportedOpcode(0x01))
+            ));
+            assert_eq!(data, [1, 2]);
+
+            // close right after the payload
+            let mut frame = make_head(OpCode::Binary, Mask::None, 4);
+            frame.extend([1, 2, 3, 4]);
+            frame.extend(&close);
+            let (data, stream) = read(&frame, limit);
+            assert!(stream.unwrap().is_read_close());
+            assert_eq!(data, [1, 2, 3, 4]);
+        }
+    }
+
+    #[test]
+    fn read_strict_mask_from_stream() {
+        fn read<R: RoleHelper>(strict: bool, frame: &[u8]) -> Result<Vec<u8>> {
+            let mut stream = Stream::new(frame, R::new()).guard();
+            stream.set_strict_mask(strict);
+            let mut buf = Vec::new();
+            stream.read_to_end(&mut buf).map(|_| buf)
+        }
+
+        let (masked, data) = make_masked_frame(OpCode::Binary, [1, 2, 3, 4], 100);
+        let (unmasked, _) = make_frame_with_mask(OpCode::Binary, Mask::None, 100);
+        let ping = make_head(OpCode::Ping, Mask::None, 0);
+
+        let masked_ping = make_head(OpCode::Ping, Mask::Key([1, 2, 3, 4]), 0);
+
+        for strict in [false, true] {
+            assert_eq!(read::<Server>(strict, &masked).unwrap(), data);
+            assert!(read::<Server>(strict, &masked_ping).is_ok());
+            assert!(read::<Client>(strict, &unmasked).is_ok());
+            assert!(read::<Client>(strict, &ping).is_ok());
+            assert!(read::<StandardClient>(strict, &unmasked).is_ok());
+        }
+
+        assert!(read::<Server>(false, &unmasked).is_ok());
+        for frame in [&unmasked, &ping] {
+            let e = read::<Server>(true, frame)
+                .unwrap_err()
+                .into_inner()
+                .unwrap();
+            let e: &Error = e.downcast_ref().unwrap();
+            assert!(matches!(e, Error::Frame(FrameError::UnmaskedClientFrame)));
+        }
+
+        // strict, except for the mask
+        let mut stream = Stream::new(unmasked.as_slice(), Server).guard();
+        stream.set_strict(true);
+        stream.allow_unmasked();
+        assert!(!stream.is_strict_mask() && stream.is_strict_control());
+        assert!(stream.read_to_end(&mut Vec::new()).is_ok());
+
+        assert_eq!(read::<Client>(false, &masked).unwrap(), data);
+        for frame in [&masked, &masked_ping] {
+            for e in [
+                read::<Client>(true, frame).unwrap_err(),
+                read::<StandardClient>(true, frame).unwrap_err(),
+            ] {
+                let e = e.into_inner().unwrap();
+                let e: &Error = e.downcast_ref().unwrap();
+                assert!(matches!(e, Error::Frame(FrameError::MaskedServerFrame)));
+            }
+        }
+    }
+
+    #[test]
+    fn read_strict_control_from_stream() {
+        fn read(strict: bool, frame: &[u8]) -> Result<Vec<u8>> {
+            let mut stream = Stream::new(frame, Client::new()).guard();
+            stream.set_strict_control(strict);
+            let mut buf = Vec::new();
+            stream.read_to_end(&mut buf).map(|_| buf)
+        }
+
+        let fragmented_ping = make_head_with_fin(Fin::N, OpCode::Ping, Mask::None, 0);
+        let short_close = [0x88, 0x01, 0x03];
+        let reserved_close = [0x88, 0x02, 0x03, 0xed];
+        let close = [0x88, 0x02, 0x03, 0xe8];
+
+        for frame in [&fragmented_ping[..], &short_close, &reserved_close] {
+            assert!(read(false, frame).is_ok());
+            let e = read(true, frame).unwrap_err().into_inner().unwrap();
+            let e: &Error = e.downcast_ref().unwrap();
+            assert!(matches!(e, Error::Frame(FrameError::IllegalData)));
+        }
+        assert!(read(true, &close).is_ok());
+    }
+
+    #[test]
+    fn read_strict_length_from_stream() {
+        fn read(strict: bool, limit: usize) {
+            // 16-bit length of 5
+            let frame = [0x82, 0x7e, 0x00, 0x05, 1, 2, 3, 4, 5];
+
+            let io = LimitReadWriter {
+                buf: frame.to_vec(),
+                rlimit: limit,
+                wlimit: 0,
+                cursor: 0,
+            };
+
+            let mut stream = Stream::new(io, Client::new());
+            stream.set_strict_length(strict);
+            let mut buf = vec![0; 32];
+
+            let mut data = Vec::new();
+            let e = loop {
+                match stream.read(&mut buf) {
+                    Ok(0) if stream.is_read_eof() => break None,
+                    Ok(n) => data.extend_from_slice(&buf[..n]),
+                    Err(e) => break Some(e),
+                }
+            };
+
+            if !strict {
+                assert!(e.is_none());
+                assert_eq!(data, [1, 2, 3, 4, 5]);
+                return;
+            }
+            let e = e.unwrap().into_inner().unwrap();
+            let e: &Error = e.downcast_ref().unwrap();
+            assert!(matches!(e, Error::Frame(FrameError::IllegalLength)));
+            assert!(data.is_empty());
+        }
+
+        for limit in [1, 2, 3, 100] {
+            read(false, limit);
+            read(true, limit);
+        }
+    }
+
+    #[test]
+    fn read_text_from_stream() {
+        fn frame(fin: Fin, opcode: OpCode, mask: Mask, data: &[u8]) -> Vec<u8> {
+            let mut frame = make_head_with_fin(fin, opcode, mask, data.len());
+            let mut data = data.to_vec();
+            if let Mask::Key(key) = mask {
+                apply_mask4(key, &mut data);
+            }
+            frame.extend(data);
+            frame
+        }
+
+        fn read<R: RoleHelper>(
+            input: &[u8],
+            limit: usize,
+        ) -> (Vec<u8>, Result<Stream<LimitReadWriter, R>>) {
+            let io = LimitReadWriter {
+                buf: input.to_vec(),
+                rlimit: limit,
+                wlimit: 0,
+                cursor: 0,
+            };
+            let mut stream = Stream::builder(io, R::new()).accept_text(true).build();
+            let mut buf = vec![0; 32];
+            let mut data = Vec::new();
+            loop {
+                match stream.read(&mut buf) {
+                    Ok(0) if stream.is_read_end() => return (data, Ok(stream)),
+                    Ok(n) => data.extend_from_slice(&buf[..n]),
+                    Err(e) => return (data, Err(e)),
+                }
+            }
+        }
+
+        fn text<R: RoleHelper>(mask: Mask, limit: usize) {
+            // U+1F600, f0 9f 98 80
+            let emoji = "\u{1f600}".as_bytes();
+            let close = frame(Fin::Y, OpCode::Close, mask, &[]);
+
+            // a codepoint split across two continuation frames
+            let input = [
+                frame(Fin::N, OpCode::Text, mask, b"hi"),
+                frame(Fin::N, OpCode::Continue, mask, &emoji[..2]),
+                frame(Fin::Y, OpCode::Continue, mask, &emoji[2..]),
+                frame(Fin::Y, OpCode::Binary, mask, b"\xff"),
+                close.clone(),
+            ]
+            .concat();
+            let (data, stream) = read::<R>(&input, limit);
+            assert!(stream.unwrap().is_read_close());
+            assert_eq!(data, [b"hi", emoji, b"\xff"].concat());
+
+            // a text message ends with a partial codepoint
+            let input = [
+                frame(Fin::N, OpCode::Text, mask, b"hi"),
+                frame(Fin::Y, OpCode::Continue, mask, &emoji[..2]),
+                close.clone(),
+            ]
+            .concat();
+            let (_, stream) = read::<R>(&input, limit);
+            let e = stream.map(|_| ()).unwrap_err().into_inner().unwrap();
+            let e: &Error = e.downcast_ref().unwrap();
+            assert!(matches!(e, Error::Frame(FrameError::InvalidUtf8)));
+
+            // an invalid byte stops reading
+            let input = [frame(Fin::Y, OpCode::Text, mask, b"hi\xffhi"), close].concat();
+            let io = LimitReadWriter {
+                buf: input,
+                rlimit: limit,
+                wlimit: 0,
+                cursor: 0,
+            };
+            let mut stream = Stream::builder(io, R::new()).accept_text(true).build();
+            let mut buf = vec![0; 32];
+            let e = loop {
+                match stream.read(&mut buf) {
+                    Ok(0) if stream.is_read_end() => panic!("invalid text is accepted"),
+                    Ok(_) => continue,
+                    Err(e) => break e,
+                }
+            };
+            let e = e.into_inner().unwrap();
+            let e: &Error = e.downcast_ref().unwrap();
+            assert!(matches!(e, Error::Frame(FrameError::InvalidUtf8)));
+            assert!(stream.is_read_truncated());
+            assert_eq!(stream.read(&mut buf).unwrap(), 0);
+        }
+
+        for limit in [1, 2, 3, 100] {
+            text::<Client>(Mask::None, limit);
+            text::<Server>(Mask::Key([1, 2, 3, 4]), limit);
+        }
+    }
+
+    #[test]
+    fn read_fragmented_from_stream() {
+        fn frame<R: RoleHelper>(fin: Fin, opcode: OpCode, n: usize) -> Vec<u8> {
+            let mut frame = make_head_with_fin(fin, opcode, R::new().mask_key(), n);
+            frame.extend(std::iter::repeat_n(0u8, n));
+            frame
+        }
+
+        fn read<R: RoleHelper>(frames: &[Vec<u8>], limit: usize) -> Result<usize> {
+            let io = LimitReadWriter {
+                buf: frames.concat(),
+                rlimit: limit,
+                wlimit: 0,
+                cursor: 0,
+            };
+
+            let mut buf = Vec::new();
+            let mut stream = Stream::new(io, R::new()).guard();
+            stream.read_to_end(&mut buf)
+        }
+
+        fn illegal<R: RoleHelper>(frames: &[Vec<u8>], limit: usize) {
+            let e = read::<R>(frames, limit).unwrap_err();
+            let e = e.into_inner().unwrap();
+            let e: &Error = e.downcast_ref().unwrap();
+            assert!(matches!(e, Error::Frame(FrameError::IllegalData)));
+        }
+
+        fn run<R1: RoleHelper, R2: RoleHelper>(limit: usize) {
+            // fragmented message, interleaved with a ping
+            let n = read::<R2>(
+                &[
+                    frame::<R1>(Fin::N, OpCode::Binary, 10),
+                    frame::<R1>(Fin::Y, OpCode::Ping, 4),
+                    frame::<R1>(Fin::N, OpCode::Continue, 20),
+                    frame::<R1>(Fin::Y, OpCode::Continue, 30),
+                    frame::<R1>(Fin::Y, OpCode::Binary, 40),
+                ],
+                limit,
+            )
+            .unwrap();
+            assert_eq!(n, 100);
+
+            // continue without an open message
+            illegal::<R2>(&[frame::<R1>(Fin::Y, OpCode::Continue, 10)], limit);
+            illegal::<R2>(
+                &[
+                    frame::<R1>(Fin::Y, OpCode::Binary, 10),
+                    frame::<R1>(Fin::N, OpCode::Continue, 10),
+                ],
+                limit,
+            );
+
+            // new message before the last one ends
+            illegal::<R2>(
+                &[
+                    frame::<R1>(Fin::N, OpCode::Binary, 10),
+                    frame::<R1>(Fin::Y, OpCode::Binary, 10),
+                ],
+                limit,
+            );
+            illegal::<R2>(
+                &[
+                    frame::<R1>(Fin::N, OpCode::Binary, 10),
+                    frame::<R1>(Fin::N, OpCode::Continue, 10),
+                    frame::<R1>(Fin::Y, OpCode::Ping, 0),
+                    frame::<R1>(Fin::N, OpCode::Binary, 10),
+                ],
+                limit,
+            );
+        }
+
+        for limit in [1, 2, 3, 7, 16, 64, usize::MAX] {
+            run::<Client, Server>(limit);
+            run::<Server, Client>(limit);
+        }
+    }
+
     #[test]
     fn read_ping_from_stream() {
         fn read<R1: RoleHelper, R2: RoleHelper>(n: usize, limit: usize) {
@@ -277,6 +1304,57 @@ mod test {
         }
     }
 
+    #[test]
+    fn read_pong_from_stream() {
+        fn read<R1: RoleHelper, R2: RoleHelper>(n: usize, strict: bool, size: usize, limit: usize) {
+            let (f1, d1) = make_frame::<R1>(OpCode::Binary, 3);
+            let (f2, _) = make_frame::<R1>(OpCode::Pong, n);
+            let (f3, d2) = make_frame::<R1>(OpCode::Binary, 5);
+            let (f4, _) = make_frame::<R1>(OpCode::Pong, 0);
+            let (f5, _) = make_frame::<R1>(OpCode::Close, 0);
+
+            let io = LimitReadWriter {
+                buf: [f1, f2, f3, f4, f5].concat(),
+                rlimit: limit,
+                wlimit: 0,
+                cursor: 0,
+            };
+
+            let mut stream = Stream::new(io, R2::new());
+            stream.set_strict(strict);
+
+            let mut data = Vec::new();
+            let mut buf = vec![0; size];
+            while !stream.is_read_end() {
+                let n = stream.read(&mut buf).unwrap();
+                data.extend_from_slice(&buf[..n]);
+            }
+
+            assert!(stream.is_read_close());
+            assert!(stream.ping_data().is_empty());
+            assert_eq!(data, [d1, d2].concat());
+        }
+
+        for n in [0, 1, 13, 14, 125] {
+            for size in [1, 4, 13, 14, 200] {
+                for limit in [1, 3, 16, 200] {
+                    for strict in [false, true] {
+                        read::<Client, Server>(n, strict, size, limit);
+                        read::<Server, Client>(n, strict, size, limit);
+                    }
+                }
+            }
+        }
+
+        // a pong must not have extended data
+        let (frame, _) = make_frame::<Client>(OpCode::Pong, 126);
+        let mut stream = Stream::new(frame.as_slice(), Server::new());
+        let e = stream.read(&mut [0; 32]).unwrap_err();
+        let e = e.into_inner().unwrap();
+        let e: &Error = e.downcast_ref().unwrap();
+        assert!(matches!(e, Error::Frame(FrameError::IllegalData)));
+    }
+
     #[test]
     fn read_multi_frame_from_stream() {
         fn read<R1: RoleHelper, R2: RoleHelper>(n: usize, step: usize, limit: usize) {
@@ -361,4 +1439,114 @@ mod test {
             }
         }
     }
+
+    #[test]
+    fn read_to_end_ping_only_stream() {
+        // an open connection, which has nothing more to read
+        struct Pending(Vec<u8>);
+
+        impl Read for Pending {
+            fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+                if self.0.is_empty() {
+                    return Err(ErrorKind::WouldBlock.into());
+                }
+                let n = std::cmp::min(buf.len(), self.0.len());
+                buf[..n].copy_from_slice(&self.0[..n]);
+                self.0.drain(..n);
+                Ok(n)
+            }
+        }
+
+        let (data_frame, data) = make_frame::<Client>(OpCode::Binary, 100);
+        let mut input = data_frame;
+        for len in [0, 4, 8] {
+            input.extend(make_frame::<Client>(OpCode::Ping, len).0);
+        }
+
+        let mut stream = Stream::new(Pending(input), Server).guard();
+        let mut buf = Vec::new();
+        let e = stream.read_to_end(&mut buf).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::WouldBlock);
+        assert_eq!(buf, data);
+        assert!(stream.is_pinged());
+        assert_eq!(stream.ping_data().len(), 8);
+        assert!(!stream.is_read_end());
+
+        // resume after more frames arrive
+        let (frame, data2) = make_frame::<Client>(OpCode::Binary, 10);
+        stream.as_mut().0 = [frame, make_frame::<Client>(OpCode::Close, 0).0].concat();
+        assert_eq!(stream.read_to_end(&mut buf).unwrap(), 10);
+        assert_eq!(buf, [data, data2].concat());
+        assert!(stream.is_read_close());
+    }
+
+    #[test]
+    fn read_message_frame_from_stream() {
+        fn frame(fin: Fin, opcode: OpCode, mask: Mask, len: usize) -> (Vec<u8>, Vec<u8>) {
+            let data = make_data(len);
+            let mut payload = data.clone();
+            if let Mask::Key(key) = mask {
+                apply_mask4(key, &mut payload);
+            }
+            (
+                [make_head_with_fin(fin, opcode, mask, len), payload].concat(),
+                data,
+            )
+        }
+
+        fn read<R: RoleHelper>(mask: Mask, limit: usize) {
+            let frames = [
+                (Fin::N, OpCode::Binary, 3),
+                (Fin::Y, OpCode::Ping, 5),
+                (Fin::N, OpCode::Continue, 0),
+                (Fin::Y, OpCode::Continue, 20000),
+                (Fin::Y, OpCode::Binary, 10),
+                (Fin::Y, OpCode::Text, 0),
+                (Fin::N, OpCode::Text, 0),
+                (Fin::Y, OpCode::Ping, 0),
+                (Fin::Y, OpCode::Continue, 0),
+                (Fin::Y, OpCode::Binary, 0),
+                (Fin::Y, OpCode::Close, 0),
+            ];
+            let (buf, data): (Vec<_>, Vec<_>) = frames
+                .iter()
+                .map(|&(fin, opcode, len)| frame(fin, opcode, mask, len))
+                .unzip();
+
+            let io = LimitReadWriter {
+                buf: buf.concat(),
+                rlimit: limit,
+                wlimit: 0,
+                cursor: 0,
+            };
+            let mut stream = Stream::new(WouldBlockReadWriter::new(io), R::new());
+            stream.set_accept_text(true);
+
+            for (&(fin, opcode, _), data) in frames.iter().zip(data.iter()) {
+                if opcode == OpCode::Ping {
+                    continue;
+                }
+                let mut buf = vec![0xff];
+                let res = loop {
+                    match stream.read_message_frame(&mut buf) {
+                        Ok(x) => break x,
+                        Err(e) => assert_eq!(e.kind(), ErrorKind::WouldBlock),
+                    }
+                };
+                assert_eq!(res, (opcode, fin == Fin::Y));
+                assert_eq!(buf[0], 0xff);
+                assert_eq!(&buf[1..], data);
+            }
+            assert_eq!(stream.ping_data(), data[7].as_slice());
+        }
+
+        for limit in [1, 2, 3, 7, 16, 100, 0x10000] {
+            read::<Client>(Mask::None, limit);
+            read::<Server>(Mask::Key(new_mask_key()), limit);
+        }
+
+        let mut stream = Stream::new([0_u8; 0].as_slice(), Server::new());
+        let err = stream.read_message_frame(&mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+    }
 }
@@ -1,13 +1,132 @@
-use super::{Stream, RoleHelper};
+use super::{Stream, State, RoleHelper};
 use std::io::Result;
 use std::net::TcpStream;
 
+impl<IO, Role: RoleHelper> Stream<IO, Role> {
+    /// Creates a new handle with the IO source cloned by the provided closure,
+    /// e.g. [`TcpStream::try_clone`].
+    ///
+    /// Role and options are copied, while read and write states are fresh.
+    ///
+    /// Caution: **states are not shared among instances!**
+    pub fn try_clone_with<F>(&self, clone_io: F) -> Result<Self>
+    where
+        F: FnOnce(&IO) -> Result<IO>,
+    {
+        let io = clone_io(&self.io)?;
+        let mut stream = Self::new(io, self.state.role);
+        stream.state.max_message_size = self.state.max_message_size;
+        stream.state.strict_length = self.state.strict_length;
+        stream.state.strict_mask = self.state.strict_mask;
+        stream.state.strict_control = self.state.strict_control;
+        stream.state.accept_text = self.state.accept_text;
+        stream.state.negotiated = self.state.negotiated.clone();
+        stream.state.write_opcode = self.state.write_opcode;
+        stream.set_auto_pong(self.is_auto_pong());
+        Ok(stream)
+    }
+}
+
 impl<Role: RoleHelper> Stream<TcpStream, Role> {
     /// Creates a new independently owned handle to the underlying IO source.
     ///
     /// Caution: **states are not shared among instances!**
-    pub fn try_clone(&self) -> Result<Self> {
-        let io = self.io.try_clone()?;
-        Ok(Self::new(io, self.role))
+    pub fn try_clone(&self) -> Result<Self> { self.try_clone_with(TcpStream::try_clone) }
+}
+
+/// Clone the IO source, role and all states,
+/// except the frame hook, and the timer of the automatic ping, which restarts.
+///
+/// Caution: **states are not shared among instances!**
+/// An in-flight partial frame is copied, each instance continues it separately.
+impl<IO: Clone, Role: Clone, Guard> Clone for Stream<IO, Role, Guard> {
+    fn clone(&self) -> Self {
+        Stream {
+            io: self.io.clone(),
+            state: self.state.clone(),
+            __marker: self.__marker,
+        }
+    }
+}
+
+impl<Role: Clone> Clone for State<Role> {
+    fn clone(&self) -> Self {
+        State {
+            role: self.role.clone(),
+            read_state: self.read_state.clone(),
+            write_state: self.write_state.clone(),
+            heartbeat: self.heartbeat.clone(),
+            leftover: self.leftover.clone(),
+            read_fragmented: self.read_fragmented,
+            read_opcode: self.read_opcode,
+            read_message_len: self.read_message_len,
+            read_utf8: self.read_utf8,
+            read_close: self.read_close.clone(),
+            write_fragmented: self.write_fragmented,
+            write_opcode: self.write_opcode,
+            max_message_size: self.max_message_size,
+            strict_length: self.strict_length,
+            strict_mask: self.strict_mask,
+            strict_control: self.strict_control,
+            accept_text: self.accept_text,
+            negotiated: self.negotiated.clone(),
+            close_store: self.close_store.clone(),
+            drop_close: self.drop_close.clone(),
+            auto_pong: self.auto_pong.clone(),
+            #[cfg(feature = "async")]
+            keepalive: self.keepalive.clone(),
+            #[cfg(feature = "metrics")]
+            stats: self.stats,
+            #[cfg(feature = "frame_hook")]
+            frame_hook: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::test::*;
+    use crate::frame::OpCode;
+    use crate::role::*;
+    use std::io::{Read, Write};
+
+    #[test]
+    fn clone_stream() {
+        fn clone<R: RoleHelper>() {
+            let (frame, data) = make_frame::<R>(OpCode::Binary, 10);
+
+            // partially read frame
+            let mut stream = Stream::new(frame.as_slice(), R::new());
+            stream.set_max_message_size(100);
+            let mut buf = [0_u8; 4];
+            while stream.read(&mut buf).unwrap() == 0 {}
+            assert_eq!(&buf, &data[..4]);
+
+            let mut stream2 = stream.clone();
+            assert_eq!(stream2.max_message_size(), 100);
+            assert!(!stream2.is_read_end());
+
+            let mut buf = [0_u8; 6];
+            assert_eq!(stream.read(&mut buf).unwrap(), 6);
+            assert_eq!(&buf, &data[4..]);
+            assert_eq!(stream2.read(&mut buf).unwrap(), 6);
+            assert_eq!(&buf, &data[4..]);
+
+            // fresh states
+            let mut stream = Stream::builder(Vec::new(), R::new())
+                .auto_pong(true)
+                .text(true)
+                .build();
+            assert_eq!(stream.write(b"hello").unwrap(), 5);
+            let mut stream2 = stream.try_clone_with(|_| Ok(Vec::new())).unwrap();
+            assert!(stream2.is_auto_pong());
+            assert_eq!(stream2.write_opcode(), OpCode::Text);
+            assert_eq!(stream2.write(b"hello").unwrap(), 5);
+            assert_eq!(stream.as_ref(), stream2.as_ref());
+        }
+
+        clone::<Client>();
+        clone::<Server>();
     }
 }
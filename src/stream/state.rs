@@ -1,18 +1,40 @@
-use super::Stream;
+use super::{Stream, State};
 
-use crate::frame::Mask;
+use crate::frame::{Mask, OpCode};
 use crate::bleed::Store;
 
 /// Store incomplete frame head.
+///
+/// This is sized for the longest head(2 + 8 + 4) regardless of the role,
+/// since a peer may send an unexpected mask key, and a read state is
+/// no smaller than [`ReadState::ProcessBuf`] anyway.
 pub(super) type HeadStore = Store<14>;
 
+/// Max payload length of a control frame, 125.
+///
+/// [RFC-6455 Section 5.5](https://datatracker.ietf.org/doc/html/rfc6455#section-5.5)
+pub(super) const MAX_CONTROL_LEN: usize = 125;
+
 /// Store the most recent ping.
-pub(super) type PingStore = Store<125>;
+pub(super) type PingStore = Store<MAX_CONTROL_LEN>;
+
+/// Store the payload of a close frame in strict mode,
+/// which is validated once complete.
+pub(super) type ClosePayload = Store<MAX_CONTROL_LEN>;
+
+/// Store a control frame being written, a pong or a close,
+/// see [`Stream::set_auto_pong`] and [`Stream::close`].
+pub(super) type CtrlStore = Store<{ 14 + MAX_CONTROL_LEN }>;
 
-#[derive(Debug)]
+/// Max number of completed pings waiting to be answered.
+pub(super) const MAX_PENDING_PINGS: usize = 2;
+
+#[derive(Debug, Clone)]
 pub(super) struct HeartBeat {
     pub ping_store: PingStore,
     pub is_complete: bool,
+    // number of completed pings, wrapping
+    pub completed: u8,
 }
 
 impl HeartBeat {
@@ -21,28 +43,155 @@ impl HeartBeat {
         Self {
             ping_store: PingStore::new(),
             is_complete: false,
+            completed: 0,
+        }
+    }
+
+    /// Mark the most recent ping as complete.
+    #[inline]
+    pub fn complete(&mut self) {
+        self.is_complete = true;
+        self.completed = self.completed.wrapping_add(1);
+    }
+}
+
+/// Pings waiting to be answered and the pong being written,
+/// only allocated with auto pong, see [`Stream::set_auto_pong`].
+#[derive(Debug, Clone)]
+pub(super) struct AutoPong {
+    pub pong_store: CtrlStore,
+    pub pending: [PingStore; MAX_PENDING_PINGS],
+    pub pending_len: u8,
+    pub dropped: usize,
+}
+
+impl AutoPong {
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            pong_store: CtrlStore::new(),
+            pending: [PingStore::new(); MAX_PENDING_PINGS],
+            pending_len: 0,
+            dropped: 0,
+        }
+    }
+
+    /// Queue a completed ping.
+    /// A ping beyond the bound is dropped and counted.
+    #[inline]
+    pub fn push(&mut self, ping: &PingStore) {
+        if (self.pending_len as usize) < MAX_PENDING_PINGS {
+            self.pending[self.pending_len as usize] = *ping;
+            self.pending_len += 1;
+        } else {
+            self.dropped += 1;
         }
     }
+
+    /// Remove the oldest pending ping.
+    #[inline]
+    pub fn pop(&mut self) -> bool {
+        if self.pending_len == 0 {
+            return false;
+        }
+        self.pending.copy_within(1.., 0);
+        self.pending_len -= 1;
+        true
+    }
+
+    /// Clear pending pings, along with the counter.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.pending_len = 0;
+        self.dropped = 0;
+    }
+}
+
+/// Data read past the handshake, consumed before reading from IO.
+#[derive(Debug, Clone)]
+pub(super) struct Leftover {
+    buf: Vec<u8>,
+    pos: usize,
+}
+
+impl Leftover {
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            buf: Vec::new(),
+            pos: 0,
+        }
+    }
+
+    #[inline]
+    pub fn new_with_data(buf: Box<[u8]>) -> Self {
+        Self {
+            buf: buf.into_vec(),
+            pos: 0,
+        }
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool { self.pos == self.buf.len() }
+
+    #[inline]
+    pub fn len(&self) -> usize { self.buf.len() - self.pos }
+
+    /// Get unread data.
+    #[inline]
+    pub fn into_vec(mut self) -> Vec<u8> {
+        self.buf.drain(..self.pos);
+        self.buf
+    }
+
+    /// Copy data to the provided buffer, return the number of copied bytes.
+    #[inline]
+    pub fn read(&mut self, buf: &mut [u8]) -> usize {
+        let n = std::cmp::min(buf.len(), self.buf.len() - self.pos);
+        buf[..n].copy_from_slice(&self.buf[self.pos..self.pos + n]);
+        self.pos += n;
+        // release memory
+        if self.is_empty() {
+            *self = Self::new();
+        }
+        n
+    }
 }
 
 /// Read state.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub(super) enum ReadState {
     ReadHead(HeadStore),
     ReadData {
         next: u64,
         mask: Mask,
+        // number of read bytes modulo 4
+        phase: u8,
+        // Binary or Continue
+        opcode: OpCode,
     },
     ReadPing {
         next: u8,
         mask: Mask,
     },
+    // payload of a pong, which is discarded
+    ReadPong {
+        next: u8,
+    },
+    // payload of a close frame in strict mode
+    ReadClose {
+        next: u8,
+        mask: Mask,
+    },
     ProcessBuf {
         beg: usize,
         end: usize,
         processed: usize,
     },
-    Eof,
+    Eof {
+        // EOF occurs in the middle of a frame
+        truncated: bool,
+    },
     Close,
 }
 
@@ -53,9 +202,11 @@ impl ReadState {
 
 /// Write state.
 #[allow(clippy::enum_variant_names)]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub(super) enum WriteState {
     WriteHead(HeadStore),
+    // payload is masked as a whole before the head is built,
+    // so there is no mask phase to track
     WriteData(u64),
     WriteZero,
 }
@@ -69,41 +220,310 @@ impl WriteState {
 impl<IO, Role, Guard> Stream<IO, Role, Guard> {
     /// Check if a `Ping` frame is received.
     #[inline]
-    pub const fn is_pinged(&self) -> bool { !self.heartbeat.ping_store.is_empty() }
+    pub const fn is_pinged(&self) -> bool { !self.state.heartbeat.ping_store.is_empty() }
 
     /// Check if a `Ping` frame is completely read.
     #[inline]
-    pub const fn is_ping_completed(&self) -> bool { self.heartbeat.is_complete }
+    pub const fn is_ping_completed(&self) -> bool { self.state.heartbeat.is_complete }
 
     /// Get the most recent ping.
     #[inline]
-    pub const fn ping_data(&self) -> &[u8] { self.heartbeat.ping_store.read() }
+    pub const fn ping_data(&self) -> &[u8] { self.state.heartbeat.ping_store.read() }
+
+    /// Get the number of completed pings waiting to be answered,
+    /// always `0` unless [auto pong](Self::set_auto_pong) is enabled.
+    ///
+    /// At most 2 pings are queued, the following ones are dropped
+    /// until the queue is popped, see [`dropped_pings`](Self::dropped_pings).
+    #[inline]
+    pub const fn pending_pings(&self) -> usize {
+        match &self.state.auto_pong {
+            Some(auto_pong) => auto_pong.pending_len as usize,
+            None => 0,
+        }
+    }
+
+    /// Get the oldest pending ping.
+    #[inline]
+    pub const fn pending_ping_data(&self) -> Option<&[u8]> {
+        match &self.state.auto_pong {
+            Some(auto_pong) if auto_pong.pending_len != 0 => Some(auto_pong.pending[0].read()),
+            _ => None,
+        }
+    }
+
+    /// Get the number of completed pings dropped since the queue was full,
+    /// always `0` unless [auto pong](Self::set_auto_pong) is enabled.
+    #[inline]
+    pub const fn dropped_pings(&self) -> usize {
+        match &self.state.auto_pong {
+            Some(auto_pong) => auto_pong.dropped,
+            None => 0,
+        }
+    }
 
     /// Check if `EOF` is reached.
     #[inline]
-    pub const fn is_read_eof(&self) -> bool { matches!(&self.read_state, ReadState::Eof) }
+    pub const fn is_read_eof(&self) -> bool { self.state.is_read_eof() }
+
+    /// Check if `EOF` is reached in the middle of a frame,
+    /// i.e. a frame head or payload is partially read.
+    #[inline]
+    pub const fn is_read_truncated(&self) -> bool { self.state.is_read_truncated() }
 
     /// Check if a `Close` frame is received.
     #[inline]
-    pub const fn is_read_close(&self) -> bool { matches!(&self.read_state, ReadState::Close) }
+    pub const fn is_read_close(&self) -> bool { self.state.is_read_close() }
 
     /// Check if a `Close` frame is received or `EOF` is reached.
     #[inline]
-    pub const fn is_read_end(&self) -> bool { self.is_read_eof() || self.is_read_close() }
+    pub const fn is_read_end(&self) -> bool { self.state.is_read_end() }
 
     /// Check if a `WriteZero` error occurred.
     #[inline]
-    pub const fn is_write_zero(&self) -> bool { matches!(&self.write_state, WriteState::WriteZero) }
+    pub const fn is_write_zero(&self) -> bool { self.state.is_write_zero() }
 
-    /// Check if a frame head is partially read.
+    /// Check if a `Close` frame is completely written, see [`Stream::close`].
     #[inline]
-    pub const fn is_read_partial_head(&self) -> bool {
-        matches!(&self.read_state, ReadState::ReadHead(..))
+    pub const fn is_write_close(&self) -> bool { self.state.is_write_close() }
+
+    /// Get the number of payload bytes left to write in the current frame,
+    /// or `0` if no frame is partially written.
+    ///
+    /// A frame head which is partially written is not counted.
+    #[inline]
+    pub const fn write_remaining(&self) -> u64 {
+        match &self.state.write_state {
+            WriteState::WriteData(next) => *next,
+            _ => 0,
+        }
     }
 
+    /// Check if an outgoing message is not finished,
+    /// see [`Stream::write_fragment`].
+    #[inline]
+    pub const fn is_write_fragmented(&self) -> bool { self.state.write_fragmented }
+
+    /// Check if a frame head is partially read.
+    #[inline]
+    pub const fn is_read_partial_head(&self) -> bool { self.state.is_read_partial_head() }
+
     /// Check if frame head is partially written.
     #[inline]
     pub const fn is_write_partial_head(&self) -> bool {
-        matches!(&self.write_state, WriteState::WriteHead(..))
+        matches!(&self.state.write_state, WriteState::WriteHead(..))
+    }
+}
+
+/// Check status, shared by [`Stream`] and the pinned paths,
+/// where only the states are borrowed.
+impl<Role> State<Role> {
+    #[inline]
+    pub(super) const fn is_read_eof(&self) -> bool {
+        matches!(&self.read_state, ReadState::Eof { .. })
+    }
+
+    #[inline]
+    pub(super) const fn is_read_truncated(&self) -> bool {
+        matches!(&self.read_state, ReadState::Eof { truncated: true })
+    }
+
+    #[inline]
+    pub(super) const fn is_read_close(&self) -> bool {
+        matches!(&self.read_state, ReadState::Close)
+    }
+
+    #[inline]
+    pub(super) const fn is_read_end(&self) -> bool { self.is_read_eof() || self.is_read_close() }
+
+    #[inline]
+    pub(super) const fn is_read_partial_head(&self) -> bool {
+        matches!(&self.read_state, ReadState::ReadHead(..))
+    }
+
+    #[inline]
+    pub(super) const fn is_write_zero(&self) -> bool {
+        matches!(&self.write_state, WriteState::WriteZero)
+    }
+
+    /// Check if neither a data frame nor a pong is partially written,
+    /// and no close frame is staged, so that another frame could be sent.
+    #[cfg(feature = "async")]
+    #[inline]
+    pub(super) const fn is_write_boundary(&self) -> bool {
+        let is_pong_done = match &self.auto_pong {
+            Some(auto_pong) => auto_pong.pong_store.rd_left() == 0,
+            None => true,
+        };
+        matches!(&self.write_state, WriteState::WriteHead(head) if head.is_empty())
+            && is_pong_done
+            && self.close_store.is_none()
+    }
+
+    /// Check if a close frame is partially or completely written,
+    /// after which no other frame is written.
+    #[inline]
+    pub(super) const fn is_close_started(&self) -> bool {
+        matches!(&self.close_store, Some(close_store) if close_store.rd_pos() != 0)
+    }
+
+    /// Check if a close frame is staged but not completely written.
+    #[inline]
+    pub(super) const fn is_close_pending(&self) -> bool {
+        matches!(&self.close_store, Some(close_store) if close_store.rd_left() != 0)
+    }
+
+    #[inline]
+    pub(super) const fn is_write_close(&self) -> bool {
+        matches!(&self.close_store, Some(close_store) if close_store.rd_left() == 0)
+    }
+
+    /// Mark the most recent ping as complete,
+    /// and queue it if auto pong is enabled.
+    #[inline]
+    pub(super) fn complete_ping(&mut self) {
+        self.heartbeat.complete();
+        if let Some(auto_pong) = &mut self.auto_pong {
+            auto_pong.push(&self.heartbeat.ping_store);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Read;
+    use super::super::Stream;
+    use super::super::test::make_frame;
+    use crate::frame::OpCode;
+    use crate::role::*;
+
+    #[test]
+    fn read_status() {
+        fn status<R1: RoleHelper, R2: RoleHelper>() {
+            let (mut frame, data) = make_frame::<R1>(OpCode::Ping, 16);
+            let (mut close, _) = make_frame::<R1>(OpCode::Close, 0);
+            frame.append(&mut close);
+
+            let mut stream = Stream::new(frame.as_slice(), R2::new());
+            assert!(!stream.is_pinged());
+            assert!(!stream.is_read_end());
+            assert!(stream.is_read_partial_head());
+
+            let mut buf = vec![0; 64];
+            let n = stream.read(&mut buf).unwrap();
+            assert_eq!(n, 0);
+            assert!(stream.is_pinged());
+            assert!(stream.is_ping_completed());
+            assert_eq!(stream.ping_data(), &data);
+            assert!(stream.is_read_end());
+            assert!(stream.is_read_close());
+            assert!(!stream.is_read_eof());
+
+            let mut stream = Stream::new([].as_slice(), R2::new());
+            let n = stream.read(&mut buf).unwrap();
+            assert_eq!(n, 0);
+            assert!(!stream.is_pinged());
+            assert!(stream.is_read_end());
+            assert!(stream.is_read_eof());
+            assert!(!stream.is_read_close());
+        }
+
+        status::<Client, Server>();
+        status::<Server, Client>();
+    }
+
+    #[test]
+    fn write_status() {
+        use std::io::Write;
+        use super::super::test::LimitReadWriter;
+
+        let io = LimitReadWriter {
+            buf: Vec::new(),
+            rlimit: 0,
+            wlimit: 5,
+            cursor: 0,
+        };
+        let mut stream = Stream::new(io, Server::new());
+        assert_eq!(stream.write_remaining(), 0);
+
+        // the head is written, then the payload
+        let data = [1_u8; 10];
+        assert_eq!(stream.write(&data).unwrap(), 0);
+        assert_eq!(stream.write_remaining(), 10);
+        assert_eq!(stream.write(&data).unwrap(), 5);
+        assert_eq!(stream.write_remaining(), 5);
+        assert_eq!(stream.write(&data[5..]).unwrap(), 5);
+        assert_eq!(stream.write_remaining(), 0);
+
+        // guarded write continues until the frame is completely written
+        let mut stream = stream.guard();
+        stream.write_all(&data).unwrap();
+        assert_eq!(stream.write_remaining(), 0);
+    }
+
+    #[test]
+    fn pending_pings() {
+        fn pending<R1: RoleHelper, R2: RoleHelper>() {
+            let mut frame = Vec::new();
+            let mut data = Vec::new();
+            for i in 1..=3 {
+                let (mut f, d) = make_frame::<R1>(OpCode::Ping, i);
+                frame.append(&mut f);
+                data.push(d);
+            }
+
+            // not queued without auto pong
+            let mut stream = Stream::new(frame.as_slice(), R2::new());
+            let mut buf = vec![0; 64];
+            assert_eq!(stream.read(&mut buf).unwrap(), 0);
+            assert_eq!(stream.ping_data(), &data[2]);
+            assert_eq!(stream.pending_pings(), 0);
+            assert_eq!(stream.dropped_pings(), 0);
+            assert!(!stream.pop_pending_ping());
+
+            let mut stream = Stream::new(frame.as_slice(), R2::new());
+            stream.set_auto_pong(true);
+            let n = stream.read(&mut buf).unwrap();
+            assert_eq!(n, 0);
+            assert_eq!(stream.ping_data(), &data[2]);
+            assert_eq!(stream.pending_pings(), 2);
+            assert_eq!(stream.dropped_pings(), 1);
+
+            assert_eq!(stream.pending_ping_data(), Some(data[0].as_slice()));
+            assert!(stream.pop_pending_ping());
+            assert_eq!(stream.pending_ping_data(), Some(data[1].as_slice()));
+            assert!(stream.pop_pending_ping());
+            assert_eq!(stream.pending_ping_data(), None);
+            assert!(!stream.pop_pending_ping());
+            assert_eq!(stream.pending_pings(), 0);
+        }
+
+        pending::<Client, Server>();
+        pending::<Server, Client>();
+    }
+
+    #[test]
+    #[cfg(target_pointer_width = "64")]
+    fn state_size() {
+        use std::mem::size_of;
+        use super::{HeadStore, ReadState, WriteState, HeartBeat};
+        assert_eq!(size_of::<HeadStore>(), 16);
+        assert_eq!(size_of::<ReadState>(), 32);
+        assert_eq!(size_of::<WriteState>(), 24);
+        assert_eq!(size_of::<HeartBeat>(), 129);
+    }
+
+    #[test]
+    #[cfg(all(
+        target_pointer_width = "64",
+        feature = "async",
+        not(feature = "metrics"),
+        not(feature = "frame_hook")
+    ))]
+    fn stream_size() {
+        use std::mem::size_of;
+        use std::net::TcpStream;
+        use super::super::Stream;
+        assert_eq!(size_of::<Stream<TcpStream, Server>>(), 336);
     }
 }
@@ -0,0 +1,91 @@
+use super::Stream;
+
+/// Counters of a [`Stream`], see [`Stream::stats`].
+///
+/// Bytes are counted on the underlying IO source, including frame heads,
+/// and a frame is counted once its head is read or starts to be written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StreamStats {
+    /// count of frames read, including control frames
+    pub frames_read: u64,
+    /// count of frames written, including control frames
+    pub frames_written: u64,
+    /// count of bytes read
+    pub bytes_read: u64,
+    /// count of bytes written
+    pub bytes_written: u64,
+    /// count of pings received
+    pub pings_received: u64,
+}
+
+impl StreamStats {
+    /// Constructor, all counters are zero.
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            frames_read: 0,
+            frames_written: 0,
+            bytes_read: 0,
+            bytes_written: 0,
+            pings_received: 0,
+        }
+    }
+}
+
+impl<IO, Role, Guard> Stream<IO, Role, Guard> {
+    /// Get the counters.
+    #[inline]
+    pub const fn stats(&self) -> StreamStats { self.state.stats }
+
+    /// Reset all counters to zero.
+    #[inline]
+    pub fn reset_stats(&mut self) { self.state.stats = StreamStats::new(); }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::test::*;
+    use crate::frame::{Mask, OpCode};
+    use crate::role::*;
+    use std::io::{Read, Write};
+
+    #[test]
+    fn count_frames() {
+        let (frame, data) = make_frame::<Client>(OpCode::Binary, 100);
+
+        // write
+        let mut stream = Stream::new(Vec::new(), Client::new()).guard();
+        for _ in 0..3 {
+            stream.write_all(&data).unwrap();
+        }
+        let stats = stream.stats();
+        assert_eq!(stats.frames_written, 3);
+        assert_eq!(stats.bytes_written, frame.len() as u64 * 3);
+        assert_eq!(stats.frames_read, 0);
+
+        // read, with a ping and a close
+        let mut input = stream.as_ref().clone();
+        input.extend(make_head(OpCode::Ping, Mask::Key([1, 2, 3, 4]), 0));
+        input.extend(make_head(OpCode::Close, Mask::Key([1, 2, 3, 4]), 0));
+        let io = LimitReadWriter {
+            buf: input.clone(),
+            rlimit: 7,
+            wlimit: 0,
+            cursor: 0,
+        };
+        let mut stream = Stream::new(io, Server::new()).guard();
+        let mut buf = Vec::new();
+        stream.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf, data.repeat(3));
+
+        let stats = stream.stats();
+        assert_eq!(stats.frames_read, 5);
+        assert_eq!(stats.pings_received, 1);
+        assert_eq!(stats.bytes_read, input.len() as u64);
+        assert_eq!(stats.frames_written, 0);
+
+        stream.reset_stats();
+        assert_eq!(stream.stats(), StreamStats::default());
+    }
+}
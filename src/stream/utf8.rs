@@ -0,0 +1,172 @@
+use crate::error::FrameError;
+
+/// Incremental UTF-8 validator of a text message.
+///
+/// Payload is fed fragment by fragment, a codepoint may be split
+/// at any fragment boundary, while an invalid sequence is rejected
+/// as soon as the offending byte is fed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Utf8Validator {
+    // continuation bytes still expected
+    need: u8,
+    // valid range of the next continuation byte
+    lo: u8,
+    hi: u8,
+}
+
+impl Utf8Validator {
+    /// Constructor, at a codepoint boundary.
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            need: 0,
+            lo: 0x80,
+            hi: 0xbf,
+        }
+    }
+
+    /// Check if a codepoint is partially fed.
+    #[inline]
+    pub const fn is_incomplete(&self) -> bool { self.need != 0 }
+
+    /// Validate the next piece of payload.
+    pub fn feed(&mut self, data: &[u8]) -> Result<(), FrameError> {
+        for &b in data {
+            if self.need != 0 {
+                if b < self.lo || b > self.hi {
+                    return Err(FrameError::InvalidUtf8);
+                }
+                self.need -= 1;
+                self.lo = 0x80;
+                self.hi = 0xbf;
+                continue;
+            }
+            // reject overlong forms, surrogates and codepoints beyond U+10FFFF
+            // by narrowing the range of the first continuation byte
+            (self.need, self.lo, self.hi) = match b {
+                0x00..=0x7f => continue,
+                0xc2..=0xdf => (1, 0x80, 0xbf),
+                0xe0 => (2, 0xa0, 0xbf),
+                0xe1..=0xec | 0xee..=0xef => (2, 0x80, 0xbf),
+                0xed => (2, 0x80, 0x9f),
+                0xf0 => (3, 0x90, 0xbf),
+                0xf1..=0xf3 => (3, 0x80, 0xbf),
+                0xf4 => (3, 0x80, 0x8f),
+                _ => return Err(FrameError::InvalidUtf8),
+            };
+        }
+        Ok(())
+    }
+
+    /// Finish a message, a trailing partial codepoint is invalid.
+    /// The validator is reset either way.
+    #[inline]
+    pub fn finish(&mut self) -> Result<(), FrameError> {
+        let incomplete = self.is_incomplete();
+        *self = Self::new();
+        if incomplete {
+            Err(FrameError::InvalidUtf8)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl Default for Utf8Validator {
+    fn default() -> Self { Self::new() }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::test::*;
+    use crate::frame::{Fin, Mask, OpCode, FrameHead};
+
+    // payloads of a fragmented text message
+    fn fragments(parts: &[&[u8]]) -> Vec<Vec<u8>> {
+        let last = parts.len() - 1;
+        parts
+            .iter()
+            .enumerate()
+            .map(|(i, part)| {
+                let opcode = if i == 0 {
+                    OpCode::Text
+                } else {
+                    OpCode::Continue
+                };
+                let fin = if i == last { Fin::Y } else { Fin::N };
+                let mut frame = make_head_with_fin(fin, opcode, Mask::None, part.len());
+                frame.extend_from_slice(part);
+                frame
+            })
+            .collect()
+    }
+
+    fn validate(parts: &[&[u8]]) -> Result<(), FrameError> {
+        let mut utf8 = Utf8Validator::new();
+        for frame in fragments(parts) {
+            let (head, parse_n) = FrameHead::decode(&frame).unwrap();
+            utf8.feed(&frame[parse_n..])?;
+            if head.fin == Fin::Y {
+                utf8.finish()?;
+            }
+        }
+        assert!(!utf8.is_incomplete());
+        Ok(())
+    }
+
+    #[test]
+    fn utf8_split_codepoint() {
+        // U+1F600, f0 9f 98 80
+        let emoji = "\u{1f600}".as_bytes();
+        assert_eq!(emoji.len(), 4);
+
+        for i in 0..=4 {
+            let (a, b) = emoji.split_at(i);
+            assert_eq!(validate(&[b"hi", a, b]), Ok(()));
+        }
+        assert_eq!(validate(&[b"a\xf0\x9f", b"\x98", b"\x80b"]), Ok(()));
+        assert_eq!(
+            validate(&["κόσμε".as_bytes(), b"\xef\xbf\xbf", b"\xf4\x8f\xbf\xbf"]),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn utf8_invalid_sequence() {
+        // rejected mid-fragment, before the rest is fed
+        let mut utf8 = Utf8Validator::new();
+        assert_eq!(utf8.feed(b"\xf0\x9f"), Ok(()));
+        assert_eq!(utf8.feed(b"\x98a"), Err(FrameError::InvalidUtf8));
+
+        for bad in [
+            &b"\x80"[..],
+            b"\xc0\xaf",
+            b"\xe0\x80\xaf",
+            b"\xed\xa0\x80",
+            b"\xf0\x8f\xbf\xbf",
+            b"\xf4\x90\x80\x80",
+            b"\xf5\x80\x80\x80",
+            b"\xff",
+        ] {
+            assert_eq!(
+                validate(&[b"ok", bad]),
+                Err(FrameError::InvalidUtf8),
+                "{:x?}",
+                bad
+            );
+        }
+
+        // a message must not end with a partial codepoint
+        assert_eq!(
+            validate(&[b"\xf0\x9f", b"\x98"]),
+            Err(FrameError::InvalidUtf8)
+        );
+
+        // reset after finish
+        let mut utf8 = Utf8Validator::new();
+        utf8.feed(b"\xe2\x82").unwrap();
+        assert_eq!(utf8.finish(), Err(FrameError::InvalidUtf8));
+        assert_eq!(utf8, Utf8Validator::new());
+    }
+}
@@ -1,8 +1,11 @@
-use std::io::{Write, Result};
+use std::io::{Write, Result, ErrorKind};
 use std::task::Poll;
 
 use super::{Stream, RoleHelper, Guarded};
-use super::detail::write_some;
+use super::detail::{write_some, write_frame_some, write_frames_some, flush_head};
+
+use crate::frame::{Fin, OpCode};
+use crate::error::CtrlError;
 
 impl<IO: Write, Role: RoleHelper> Write for Stream<IO, Role> {
     /// Write some data to the underlying IO source,
@@ -17,15 +20,31 @@ impl<IO: Write, Role: RoleHelper> Write for Stream<IO, Role> {
     ///
     /// A standard client should mask payload data before sending it.
     fn write(&mut self, buf: &[u8]) -> Result<usize> {
-        match write_some(self, |io, iovec| io.write_vectored(iovec).into(), buf) {
+        match write_some(
+            &mut self.state,
+            |iovec| self.io.write_vectored(iovec).into(),
+            buf,
+        ) {
             Poll::Ready(x) => x,
             Poll::Pending => unreachable!(),
         }
     }
 
-    /// The writer does not buffer any data, simply flush
-    /// the underlying IO source.
-    fn flush(&mut self) -> Result<()> { self.io.flush() }
+    /// Write the rest of a partially written frame head (if any),
+    /// and answer pending pings if auto pong is enabled,
+    /// then flush the underlying IO source.
+    ///
+    /// Payload data is never buffered, so it should still be
+    /// written with the next write.
+    fn flush(&mut self) -> Result<()> {
+        match flush_head(&mut self.state, |iovec| {
+            self.io.write_vectored(iovec).into()
+        }) {
+            Poll::Ready(x) => x?,
+            Poll::Pending => unreachable!(),
+        };
+        self.io.flush()
+    }
 
     /// **This is NOT supported!**
     fn write_all(&mut self, _: &[u8]) -> Result<()> {
@@ -36,30 +55,132 @@ impl<IO: Write, Role: RoleHelper> Write for Stream<IO, Role> {
 impl<IO: Write, Role: RoleHelper> Write for Stream<IO, Role, Guarded> {
     /// Wrap write in a loop.
     /// Continue to write if frame head is not completely written.
+    ///
+    /// Unlike the direct mode, once `WriteZero` occurs, this and any later
+    /// write will return an error of [`ErrorKind::WriteZero`].
+    ///
+    /// An `Interrupted` error is retried. Other IO errors(like `WouldBlock`)
+    /// are returned as is, and the next write resumes from where it stopped.
     fn write(&mut self, buf: &[u8]) -> Result<usize> {
         loop {
-            match write_some(self, |io, iovec| io.write_vectored(iovec).into(), buf) {
-                Poll::Ready(Ok(0)) if self.is_write_partial_head() || !self.is_write_zero() => {
-                    continue
+            match write_some(
+                &mut self.state,
+                |iovec| self.io.write_vectored(iovec).into(),
+                buf,
+            ) {
+                Poll::Ready(Ok(0)) if self.is_write_zero() => {
+                    return Err(ErrorKind::WriteZero.into())
                 }
+                Poll::Ready(Ok(0)) => continue,
+                Poll::Ready(Err(e)) if e.kind() == ErrorKind::Interrupted => continue,
                 Poll::Ready(x) => return x,
                 Poll::Pending => unreachable!(),
             }
         }
     }
 
-    /// The writer does not buffer any data, simply flush
-    /// the underlying IO source.
-    fn flush(&mut self) -> Result<()> { self.io.flush() }
+    /// Write the rest of a partially written frame head (if any),
+    /// and answer pending pings if auto pong is enabled,
+    /// then flush the underlying IO source.
+    ///
+    /// Payload data is never buffered, so it should still be
+    /// written with the next write.
+    fn flush(&mut self) -> Result<()> {
+        match flush_head(&mut self.state, |iovec| {
+            self.io.write_vectored(iovec).into()
+        }) {
+            Poll::Ready(x) => x?,
+            Poll::Pending => unreachable!(),
+        };
+        self.io.flush()
+    }
+}
+
+impl<IO: Write, Role: RoleHelper, Guard> Stream<IO, Role, Guard> {
+    /// Write each buffer as a frame, with **at most one** vectored write,
+    /// return the number of payload bytes written.
+    ///
+    /// At most [`MAX_WRITE_FRAMES`](super::MAX_WRITE_FRAMES) frames are written at once,
+    /// and empty buffers are skipped.
+    /// If a frame is partially written, caller should continue with the rest of
+    /// its payload followed by the remaining buffers,
+    /// like [`write`](Write::write) in [`Direct`](super::Direct) mode.
+    pub fn write_frames(&mut self, bufs: &[&[u8]]) -> Result<usize> {
+        match write_frames_some(
+            &mut self.state,
+            |iovec| self.io.write_vectored(iovec).into(),
+            bufs,
+        ) {
+            Poll::Ready(x) => x,
+            Poll::Pending => unreachable!(),
+        }
+    }
+
+    /// Write some data like [`write`](Write::write) in [`Direct`](super::Direct) mode,
+    /// where a new frame is created with the provided opcode instead of `Binary`.
+    ///
+    /// Only data opcodes(`Text`, `Binary`, `Continue`) are allowed, otherwise
+    /// it fails with [`CtrlError::NotDataOpcode`] before anything is written.
+    /// The opcode has no effect on an unfinished frame,
+    /// which is continued as [`write`](Write::write) does.
+    pub fn write_with_opcode(&mut self, opcode: OpCode, buf: &[u8]) -> Result<usize> {
+        if opcode.is_control() {
+            return Err(CtrlError::NotDataOpcode.into());
+        }
+
+        match write_frame_some(
+            &mut self.state,
+            |iovec| self.io.write_vectored(iovec).into(),
+            buf,
+            Fin::Y,
+            opcode,
+        ) {
+            Poll::Ready(x) => x,
+            Poll::Pending => unreachable!(),
+        }
+    }
+
+    /// Write some data like [`write`](Write::write) in [`Direct`](super::Direct) mode,
+    /// where a new frame is created as a fragment of an outgoing message.
+    ///
+    /// The first fragment is sent with [`write_opcode`](Self::write_opcode),
+    /// and the following ones are sent as
+    /// `Continue`, until a fragment with `fin` set ends the message,
+    /// which could be checked via [`Stream::is_write_fragmented`].
+    /// The fin flag has no effect on an unfinished frame,
+    /// which is continued as [`write`](Write::write) does.
+    ///
+    /// An empty fragment returns `Ok(0)` once it is written.
+    /// Other writes fail with [`CtrlError::MessageInProgress`] until the message ends.
+    pub fn write_fragment(&mut self, buf: &[u8], fin: bool) -> Result<usize> {
+        let opcode = if self.state.write_fragmented {
+            OpCode::Continue
+        } else {
+            self.state.write_opcode
+        };
+        let fin = if fin { Fin::Y } else { Fin::N };
+
+        match write_frame_some(
+            &mut self.state,
+            |iovec| self.io.write_vectored(iovec).into(),
+            buf,
+            fin,
+            opcode,
+        ) {
+            Poll::Ready(x) => x,
+            Poll::Pending => unreachable!(),
+        }
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
     use super::super::test::*;
+    use super::super::MAX_WRITE_FRAMES;
     use crate::frame::*;
     use crate::role::*;
-    use std::io::Write;
+    use std::io::{Read, Write};
 
     #[test]
     fn write_to_stream() {
@@ -121,6 +242,199 @@ mod test {
         }
     }
 
+    #[test]
+    fn write_to_would_block_stream() {
+        fn write<R: RoleHelper>(n: usize, limit: usize) {
+            let (frame, data) = make_frame::<R>(OpCode::Binary, n);
+
+            let io = WouldBlockReadWriter::new(LimitReadWriter {
+                buf: Vec::new(),
+                rlimit: 0,
+                wlimit: limit,
+                cursor: 0,
+            });
+            let mut stream = Stream::new(io, R::new()).guard();
+
+            let mut offset = 0;
+            while offset < n {
+                match stream.write(&data[offset..]) {
+                    Ok(n) => offset += n,
+                    // resume with the stored state
+                    Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => continue,
+                    Err(e) => panic!("{}", e),
+                }
+            }
+
+            // a single frame
+            assert_eq!(stream.as_ref().inner.buf, frame);
+        }
+
+        for limit in [1, 2, 3, 5, 14, usize::MAX] {
+            for n in [1, 125, 126, 1000, 65536] {
+                write::<Client>(n, limit);
+                write::<Server>(n, limit);
+            }
+        }
+    }
+
+    #[test]
+    fn write_to_interrupted_stream() {
+        fn write<R: RoleHelper>(n: usize, limit: usize) {
+            let (frame, data) = make_frame::<R>(OpCode::Binary, n);
+
+            let io = WouldBlockReadWriter::with_kind(
+                LimitReadWriter {
+                    buf: Vec::new(),
+                    rlimit: 0,
+                    wlimit: limit,
+                    cursor: 0,
+                },
+                std::io::ErrorKind::Interrupted,
+            );
+            let mut stream = Stream::new(io, R::new()).guard();
+
+            // never interrupted
+            let mut offset = 0;
+            while offset < n {
+                offset += stream.write(&data[offset..]).unwrap();
+            }
+            assert_eq!(stream.as_ref().inner.buf, frame);
+        }
+
+        for limit in [1, 3, 14, usize::MAX] {
+            for n in [1, 126, 1000] {
+                write::<Client>(n, limit);
+                write::<Server>(n, limit);
+            }
+        }
+    }
+
+    #[test]
+    fn flush_partial_head() {
+        fn write<R: RoleHelper>(n: usize, limit: usize) {
+            let (frame, data) = make_frame::<R>(OpCode::Binary, n);
+            let head_len = frame.len() - n;
+
+            let io = LimitReadWriter {
+                buf: Vec::new(),
+                rlimit: 0,
+                wlimit: limit,
+                cursor: 0,
+            };
+            let mut stream = Stream::new(io, R::new());
+
+            // part of the head is written
+            assert_eq!(stream.write(&data).unwrap(), 0);
+            assert_eq!(stream.as_ref().buf.len(), limit);
+
+            // the rest of the head is written
+            stream.flush().unwrap();
+            assert_eq!(stream.as_ref().buf, &frame[..head_len]);
+
+            // nothing else is staged
+            stream.flush().unwrap();
+            assert_eq!(stream.as_ref().buf.len(), head_len);
+
+            let mut offset = 0;
+            while offset < n {
+                offset += stream.write(&data[offset..]).unwrap();
+            }
+            assert_eq!(stream.as_ref().buf, frame);
+        }
+
+        // the shortest head is 4 bytes
+        for limit in 1..4 {
+            for n in [126, 65536] {
+                write::<Client>(n, limit);
+                write::<Server>(n, limit);
+            }
+        }
+    }
+
+    #[test]
+    fn write_frames_to_stream() {
+        fn write<R: RoleHelper>(lens: &[usize], limit: usize) {
+            let mut frames = Vec::new();
+            let mut data = Vec::new();
+            for len in lens.iter().filter(|len| **len != 0) {
+                let (mut f, d) = make_frame::<R>(OpCode::Binary, *len);
+                frames.append(&mut f);
+                data.push(d);
+            }
+
+            let io = LimitReadWriter {
+                buf: Vec::new(),
+                rlimit: 0,
+                wlimit: limit,
+                cursor: 0,
+            };
+            let mut stream = Stream::new(io, R::new());
+
+            let mut bufs: Vec<&[u8]> = data.iter().map(Vec::as_slice).collect();
+            while !bufs.is_empty() {
+                let mut n = stream.write_frames(&bufs).unwrap();
+                while n != 0 {
+                    if n >= bufs[0].len() {
+                        n -= bufs[0].len();
+                        bufs.remove(0);
+                    } else {
+                        bufs[0] = &bufs[0][n..];
+                        n = 0;
+                    }
+                }
+            }
+
+            assert_eq!(stream.as_ref().buf, frames);
+        }
+
+        let lens = [1, 0, 125, 126, 2, 65536, 3, 4, 5, 6, 7, 8, 9];
+        for limit in [1, 2, 7, 14, 100, 1000, usize::MAX] {
+            write::<Client>(&lens, limit);
+            write::<Server>(&lens, limit);
+        }
+
+        // vectored write
+        let io: Vec<u8> = Vec::new();
+        let mut stream = Stream::new(io, Client::new());
+        let data: Vec<Vec<u8>> = (1..=10).map(make_data).collect();
+        let bufs: Vec<&[u8]> = data.iter().map(Vec::as_slice).collect();
+        let n = stream.write_frames(&bufs).unwrap();
+        assert_eq!(n, (1..=MAX_WRITE_FRAMES).sum::<usize>());
+    }
+
+    #[test]
+    fn write_zero_to_stream() {
+        fn write<R: RoleHelper>() {
+            let io = LimitReadWriter {
+                buf: Vec::new(),
+                rlimit: 0,
+                wlimit: 0,
+                cursor: 0,
+            };
+            let data = make_data(32);
+
+            let mut stream = Stream::new(io, R::new());
+            for _ in 0..4 {
+                let n = stream.write(&data).unwrap();
+                assert_eq!(n, 0);
+                assert!(stream.is_write_zero());
+            }
+
+            let mut stream = stream.guard();
+            for _ in 0..4 {
+                let e = stream.write(&data).unwrap_err();
+                assert_eq!(e.kind(), ErrorKind::WriteZero);
+                assert!(stream.is_write_zero());
+            }
+
+            let e = std::io::copy(&mut data.as_slice(), &mut stream).unwrap_err();
+            assert_eq!(e.kind(), ErrorKind::WriteZero);
+        }
+
+        write::<Client>();
+        write::<Server>();
+    }
+
     #[test]
     #[cfg(feature = "unsafe_auto_mask_write")]
     fn write_to_stream_auto_mask_fixed() {
@@ -259,4 +573,232 @@ mod test {
             }
         }
     }
+
+    #[test]
+    fn write_with_opcode_to_stream() {
+        fn write<R: RoleHelper>(opcode: OpCode, n: usize, limit: usize) {
+            let (frame, data) = make_frame::<R>(opcode, n);
+
+            let io = LimitReadWriter {
+                buf: Vec::new(),
+                rlimit: 0,
+                wlimit: limit,
+                cursor: 0,
+            };
+            let mut stream = Stream::new(io, R::new());
+
+            let mut offset = 0;
+            while offset < n {
+                offset += stream.write_with_opcode(opcode, &data[offset..]).unwrap();
+            }
+
+            assert_eq!(stream.as_ref().buf, frame);
+        }
+
+        for opcode in [OpCode::Text, OpCode::Binary, OpCode::Continue] {
+            for limit in [1, 2, 3, 7, 16, 200] {
+                write::<Client>(opcode, 126, limit);
+                write::<Server>(opcode, 126, limit);
+            }
+        }
+
+        for opcode in [OpCode::Close, OpCode::Ping, OpCode::Pong] {
+            let mut stream = Stream::new(Vec::new(), Server);
+            let e = stream.write_with_opcode(opcode, b"data").unwrap_err();
+            let e = e.into_inner().unwrap();
+            assert!(matches!(
+                e.downcast_ref::<crate::error::Error>(),
+                Some(crate::error::Error::Ctrl(CtrlError::NotDataOpcode))
+            ));
+            assert!(stream.as_ref().is_empty());
+        }
+    }
+
+    #[test]
+    fn write_fragment_to_stream() {
+        fn write<R: RoleHelper>(limit: usize) {
+            let fragments = [
+                (b"hello".as_slice(), false),
+                (b"", false),
+                (b" world", true),
+            ];
+
+            let io = LimitReadWriter {
+                buf: Vec::new(),
+                rlimit: 0,
+                wlimit: limit,
+                cursor: 0,
+            };
+            let mut stream = Stream::new(io, R::new());
+
+            // send a message twice
+            for _ in 0..2 {
+                for (data, fin) in fragments {
+                    let mut offset = stream.write_fragment(data, fin).unwrap();
+                    while offset < data.len() {
+                        offset += stream.write_fragment(&data[offset..], fin).unwrap();
+                    }
+                    // complete the head of an empty fragment
+                    stream.flush().unwrap();
+                    assert_eq!(stream.is_write_fragmented(), !fin);
+                }
+            }
+
+            let mut frames = Vec::new();
+            for (i, (data, fin)) in fragments.into_iter().enumerate() {
+                let fin = if fin { Fin::Y } else { Fin::N };
+                let opcode = if i == 0 {
+                    OpCode::Binary
+                } else {
+                    OpCode::Continue
+                };
+                frames.extend(make_head_with_fin(
+                    fin,
+                    opcode,
+                    R::new().mask_key(),
+                    data.len(),
+                ));
+                frames.extend_from_slice(data);
+            }
+            assert_eq!(stream.as_ref().buf, [frames.clone(), frames].concat());
+        }
+
+        for limit in [1, 2, 3, 100] {
+            write::<Client>(limit);
+            write::<Server>(limit);
+        }
+    }
+
+    #[test]
+    fn auto_pong_to_stream() {
+        fn write<R: RoleHelper>(limit: usize, by_flush: bool) {
+            let (ping1, data1) = make_frame_with_mask(OpCode::Ping, Mask::None, 5);
+            let (ping2, data2) = make_frame_with_mask(OpCode::Ping, Mask::None, 0);
+
+            let io = LimitReadWriter {
+                buf: [ping1.clone(), ping2.clone()].concat(),
+                rlimit: 0x100,
+                wlimit: limit,
+                cursor: 0,
+            };
+            let mut stream = Stream::builder(io, R::new())
+                .auto_pong(true)
+                .text(true)
+                .build();
+
+            let mut buf = [0_u8; 0x100];
+            while stream.pending_pings() < 2 {
+                assert_eq!(stream.read(&mut buf).unwrap(), 0);
+            }
+
+            let mut expect = [ping1, ping2].concat();
+            for data in [data1, data2] {
+                expect.extend(make_head(OpCode::Pong, R::new().mask_key(), data.len()));
+                expect.extend(data);
+            }
+
+            if by_flush {
+                stream.flush().unwrap();
+            } else {
+                let data = b"hello";
+                let mut offset = 0;
+                while offset < data.len() {
+                    offset += stream.write(&data[offset..]).unwrap();
+                }
+                expect.extend(make_head(OpCode::Text, R::new().mask_key(), data.len()));
+                expect.extend(data);
+            }
+
+            assert_eq!(stream.pending_pings(), 0);
+            assert_eq!(stream.as_ref().buf, expect);
+        }
+
+        for limit in [1, 2, 3, 100] {
+            for by_flush in [false, true] {
+                write::<Client>(limit, by_flush);
+                write::<Server>(limit, by_flush);
+            }
+        }
+    }
+
+    #[test]
+    fn auto_pong_echo_each_ping() {
+        fn write<R: RoleHelper>(limit: usize) {
+            let bodies: [&[u8]; 2] = [b"first", b"second ping"];
+            let mut input = Vec::new();
+            for data in bodies {
+                input.extend(make_head(OpCode::Ping, Mask::None, data.len()));
+                input.extend_from_slice(data);
+            }
+
+            let io = LimitReadWriter {
+                buf: input.clone(),
+                rlimit: 0x100,
+                wlimit: limit,
+                cursor: 0,
+            };
+            let mut stream = Stream::builder(io, R::new()).auto_pong(true).build();
+
+            // the second ping replaces the ping data before any pong is sent
+            let mut buf = [0_u8; 0x100];
+            while stream.pending_pings() < 2 {
+                assert_eq!(stream.read(&mut buf).unwrap(), 0);
+            }
+            assert_eq!(stream.ping_data(), bodies[1]);
+            stream.flush().unwrap();
+
+            // each pong carries the data of its own ping
+            let mut expect = input;
+            for data in bodies {
+                let mut data = data.to_vec();
+                let mask = R::new().mask_key();
+                if let Mask::Key(key) = mask {
+                    apply_mask4(key, &mut data);
+                }
+                expect.extend(make_head(OpCode::Pong, mask, data.len()));
+                expect.extend(data);
+            }
+            assert_eq!(stream.pending_pings(), 0);
+            assert_eq!(stream.as_ref().buf, expect);
+        }
+
+        for limit in [1, 3, 100] {
+            write::<Client>(limit);
+            write::<Server>(limit);
+            write::<StandardClient>(limit);
+        }
+    }
+    #[test]
+    fn auto_pong_one_write_per_call() {
+        let mut input = Vec::new();
+        for _ in 0..2 {
+            input.extend(make_head(OpCode::Ping, Mask::None, 4));
+            input.extend_from_slice(b"ping");
+        }
+        let io = LimitReadWriter {
+            buf: input.clone(),
+            rlimit: 0x100,
+            wlimit: 0x100,
+            cursor: 0,
+        };
+        let mut stream = Stream::builder(io, Server).auto_pong(true).build();
+        let mut buf = [0_u8; 0x100];
+        while stream.pending_pings() < 2 {
+            assert_eq!(stream.read(&mut buf).unwrap(), 0);
+        }
+
+        // each write sends one pong before the data frame
+        let pong = [make_head(OpCode::Pong, Mask::None, 4), b"ping".to_vec()].concat();
+        let frame = [make_head(OpCode::Binary, Mask::None, 5), b"hello".to_vec()].concat();
+        assert_eq!(stream.write(b"hello").unwrap(), 0);
+        assert_eq!(&stream.as_ref().buf[input.len()..], &pong);
+        assert_eq!(stream.write(b"hello").unwrap(), 0);
+        assert_eq!(stream.pending_pings(), 0);
+        // the frame head may be written alone
+        while stream.write(b"hello").unwrap() == 0 {}
+        assert_eq!(
+            &stream.as_ref().buf[input.len()..],
+            [pong.clone(), pong, frame].concat()
+        );
+    }
 }
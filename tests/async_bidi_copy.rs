@@ -61,7 +61,9 @@ async fn async_bidi_copy() {
             debug!("relay: server close");
         });
 
-        let _ = tokio::join!(t1, t2);
+        let (r1, r2) = tokio::join!(t1, t2);
+        r1.unwrap();
+        r2.unwrap();
     });
 
     let server = tokio::spawn(async move {
@@ -112,5 +114,8 @@ async fn async_bidi_copy() {
         debug!("client: close");
     });
 
-    let _ = tokio::join!(relay, server, client);
+    let (r1, r2, r3) = tokio::join!(relay, server, client);
+    r1.unwrap();
+    r2.unwrap();
+    r3.unwrap();
 }
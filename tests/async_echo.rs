@@ -70,5 +70,7 @@ async fn async_echo() {
         debug!("client: close");
     });
 
-    let _ = tokio::join!(t1, t2);
+    let (r1, r2) = tokio::join!(t1, t2);
+    r1.unwrap();
+    r2.unwrap();
 }
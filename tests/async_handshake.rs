@@ -1,6 +1,7 @@
 use std::time::Duration;
 
 use tokio::net::{TcpStream, TcpListener};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
 use lightws::endpoint::Endpoint;
 use lightws::role::{Client, Server};
@@ -21,10 +22,16 @@ async fn async_handshake() {
         let mut buf = vec![0u8; 1024];
         let (tcp, _) = lis.accept().await.unwrap();
         debug!("server: tcp accepted!");
-        let _ = Endpoint::<_, Server>::accept_async(tcp, &mut buf, HOST, PATH)
+        let ws = Endpoint::<_, Server>::accept_async(tcp, &mut buf, HOST, PATH)
             .await
             .unwrap();
         debug!("server: websocket accepted!");
+
+        // the first frame from the client
+        let mut ws = ws.guard();
+        let mut data = [0u8; 5];
+        ws.read_exact(&mut data).await.unwrap();
+        assert_eq!(&data, b"hello");
     });
 
     let t2 = tokio::spawn(async {
@@ -33,11 +40,14 @@ async fn async_handshake() {
         tokio::time::sleep(Duration::from_millis(500)).await;
         let tcp = TcpStream::connect(ADDR).await.unwrap();
         debug!("client: tcp connected!");
-        let _ = Endpoint::<_, Client>::connect_async(tcp, &mut buf, HOST, PATH)
+        let mut ws = Endpoint::<_, Client>::connect_async(tcp, &mut buf, HOST, PATH)
             .await
             .unwrap();
         debug!("client: websocket connected!");
+        assert_eq!(ws.write(b"hello").await.unwrap(), 5);
     });
 
-    let _ = tokio::join!(t1, t2);
+    let (r1, r2) = tokio::join!(t1, t2);
+    r1.unwrap();
+    r2.unwrap();
 }
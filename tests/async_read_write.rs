@@ -63,5 +63,7 @@ async fn async_read_write() {
         assert_eq!(&buf[..n], PONG_DATA);
     });
 
-    let _ = tokio::join!(t1, t2);
+    let (r1, r2) = tokio::join!(t1, t2);
+    r1.unwrap();
+    r2.unwrap();
 }
@@ -0,0 +1,281 @@
+use std::io::{Read, Write};
+use std::net::{TcpStream, TcpListener};
+use std::sync::mpsc;
+use std::time::Duration;
+use std::thread;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use lightws::endpoint::Endpoint;
+use lightws::frame::{FrameReader, OpCode, apply_mask4};
+use lightws::role::{Client, Server};
+
+use log::debug;
+
+const ADDR: &str = "127.0.0.1:10000";
+const ASYNC_ADDR: &str = "127.0.0.1:10001";
+const HOST: &str = "www.example.com";
+const PATH: &str = "/ws";
+const KEY: [u8; 4] = [0x37, 0xfa, 0x21, 0x3d];
+
+/// Expected behavior of the server.
+#[derive(Debug)]
+enum Expect {
+    /// Echo the payload, then echo the close code.
+    Echo(Vec<u8>, u16),
+    /// Fail the connection.
+    Fail,
+}
+
+/// A masked frame from its first byte, e.g. `0x82` for a fin binary frame.
+fn frame(b1: u8, payload: &[u8]) -> Vec<u8> {
+    let mut buf = vec![b1];
+    match payload.len() {
+        n if n < 126 => buf.push(0x80 | n as u8),
+        n if n <= 0xffff => {
+            buf.push(0x80 | 126);
+            buf.extend((n as u16).to_be_bytes());
+        }
+        n => {
+            buf.push(0x80 | 127);
+            buf.extend((n as u64).to_be_bytes());
+        }
+    }
+    buf.extend(KEY);
+    let mut payload = payload.to_vec();
+    apply_mask4(KEY, &mut payload);
+    buf.extend(payload);
+    buf
+}
+
+fn close(code: u16) -> Vec<u8> { frame(0x88, &code.to_be_bytes()) }
+
+/// Cases named after the ones of Autobahn TestSuite.
+fn cases() -> Vec<(&'static str, Vec<u8>, Expect)> {
+    let data = |len: usize| (0..len).map(|x| x as u8).collect::<Vec<u8>>();
+    let concat = |frames: &[Vec<u8>]| frames.concat();
+
+    vec![
+        (
+            "1.2.1 empty binary",
+            concat(&[frame(0x82, &[]), close(1000)]),
+            Expect::Echo(Vec::new(), 1000),
+        ),
+        (
+            "1.2.3 binary with 16-bit length",
+            concat(&[frame(0x82, &data(126)), close(1000)]),
+            Expect::Echo(data(126), 1000),
+        ),
+        (
+            "1.2.6 binary with 64-bit length",
+            concat(&[frame(0x82, &data(65536)), close(1000)]),
+            Expect::Echo(data(65536), 1000),
+        ),
+        ("3.1 reserved bit 1", frame(0xc2, b"hello"), Expect::Fail),
+        ("3.4 reserved bit 3", frame(0x92, b"hello"), Expect::Fail),
+        ("4.1.1 reserved data opcode", frame(0x83, &[]), Expect::Fail),
+        (
+            "4.2.1 reserved control opcode",
+            frame(0x8b, &[]),
+            Expect::Fail,
+        ),
+        (
+            "5.1 fragmented ping",
+            concat(&[frame(0x09, b"he"), frame(0x80, b"llo")]),
+            Expect::Fail,
+        ),
+        (
+            "5.3 fragmented binary",
+            concat(&[frame(0x02, b"he"), frame(0x80, b"llo"), close(1000)]),
+            Expect::Echo(b"hello".to_vec(), 1000),
+        ),
+        (
+            "5.9 continuation without a message",
+            concat(&[frame(0x80, b"hello"), frame(0x82, &[])]),
+            Expect::Fail,
+        ),
+        (
+            "6.2.3 text with a codepoint split into fragments",
+            concat(&[
+                frame(0x01, b"hi\xf0\x9f"),
+                frame(0x80, b"\x98\x80"),
+                close(1000),
+            ]),
+            Expect::Echo("hi\u{1f600}".as_bytes().to_vec(), 1000),
+        ),
+        (
+            "6.3.1 invalid text",
+            frame(0x81, b"\xce\xba\xe1\xbd\xb9\xcf\x83\xed\xa0\x80edited"),
+            Expect::Fail,
+        ),
+        (
+            "6.6.1 text with a partial codepoint",
+            frame(0x81, b"\xce"),
+            Expect::Fail,
+        ),
+        ("7.1.1 close", close(1000), Expect::Echo(Vec::new(), 1000)),
+        (
+            "7.3.1 empty close",
+            frame(0x88, &[]),
+            Expect::Echo(Vec::new(), 1000),
+        ),
+        (
+            "7.3.2 close with 1 byte",
+            frame(0x88, &[0x03]),
+            Expect::Fail,
+        ),
+        (
+            "7.5.1 close with an invalid reason",
+            frame(
+                0x88,
+                b"\x03\xe8\xce\xba\xe1\xbd\xb9\xcf\x83\xed\xa0\x80edited",
+            ),
+            Expect::Fail,
+        ),
+        (
+            "7.7.1 close with a reason",
+            frame(0x88, &[b"\x03\xe8", "bye \u{1f600}".as_bytes()].concat()),
+            Expect::Echo(Vec::new(), 1000),
+        ),
+        (
+            "7.7.2 close 1001",
+            close(1001),
+            Expect::Echo(Vec::new(), 1001),
+        ),
+        (
+            "7.7.12 close 3000",
+            close(3000),
+            Expect::Echo(Vec::new(), 3000),
+        ),
+        (
+            "7.7.13 close 4999",
+            close(4999),
+            Expect::Echo(Vec::new(), 4999),
+        ),
+        ("7.9.1 close 0", close(0), Expect::Fail),
+        ("7.9.4 close 1005", close(1005), Expect::Fail),
+        ("7.9.5 close 1006", close(1006), Expect::Fail),
+        ("7.9.11 close 5000", close(5000), Expect::Fail),
+        ("unmasked frame", vec![0x82, 0x00], Expect::Fail),
+    ]
+}
+
+/// Send each case as raw frames, then check the output of the server,
+/// which reports whether the connection is failed.
+fn client(addr: &str, cases: Vec<(&'static str, Vec<u8>, Expect)>, rx: mpsc::Receiver<bool>) {
+    let mut buf = vec![0u8; 1024];
+    thread::sleep(Duration::from_millis(500));
+    for (name, input, expect) in cases {
+        debug!("client: case {}", name);
+        let tcp = TcpStream::connect(addr).unwrap();
+        let ws = Endpoint::<_, Client>::connect(tcp, &mut buf, HOST, PATH).unwrap();
+        let (mut tcp, leftover) = ws.into_inner();
+        assert!(leftover.is_none());
+
+        tcp.write_all(&input).unwrap();
+        let mut output = Vec::new();
+        tcp.read_to_end(&mut output).unwrap();
+        let ok = rx.recv().unwrap();
+
+        let mut data = Vec::new();
+        let mut code = None;
+        for frame in FrameReader::new(&output) {
+            let (head, payload) = frame.unwrap();
+            match head.opcode {
+                OpCode::Binary | OpCode::Continue => data.extend_from_slice(payload),
+                OpCode::Close => code = Some(u16::from_be_bytes([payload[0], payload[1]])),
+                opcode => panic!("case {}: unexpected {:?}", name, opcode),
+            }
+        }
+
+        match expect {
+            Expect::Echo(expect_data, expect_code) => {
+                assert!(ok, "case {}: connection failed", name);
+                assert_eq!(data, expect_data, "case {}", name);
+                assert_eq!(code, Some(expect_code), "case {}", name);
+            }
+            Expect::Fail => {
+                assert!(!ok, "case {}: connection not failed", name);
+                assert!(data.is_empty() && code.is_none(), "case {}", name);
+            }
+        }
+    }
+}
+
+#[test]
+fn conformance() {
+    let _ = env_logger::try_init();
+
+    let cases = cases();
+    let count = cases.len();
+    let lis = TcpListener::bind(ADDR).unwrap();
+    let (tx, rx) = mpsc::channel();
+
+    let t1 = thread::spawn(move || {
+        let mut buf = vec![0u8; 0x20000];
+        for _ in 0..count {
+            let (tcp, _) = lis.accept().unwrap();
+            let ws = Endpoint::<_, Server>::accept(tcp, &mut buf, HOST, PATH).unwrap();
+            let mut ws = ws.guard();
+            ws.set_strict(true);
+
+            // echo each read as a frame, the close code is echoed by the read
+            let ok = loop {
+                match ws.read(&mut buf) {
+                    Ok(0) if ws.is_read_end() => break true,
+                    Ok(n) => ws.write_all(&buf[..n]).unwrap(),
+                    Err(e) => {
+                        debug!("server: {}", e);
+                        break false;
+                    }
+                }
+            };
+            tx.send(ok).unwrap();
+        }
+    });
+
+    let t2 = thread::spawn(move || client(ADDR, cases, rx));
+
+    t1.join().unwrap();
+    t2.join().unwrap();
+}
+
+#[tokio::test]
+async fn async_conformance() {
+    let _ = env_logger::try_init();
+
+    let cases = cases();
+    let count = cases.len();
+    let lis = tokio::net::TcpListener::bind(ASYNC_ADDR).await.unwrap();
+    let (tx, rx) = mpsc::channel();
+
+    let t1 = tokio::spawn(async move {
+        let mut buf = vec![0u8; 0x20000];
+        for _ in 0..count {
+            let (tcp, _) = lis.accept().await.unwrap();
+            let ws = Endpoint::<_, Server>::accept_async(tcp, &mut buf, HOST, PATH)
+                .await
+                .unwrap();
+            let mut ws = ws.guard();
+            ws.set_strict(true);
+
+            // echo each read as a frame, the close code is echoed by the read
+            let ok = loop {
+                match ws.read(&mut buf).await {
+                    Ok(0) if ws.is_read_end() => break true,
+                    Ok(n) => ws.write_all(&buf[..n]).await.unwrap(),
+                    Err(e) => {
+                        debug!("server: {}", e);
+                        break false;
+                    }
+                }
+            };
+            tx.send(ok).unwrap();
+        }
+    });
+
+    let t2 = tokio::task::spawn_blocking(move || client(ASYNC_ADDR, cases, rx));
+
+    t1.await.unwrap();
+    t2.await.unwrap();
+}
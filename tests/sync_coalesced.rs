@@ -0,0 +1,77 @@
+use std::io::{Read, Write};
+use std::net::{TcpStream, TcpListener};
+use std::time::Duration;
+use std::thread;
+
+use lightws::endpoint::Endpoint;
+use lightws::handshake::{HttpHeader, Request, Response, derive_accept_key};
+use lightws::role::{Client, Server};
+use lightws::stream::Stream;
+
+use log::debug;
+
+const ADDR: &str = "127.0.0.1:10000";
+const HOST: &str = "www.example.com";
+const PATH: &str = "/ws";
+
+#[test]
+fn sync_coalesced() {
+    env_logger::init();
+
+    let lis = TcpListener::bind(ADDR).unwrap();
+
+    let t1 = thread::spawn(move || {
+        let mut buf = vec![0u8; 1024];
+        let (mut tcp, _) = lis.accept().unwrap();
+        debug!("server: tcp accepted!");
+
+        // read the request, along with the first frame
+        let mut n = 0;
+        let (parsed_n, sec_key) = loop {
+            n += tcp.read(&mut buf[n..]).unwrap();
+            let mut headers = HttpHeader::new_storage();
+            let mut request = Request::new_storage(&mut headers);
+            if let Ok(parsed_n) = request.decode(&buf[..n]) {
+                break (parsed_n, request.sec_key.to_vec());
+            }
+        };
+        let leftover: Box<[u8]> = Box::from(&buf[parsed_n..n]);
+        debug!("server: {} bytes past the request", leftover.len());
+
+        // send the response and the first frame in one write
+        let sec_accept = derive_accept_key(&sec_key);
+        let mut output = vec![0u8; 1024];
+        let response_n = Response::new(&sec_accept).encode(&mut output).unwrap();
+        output.truncate(response_n);
+        output.extend_from_slice(&[0x82, 0x05]);
+        output.extend_from_slice(b"world");
+        tcp.write_all(&output).unwrap();
+        debug!("server: websocket accepted!");
+
+        let mut ws = Stream::new_with_leftover(tcp, Server, leftover).guard();
+        let mut data = [0u8; 5];
+        ws.read_exact(&mut data).unwrap();
+        assert_eq!(&data, b"hello");
+    });
+
+    let t2 = thread::spawn(|| {
+        let mut buf = vec![0u8; 1024];
+        debug!("client: sleep 500ms..");
+        thread::sleep(Duration::from_millis(500));
+        let tcp = TcpStream::connect(ADDR).unwrap();
+        debug!("client: tcp connected!");
+
+        // send the request and the first frame in one write
+        let ws = Endpoint::<_, Client>::connect_with_payload(tcp, &mut buf, HOST, PATH, b"hello")
+            .unwrap();
+        debug!("client: websocket connected!");
+
+        let mut ws = ws.guard();
+        let mut data = [0u8; 5];
+        ws.read_exact(&mut data).unwrap();
+        assert_eq!(&data, b"world");
+    });
+
+    t1.join().unwrap();
+    t2.join().unwrap();
+}
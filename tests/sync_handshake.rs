@@ -1,3 +1,4 @@
+use std::io::{Read, Write};
 use std::net::{TcpStream, TcpListener};
 use std::time::Duration;
 use std::thread;
@@ -21,8 +22,14 @@ fn sync_handshake() {
         let mut buf = vec![0u8; 1024];
         let (tcp, _) = lis.accept().unwrap();
         debug!("server: tcp accepted!");
-        let _ = Endpoint::<_, Server>::accept(tcp, &mut buf, HOST, PATH).unwrap();
+        let ws = Endpoint::<_, Server>::accept(tcp, &mut buf, HOST, PATH).unwrap();
         debug!("server: websocket accepted!");
+
+        // the first frame from the client
+        let mut ws = ws.guard();
+        let mut data = [0u8; 5];
+        ws.read_exact(&mut data).unwrap();
+        assert_eq!(&data, b"hello");
     });
 
     let t2 = thread::spawn(|| {
@@ -31,8 +38,9 @@ fn sync_handshake() {
         thread::sleep(Duration::from_millis(500));
         let tcp = TcpStream::connect(ADDR).unwrap();
         debug!("client: tcp connected!");
-        let _ = Endpoint::<_, Client>::connect(tcp, &mut buf, HOST, PATH).unwrap();
+        let mut ws = Endpoint::<_, Client>::connect(tcp, &mut buf, HOST, PATH).unwrap();
         debug!("client: websocket connected!");
+        assert_eq!(ws.write(b"hello").unwrap(), 5);
     });
 
     t1.join().unwrap();
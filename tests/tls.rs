@@ -0,0 +1,139 @@
+#![cfg(feature = "tls")]
+
+use std::io::{Read, Write};
+use std::net::{TcpStream, TcpListener};
+use std::sync::Arc;
+use std::time::Duration;
+use std::thread;
+
+use lightws::endpoint::Endpoint;
+use lightws::endpoint::tls::{rustls, tokio_rustls};
+use lightws::endpoint::tls::{ClientConfig, RootCertStore, TlsConnector};
+use lightws::role::{Client, Server};
+
+use rustls::{ServerConfig, ServerConnection, StreamOwned};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
+
+const ADDR: &str = "127.0.0.1:10000";
+const ASYNC_ADDR: &str = "127.0.0.1:10001";
+const DOMAIN: &str = "localhost";
+const HOST: &str = "www.example.com";
+const PATH: &str = "/ws";
+
+// signed by ca.der for localhost
+const CA: &[u8] = include_bytes!("cert/ca.der");
+const CERT: &[u8] = include_bytes!("cert/cert.der");
+const KEY: &[u8] = include_bytes!("cert/key.der");
+
+fn server_config() -> Arc<ServerConfig> {
+    let cert = CertificateDer::from(CERT);
+    let key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(KEY));
+    let config = ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(vec![cert], key)
+        .unwrap();
+    Arc::new(config)
+}
+
+fn client_config() -> Arc<ClientConfig> {
+    let mut roots = RootCertStore::empty();
+    roots.add(CertificateDer::from(CA)).unwrap();
+    let config = ClientConfig::builder()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    Arc::new(config)
+}
+
+#[test]
+fn connect_tls() {
+    let lis = TcpListener::bind(ADDR).unwrap();
+
+    let t1 = thread::spawn(move || {
+        let mut buf = vec![0u8; 1024];
+        let (tcp, _) = lis.accept().unwrap();
+        let conn = ServerConnection::new(server_config()).unwrap();
+        let tls = StreamOwned::new(conn, tcp);
+        let ws = Endpoint::<_, Server>::accept(tls, &mut buf, HOST, PATH).unwrap();
+
+        let mut ws = ws.guard();
+        let mut data = [0u8; 5];
+        ws.read_exact(&mut data).unwrap();
+        assert_eq!(&data, b"hello");
+
+        // the client rejects the certificate
+        let (tcp, _) = lis.accept().unwrap();
+        let conn = ServerConnection::new(server_config()).unwrap();
+        let tls = StreamOwned::new(conn, tcp);
+        assert!(Endpoint::<_, Server>::accept(tls, &mut buf, HOST, PATH).is_err());
+    });
+
+    let t2 = thread::spawn(|| {
+        let mut buf = vec![0u8; 1024];
+        thread::sleep(Duration::from_millis(500));
+        let tcp = TcpStream::connect(ADDR).unwrap();
+        let mut ws =
+            Endpoint::<_, Client>::connect_tls(tcp, client_config(), DOMAIN, &mut buf, HOST, PATH)
+                .unwrap()
+                .guard();
+        ws.write_all(b"hello").unwrap();
+        ws.flush().unwrap();
+
+        // an untrusted certificate
+        let tcp = TcpStream::connect(ADDR).unwrap();
+        let config = ClientConfig::builder()
+            .with_root_certificates(RootCertStore::empty())
+            .with_no_client_auth();
+        assert!(Endpoint::<_, Client>::connect_tls(
+            tcp,
+            Arc::new(config),
+            DOMAIN,
+            &mut buf,
+            HOST,
+            PATH
+        )
+        .is_err());
+    });
+
+    t1.join().unwrap();
+    t2.join().unwrap();
+}
+
+#[tokio::test]
+async fn connect_tls_async() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let lis = tokio::net::TcpListener::bind(ASYNC_ADDR).await.unwrap();
+
+    let t1 = tokio::spawn(async move {
+        let mut buf = vec![0u8; 1024];
+        let (tcp, _) = lis.accept().await.unwrap();
+        let acceptor = tokio_rustls::TlsAcceptor::from(server_config());
+        let tls = acceptor.accept(tcp).await.unwrap();
+        let ws = Endpoint::<_, Server>::accept_async(tls, &mut buf, HOST, PATH)
+            .await
+            .unwrap();
+
+        let mut ws = ws.guard();
+        let mut data = [0u8; 5];
+        ws.read_exact(&mut data).await.unwrap();
+        assert_eq!(&data, b"hello");
+    });
+
+    let t2 = tokio::spawn(async {
+        let mut buf = vec![0u8; 1024];
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        let tcp = tokio::net::TcpStream::connect(ASYNC_ADDR).await.unwrap();
+        let connector = TlsConnector::from(client_config());
+        let mut ws =
+            Endpoint::<_, Client>::connect_tls_async(tcp, &connector, DOMAIN, &mut buf, HOST, PATH)
+                .await
+                .unwrap()
+                .guard();
+        ws.write_all(b"hello").await.unwrap();
+        ws.flush().await.unwrap();
+    });
+
+    let (r1, r2) = tokio::join!(t1, t2);
+    r1.unwrap();
+    r2.unwrap();
+}